@@ -1,20 +1,53 @@
+use crate::catalog::Catalog;
 use crate::database;
+use crate::heap_file::HeapFile;
 use crate::heap_page::{HeapPage, HeapPageId, Permission};
 use crate::lock_manager::LockManager;
-use crate::transaction::TransactionId;
-use crate::tuple::Tuple;
-use std::collections::HashMap;
+use crate::log::LogManager;
+use crate::transaction::{DbError, IsolationLevel, TransactionAbort, TransactionId};
+use crate::tuple::{RecordId, Tuple};
+use std::collections::{HashMap, HashSet};
 
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 
 pub const PAGE_SIZE: usize = 4096;
 pub const DEFAULT_PAGES: usize = 50;
 
+// Identifies a savepoint within a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavepointId(usize);
+
+// images of the pages a transaction had dirtied when it set a savepoint
+type PageImages = HashMap<HeapPageId, Vec<u8>>;
+
+// committed versions of pages as they were when a snapshot transaction began, kept for
+// each page changed since
+type SnapshotPages = HashMap<HeapPageId, HeapPage>;
+
 // Cache of pages kept in memory
 pub struct BufferPool {
     id_to_page: RwLock<HashMap<HeapPageId, Arc<RwLock<HeapPage>>>>,
     lock_manager: LockManager,
     num_pages: usize,
+    // each transaction's savepoints, oldest first
+    savepoints: RwLock<HashMap<TransactionId, Vec<PageImages>>>,
+    // transactions started with begin_transaction that haven't committed or aborted
+    active_transactions: RwLock<HashSet<TransactionId>>,
+    // the pages preserved for each running snapshot transaction
+    snapshots: RwLock<HashMap<TransactionId, SnapshotPages>>,
+    // get_page calls served from the cache and from disk
+    cache_hits: AtomicUsize,
+    cache_misses: AtomicUsize,
+    // held by commits and aborts while they log and write their pages, and exclusively by
+    // checkpoints so they never see one half done
+    checkpoint_lock: RwLock<()>,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BufferPool {
@@ -23,89 +56,493 @@ impl BufferPool {
             id_to_page: RwLock::new(HashMap::new()),
             num_pages: DEFAULT_PAGES,
             lock_manager: LockManager::new(),
+            savepoints: RwLock::new(HashMap::new()),
+            active_transactions: RwLock::new(HashSet::new()),
+            snapshots: RwLock::new(HashMap::new()),
+            cache_hits: AtomicUsize::new(0),
+            cache_misses: AtomicUsize::new(0),
+            checkpoint_lock: RwLock::new(()),
         }
     }
 
-    // Retrieves the specified page from cache or disk
+    // Gets the number of (cache hits, cache misses) in get_page so far
+    pub fn stats(&self) -> (usize, usize) {
+        (
+            self.cache_hits.load(Ordering::Relaxed),
+            self.cache_misses.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn get_lock_manager(&self) -> &LockManager {
+        &self.lock_manager
+    }
+
+    // Lists every lock held in the pool as (page, transaction, exclusive), for debugging
+    pub fn dump_locks(&self) -> Vec<(HeapPageId, TransactionId, bool)> {
+        self.lock_manager.dump_locks()
+    }
+
+    // Starts tracking the transaction as active until it commits or aborts
+    pub fn begin_transaction(&self, tid: TransactionId) {
+        self.active_transactions.write().unwrap().insert(tid);
+    }
+
+    // Checks whether the transaction was begun and hasn't committed or aborted since
+    pub fn is_active(&self, tid: TransactionId) -> bool {
+        self.active_transactions.read().unwrap().contains(&tid)
+    }
+
+    pub fn get_active_transactions(&self) -> Vec<TransactionId> {
+        let mut tids: Vec<TransactionId> = self
+            .active_transactions
+            .read()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect();
+        tids.sort();
+        tids
+    }
+
+    // Retrieves the specified page from cache or disk, returning an error if the
+    // transaction was aborted while acquiring the lock.
+    // Reads below Serializable isolation don't keep their lock, see IsolationLevel.
+    // Cached pages are handed out as they are, so a transaction reading a page it dirtied
+    // sees its own uncommitted changes.
     pub fn get_page(
         &self,
         tid: TransactionId,
         pid: HeapPageId,
         perm: Permission,
-    ) -> Option<Arc<RwLock<HeapPage>>> {
+    ) -> Result<Arc<RwLock<HeapPage>>, TransactionAbort> {
         let exclusive = perm == Permission::Write;
-        self.lock_manager.acquire_lock(tid, pid, exclusive);
+        match tid.get_isolation() {
+            IsolationLevel::Snapshot if exclusive => {
+                self.abort_transaction(tid);
+                return Err(TransactionAbort::new(tid));
+            }
+            IsolationLevel::Snapshot => {
+                let page = self.get_snapshot_page(tid, pid)?;
+                return Ok(Arc::new(RwLock::new(page)));
+            }
+            IsolationLevel::ReadUncommitted if !exclusive => {}
+            IsolationLevel::ReadCommitted if !exclusive => {
+                // wait out any writer, but only keep the lock if it's one we already had
+                let held = self.lock_manager.holds_lock(tid, pid);
+                self.lock_manager.acquire_lock(tid, pid, false)?;
+                if held.is_none() {
+                    self.lock_manager.release_lock(tid, pid);
+                }
+            }
+            _ => self.lock_manager.acquire_lock(tid, pid, exclusive)?,
+        }
 
         {
             let id_to_page = self.id_to_page.read().unwrap();
             if id_to_page.contains_key(&pid) {
-                return Some(Arc::clone(id_to_page.get(&pid).unwrap()));
+                self.cache_hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Arc::clone(id_to_page.get(&pid).unwrap()));
             }
         }
-        // read the page from disk and saves it to the buffer pool
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        // read the page from disk and saves it to the buffer pool, at its table's page size
         let db = database::get_global_db();
         let catalog = db.get_catalog();
         let table = catalog.get_table_from_id(pid.get_table_id()).unwrap();
-        let page = table.read_page(&pid);
+        let page = table
+            .read_page(&pid)
+            .map_err(|_| self.abort_unreadable(tid, pid))?;
+        table.record_page_metadata(&page);
         let mut id_to_page = self.id_to_page.write().unwrap();
         id_to_page.insert(pid, Arc::new(RwLock::new(page)));
-        Some(Arc::clone(id_to_page.get(&pid).unwrap()))
+        Ok(Arc::clone(id_to_page.get(&pid).unwrap()))
+    }
+
+    // Returns a copy of the page as it was committed when the snapshot transaction made its
+    // first read, without taking any locks. Commits since then preserve their pages'
+    // previous versions for the snapshot before changing them.
+    pub fn get_snapshot_page(
+        &self,
+        tid: TransactionId,
+        pid: HeapPageId,
+    ) -> Result<HeapPage, TransactionAbort> {
+        if !self.snapshots.read().unwrap().contains_key(&tid) {
+            self.snapshots.write().unwrap().entry(tid).or_default();
+        }
+        let db = database::get_global_db();
+        let table = db
+            .get_catalog()
+            .get_table_from_id(pid.get_table_id())
+            .unwrap();
+        let read = {
+            // held while reading so a commit can't change the page halfway through
+            let snapshots = self.snapshots.read().unwrap();
+            if let Some(page) = snapshots.get(&tid).and_then(|pages| pages.get(&pid)) {
+                return Ok(page.clone());
+            }
+            if let Some(page) = self.id_to_page.read().unwrap().get(&pid) {
+                let page = page.read().unwrap();
+                return Ok(if page.is_dirty() {
+                    page.get_before_image()
+                } else {
+                    page.clone()
+                });
+            }
+            table.read_page(&pid)
+        };
+        read.map_err(|_| self.abort_unreadable(tid, pid))
+    }
+
+    // Forgets the pages preserved for the snapshot transaction. Commit and abort do this
+    // already; it's for a transaction that read again after it finished.
+    pub fn release_snapshot(&self, tid: TransactionId) {
+        self.snapshots.write().unwrap().remove(&tid);
+    }
+
+    // Aborts a transaction that needs a page that can't be read, e.g. because it's corrupt.
+    // There's nothing to recover the page from here.
+    fn abort_unreadable(&self, tid: TransactionId, pid: HeapPageId) -> TransactionAbort {
+        self.abort_transaction(tid);
+        TransactionAbort::unreadable_page(tid, pid)
+    }
+
+    // Keeps the page's committed version for every snapshot that hasn't already kept one
+    fn preserve_for_snapshots(
+        snapshots: &mut HashMap<TransactionId, SnapshotPages>,
+        page: &HeapPage,
+    ) {
+        if snapshots.is_empty() {
+            return;
+        }
+        let image = page.get_before_image();
+        for pages in snapshots.values_mut() {
+            pages.entry(page.get_id()).or_insert_with(|| image.clone());
+        }
     }
 
     // Commits the specified transaction, writes all dirty pages to disk, and releases all locks
     pub fn commit_transaction(&self, tid: TransactionId) {
+        self.commit(tid, true);
+    }
+
+    // Commits the specified transaction without writing its pages (NO FORCE). The log is
+    // enough to redo the changes after a crash, and the pages stay in the pool marked as
+    // committed until they're flushed or evicted.
+    pub fn commit_transaction_no_force(&self, tid: TransactionId) {
+        self.commit(tid, false);
+    }
+
+    fn commit(&self, tid: TransactionId, force: bool) {
+        let _checkpoint = self.checkpoint_lock.read().unwrap();
+        let db = database::get_global_db();
+        let log_manager = db.get_log_manager();
         let locked_pages = self.lock_manager.get_locked_pages(tid);
-        for pid in locked_pages {
-            if self.id_to_page.read().unwrap().contains_key(&pid) {
-                let id_to_page = self.id_to_page.read().unwrap();
-                let page = id_to_page.get(&pid).unwrap();
-                let mut page = page.write().unwrap();
-                if page.is_dirty() {
-                    let db = database::get_global_db();
-                    let catalog = db.get_catalog();
-                    let table = catalog.get_table_from_id(pid.get_table_id()).unwrap();
-                    table.write_page(&page);
-                    page.mark_dirty(false, tid);
-                    page.set_before_image();
-                }
+        let dirty_pages: Vec<Arc<RwLock<HeapPage>>> = {
+            let id_to_page = self.id_to_page.read().unwrap();
+            locked_pages
+                .iter()
+                .filter_map(|pid| id_to_page.get(pid))
+                .filter(|page| page.read().unwrap().is_dirty())
+                .map(Arc::clone)
+                .collect()
+        };
+        // the updates and the commit must reach the log before any page is written
+        for page in dirty_pages.iter() {
+            let page = page.read().unwrap();
+            log_manager.log_update(
+                tid,
+                page.get_id(),
+                page.get_before_image().get_page_data(),
+                page.get_page_data(),
+            );
+        }
+        log_manager.log_commit(tid);
+        let mut snapshots = self.snapshots.write().unwrap();
+        snapshots.remove(&tid);
+        for page in dirty_pages.iter() {
+            let mut page = page.write().unwrap();
+            Self::preserve_for_snapshots(&mut snapshots, &page);
+            if force {
+                let catalog = db.get_catalog();
+                let table = catalog
+                    .get_table_from_id(page.get_id().get_table_id())
+                    .unwrap();
+                table.write_page(&page);
+                page.set_committed_by(None);
+            } else {
+                page.set_committed_by(Some(tid));
             }
+            page.mark_dirty(false, tid);
+            page.set_before_image();
         }
+        drop(snapshots);
+        self.savepoints.write().unwrap().remove(&tid);
+        self.active_transactions.write().unwrap().remove(&tid);
         self.lock_manager.release_locks(tid);
     }
 
     // Aborts the specified transaction, reverting any changes made, and releases all locks
     pub fn abort_transaction(&self, tid: TransactionId) {
+        let _checkpoint = self.checkpoint_lock.read().unwrap();
         let locked_pages = self.lock_manager.get_locked_pages(tid);
+        let mut discarded = vec![];
         for pid in locked_pages {
             if self.id_to_page.read().unwrap().contains_key(&pid) {
                 let id_to_page = self.id_to_page.read().unwrap();
                 let page = id_to_page.get(&pid).unwrap();
-                let mut page = page.write().unwrap();
+                // a thread that panicked while writing the page still needs it rolled back
+                let mut page = page.write().unwrap_or_else(|e| e.into_inner());
                 if page.is_dirty() {
+                    // only the aborted transaction wrote it since it was last on disk, so
+                    // drop it and read it back fresh next time
+                    if page.get_dirtied_by() == Some(tid) && page.get_committed_by().is_none() {
+                        discarded.push(pid);
+                    }
                     // revert the page to its original state
-                    *page = page.get_before_image();
-                    page.mark_dirty(false, tid)
+                    page.revert();
+                    Self::record_page_metadata(&page);
                 }
             }
         }
+        let mut id_to_page = self.id_to_page.write().unwrap();
+        for pid in discarded {
+            id_to_page.remove(&pid);
+        }
+        drop(id_to_page);
+        let db = database::get_global_db();
+        db.get_log_manager().log_abort(tid);
+        self.snapshots.write().unwrap().remove(&tid);
+        self.savepoints.write().unwrap().remove(&tid);
+        self.active_transactions.write().unwrap().remove(&tid);
         self.lock_manager.release_locks(tid);
     }
 
-    // Adds the tuple to the specified table
-    pub fn insert_tuple(&self, tid: TransactionId, table_id: usize, tuple: Tuple) {
+    // Snapshots the pages the transaction has dirtied so far
+    pub fn set_savepoint(&self, tid: TransactionId) -> SavepointId {
+        let mut images = HashMap::new();
+        let id_to_page = self.id_to_page.read().unwrap();
+        for pid in self.lock_manager.get_locked_pages(tid) {
+            if let Some(page) = id_to_page.get(&pid) {
+                let page = page.read().unwrap();
+                if page.is_dirty() {
+                    images.insert(pid, page.get_page_data());
+                }
+            }
+        }
+        let mut savepoints = self.savepoints.write().unwrap();
+        let tid_savepoints = savepoints.entry(tid).or_default();
+        tid_savepoints.push(images);
+        SavepointId(tid_savepoints.len() - 1)
+    }
+
+    // Reverts the transaction's pages to how they were at the savepoint, discarding any
+    // savepoints set after it. Locks taken since the savepoint are kept.
+    pub fn rollback_to(&self, tid: TransactionId, sp: SavepointId) -> Result<(), String> {
+        let mut savepoints = self.savepoints.write().unwrap();
+        let tid_savepoints = savepoints
+            .get_mut(&tid)
+            .filter(|s| sp.0 < s.len())
+            .ok_or(format!(
+                "no savepoint {} for transaction {}",
+                sp.0,
+                tid.get_tid()
+            ))?;
+        tid_savepoints.truncate(sp.0 + 1);
+        let images = &tid_savepoints[sp.0];
+
+        let id_to_page = self.id_to_page.read().unwrap();
+        for pid in self.lock_manager.get_locked_pages(tid) {
+            let Some(page) = id_to_page.get(&pid) else {
+                continue;
+            };
+            let mut page = page.write().unwrap();
+            match images.get(&pid) {
+                Some(image) => page.restore(image.clone()),
+                // the page was first dirtied after the savepoint
+                None if page.is_dirty() => page.revert(),
+                None => {}
+            }
+            Self::record_page_metadata(&page);
+        }
+        Ok(())
+    }
+
+    // Lets the page's table know how much room the page has and the range of its key
+    // column after it was reverted or read in
+    fn record_page_metadata(page: &HeapPage) {
+        let db = database::get_global_db();
+        let table_id = page.get_id().get_table_id();
+        if let Some(table) = db.get_catalog().get_table_from_id(table_id) {
+            table.record_page_metadata(page);
+        }
+    }
+
+    // Writes every change committed without being forced to disk, like checkpoint, and
+    // drops the pages no transaction has locked from the pool. Locked pages stay, since
+    // their transaction may still be holding on to them from get_page, and pages with
+    // uncommitted changes keep their before image, so running transactions can carry on
+    // and still commit or abort.
+    pub fn flush_all_pages(&self) {
+        let _checkpoint = self.checkpoint_lock.write().unwrap();
+        let db = database::get_global_db();
+        let catalog = db.get_catalog();
+        self.id_to_page.write().unwrap().retain(|pid, page| {
+            let mut page = page.write().unwrap();
+            Self::write_committed(catalog, &mut page);
+            page.is_dirty() || self.lock_manager.get_lock_counts(*pid) != (0, 0)
+        });
+    }
+
+    // Writes the page's last committed version if it hasn't reached disk yet. A page also
+    // changed by a running transaction has its before image written instead.
+    fn write_committed(catalog: &Catalog, page: &mut HeapPage) {
+        if page.get_committed_by().is_none() {
+            return;
+        }
+        let table = catalog
+            .get_table_from_id(page.get_id().get_table_id())
+            .unwrap();
+        if page.is_dirty() {
+            table.write_page(&page.get_before_image());
+        } else {
+            table.write_page(page);
+        }
+        page.set_committed_by(None);
+    }
+
+    // Writes every change committed without being forced to disk, then logs a checkpoint so
+    // recovery can start from it and truncates the log before it. Pages stay in the pool;
+    // a page also changed by a running transaction has its committed version written.
+    pub fn checkpoint(&self, log_manager: &LogManager) {
+        let _checkpoint = self.checkpoint_lock.write().unwrap();
+        let db = database::get_global_db();
+        let catalog = db.get_catalog();
+        for page in self.id_to_page.read().unwrap().values() {
+            Self::write_committed(catalog, &mut page.write().unwrap());
+        }
+        log_manager.log_checkpoint();
+        log_manager.truncate();
+    }
+
+    // Writes new versions of pages outside of any transaction, e.g. for HeapFile::compact,
+    // given each page's current version and the one replacing it. The change is logged and
+    // committed under a tid of its own first, so a crash part way through is redone, and
+    // snapshot transactions keep the versions being replaced.
+    pub fn write_pages_logged(&self, table: &HeapFile, pages: &[(HeapPage, HeapPage)]) {
+        let _checkpoint = self.checkpoint_lock.read().unwrap();
+        let db = database::get_global_db();
+        let log_manager = db.get_log_manager();
+        let tid = TransactionId::new();
+        for (before, after) in pages {
+            log_manager.log_update(
+                tid,
+                after.get_id(),
+                before.get_page_data(),
+                after.get_page_data(),
+            );
+        }
+        log_manager.log_commit(tid);
+        let mut snapshots = self.snapshots.write().unwrap();
+        for (before, after) in pages {
+            Self::preserve_for_snapshots(&mut snapshots, before);
+            table.write_page(after);
+        }
+    }
+
+    // Drops the page from the pool, first writing out any changes committed without being
+    // forced to disk. Pages with uncommitted changes can't be evicted.
+    pub fn evict_page(&self, pid: HeapPageId) -> Result<(), String> {
+        let mut id_to_page = self.id_to_page.write().unwrap();
+        let Some(page) = id_to_page.get(&pid) else {
+            return Ok(());
+        };
+        let page = page.read().unwrap();
+        if page.is_dirty() {
+            return Err(format!(
+                "page {} of table {} has uncommitted changes",
+                pid.get_page_number(),
+                pid.get_table_id()
+            ));
+        }
+        if page.get_committed_by().is_some() {
+            let db = database::get_global_db();
+            let table = db
+                .get_catalog()
+                .get_table_from_id(pid.get_table_id())
+                .unwrap();
+            table.write_page(&page);
+        }
+        drop(page);
+        id_to_page.remove(&pid);
+        Ok(())
+    }
+
+    // Drops the specified page from the cache without writing it out
+    pub fn discard_page(&self, pid: HeapPageId) {
+        self.id_to_page.write().unwrap().remove(&pid);
+    }
+
+    // Drops every cached page of the specified table without writing it out
+    pub fn discard_table_pages(&self, table_id: usize) {
+        let mut id_to_page = self.id_to_page.write().unwrap();
+        id_to_page.retain(|pid, _| pid.get_table_id() != table_id);
+        for pages in self.snapshots.write().unwrap().values_mut() {
+            pages.retain(|pid, _| pid.get_table_id() != table_id);
+        }
+    }
+
+    // Adds the tuple to the specified table, see HeapFile::add_tuple
+    pub fn insert_tuple(
+        &self,
+        tid: TransactionId,
+        table_id: usize,
+        tuple: Tuple,
+    ) -> Result<(), DbError> {
         let db = database::get_global_db();
         let catalog = db.get_catalog();
         let table = catalog.get_table_from_id(table_id).unwrap();
-        table.add_tuple(tid, tuple);
+        table.add_tuple(tid, tuple)?;
+        Ok(())
     }
 
-    // TODO: Deletes the tuple from the specified table
-    pub fn delete_tuple(&mut self, tid: TransactionId, table_id: usize, tuple: Tuple) {
+    // Deletes the tuple from the specified table
+    pub fn delete_tuple(
+        &self,
+        tid: TransactionId,
+        table_id: usize,
+        tuple: Tuple,
+    ) -> Result<(), DbError> {
         let db = database::get_global_db();
         let catalog = db.get_catalog();
-        // TODO: get table by record id
         let table = catalog.get_table_from_id(table_id).unwrap();
-        table.delete_tuple(tid, tuple);
+        table.delete_tuple(tid, tuple)
+    }
+
+    // Reads the tuple stored at rid, locking its page with perm. Returns None if the slot
+    // is free or out of range, including pages past the end of the table, which aren't
+    // created by looking, and fails if the transaction is aborted while acquiring the lock.
+    pub fn get_tuple(
+        &self,
+        tid: TransactionId,
+        rid: RecordId,
+        perm: Permission,
+    ) -> Result<Option<Tuple>, TransactionAbort> {
+        let pid = rid.get_page_id();
+        let db = database::get_global_db();
+        let num_pages = db
+            .get_catalog()
+            .get_table_from_id(pid.get_table_id())
+            .map_or(0, |table| table.num_pages());
+        if pid.get_page_number() >= num_pages {
+            return Ok(None);
+        }
+        let page = self.get_page(tid, pid, perm)?;
+        let page = page.read().unwrap();
+        let slot = rid.get_tuple_no();
+        if slot >= page.get_num_slots() || !page.is_slot_used(slot) {
+            return Ok(None);
+        }
+        Ok(Some(page.get_tuple(slot).clone()))
     }
 
     // Gets the number of pages in the buffer pool
@@ -113,3 +550,413 @@ impl BufferPool {
         self.num_pages
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::{FieldVal, IntField};
+    use crate::heap_file::{create_test_heap_file, HeapFile};
+    use crate::heap_page::CHECKSUM_SIZE;
+    use crate::transaction::Transaction;
+    use crate::tuple::TupleDesc;
+    use crate::types::Type;
+    use uuid::Uuid;
+
+    // Registers a fresh single-page table with the global catalog and returns its id
+    fn create_test_table() -> usize {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let name = create_test_heap_file("bp_test", td);
+        database::get_global_db()
+            .get_catalog()
+            .get_table_id_from_name(&name)
+            .unwrap()
+    }
+
+    fn int_tuple(table_id: usize, value: i32) -> Tuple {
+        let db = database::get_global_db();
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+        Tuple::new(
+            vec![FieldVal::IntField(IntField::new(value))],
+            table.get_tuple_desc(),
+        )
+    }
+
+    #[test]
+    fn test_page_size() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let mut heap_file = HeapFile::new_in_memory(td.clone());
+        assert!(heap_file.set_page_size(8).is_err());
+        heap_file.set_page_size(8192).unwrap();
+        let table_id = heap_file.get_id();
+        let db = database::get_global_db();
+        db.get_catalog()
+            .add_table(heap_file, format!("bp_test_{}", Uuid::new_v4()));
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+
+        // tables with bigger pages share the pool with ones of the default size
+        let bp = db.get_buffer_pool();
+        let per_page = (8192 - CHECKSUM_SIZE) * 8 / (td.get_size() * 8 + 1);
+        let tid = TransactionId::new();
+        for i in 0..per_page + 1 {
+            bp.insert_tuple(tid, table_id, int_tuple(table_id, i as i32))
+                .unwrap();
+        }
+        bp.commit_transaction(tid);
+        assert_eq!(table.num_pages(), 2);
+
+        let tid = TransactionId::new();
+        let mut values = vec![];
+        for page in table.iter(tid) {
+            let page = page.unwrap();
+            let page = page.read().unwrap();
+            assert_eq!(page.get_page_size(), 8192);
+            values.extend(page.iter().map(|t| t.get_int(0).unwrap()));
+        }
+        bp.commit_transaction(tid);
+        assert_eq!(values, (0..per_page as i32 + 1).collect::<Vec<_>>());
+
+        // a file that already has default sized pages can't switch sizes
+        let mut other = HeapFile::new_in_memory(td);
+        other
+            .read_page(&HeapPageId::new(other.get_id(), 0))
+            .unwrap();
+        assert!(other.set_page_size(8192).is_err());
+    }
+
+    #[test]
+    fn test_get_page_unreadable() {
+        let bp = BufferPool::new();
+        let table_id = create_test_table();
+        // far enough past the end of the file that read_page refuses it
+        let pid = HeapPageId::new(table_id, 1_000_000);
+        for isolation in [IsolationLevel::Serializable, IsolationLevel::Snapshot] {
+            let tid = TransactionId::with_isolation(isolation);
+            bp.begin_transaction(tid);
+            let abort = bp.get_page(tid, pid, Permission::Read).unwrap_err();
+            assert_eq!(abort, TransactionAbort::unreadable_page(tid, pid));
+            assert!(!abort.is_retryable());
+            assert!(!bp.is_active(tid));
+        }
+    }
+
+    #[test]
+    fn test_cache_stats() {
+        let bp = BufferPool::new();
+        let table_id = create_test_table();
+        let tid = TransactionId::new();
+        let pid = HeapPageId::new(table_id, 0);
+        assert_eq!(bp.stats(), (0, 0));
+        bp.get_page(tid, pid, Permission::Read).unwrap();
+        assert_eq!(bp.stats(), (0, 1));
+        bp.get_page(tid, pid, Permission::Read).unwrap();
+        assert_eq!(bp.stats(), (1, 1));
+        bp.get_lock_manager().release_locks(tid);
+    }
+
+    #[test]
+    fn test_get_tuple() {
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let table_id = create_test_table();
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+        let tid = TransactionId::new();
+        let rids: Vec<RecordId> = (0..3)
+            .map(|i| table.add_tuple(tid, int_tuple(table_id, i)).unwrap())
+            .collect();
+
+        let tuple = bp
+            .get_tuple(tid, rids[1], Permission::Read)
+            .unwrap()
+            .unwrap();
+        assert_eq!(tuple.get_fields(), int_tuple(table_id, 1).get_fields());
+        assert_eq!(tuple.get_record_id(), rids[1]);
+
+        table.delete_tuple(tid, tuple).unwrap();
+        assert_eq!(bp.get_tuple(tid, rids[1], Permission::Read), Ok(None));
+        let past_end = RecordId::new(rids[0].get_page_id(), usize::MAX);
+        assert_eq!(bp.get_tuple(tid, past_end, Permission::Read), Ok(None));
+        // a page past the end of the table is neither read nor added
+        let num_pages = table.num_pages();
+        let past_last_page = RecordId::new(HeapPageId::new(table_id, num_pages), 0);
+        assert_eq!(
+            bp.get_tuple(tid, past_last_page, Permission::Read),
+            Ok(None)
+        );
+        assert_eq!(table.num_pages(), num_pages);
+        bp.commit_transaction(tid);
+
+        // an abort while locking is returned rather than looking like a free slot
+        let older = TransactionId::new();
+        let younger = TransactionId::new();
+        bp.begin_transaction(younger);
+        bp.get_page(older, rids[0].get_page_id(), Permission::Write)
+            .unwrap();
+        assert!(bp.get_tuple(younger, rids[0], Permission::Read).is_err());
+        bp.commit_transaction(older);
+    }
+
+    #[test]
+    fn test_read_committed_reader_does_not_block_writer() {
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        for (isolation, writer_blocked) in [
+            (IsolationLevel::Serializable, true),
+            (IsolationLevel::ReadCommitted, false),
+            (IsolationLevel::ReadUncommitted, false),
+        ] {
+            let table_id = create_test_table();
+            let pid = HeapPageId::new(table_id, 0);
+            let reader = TransactionId::with_isolation(isolation);
+            let writer = TransactionId::new();
+            bp.get_page(reader, pid, Permission::Read).unwrap();
+
+            // the younger writer dies instead of waiting on a reader that kept its lock
+            let result = bp.insert_tuple(writer, table_id, int_tuple(table_id, 1));
+            assert_eq!(result.is_err(), writer_blocked, "{:?}", isolation);
+            bp.commit_transaction(writer);
+            bp.commit_transaction(reader);
+        }
+    }
+
+    #[test]
+    fn test_rollback_to_savepoint() {
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let table_id = create_test_table();
+        let tid = TransactionId::new();
+        bp.insert_tuple(tid, table_id, int_tuple(table_id, 1))
+            .unwrap();
+        bp.insert_tuple(tid, table_id, int_tuple(table_id, 2))
+            .unwrap();
+        let sp = bp.set_savepoint(tid);
+        bp.insert_tuple(tid, table_id, int_tuple(table_id, 3))
+            .unwrap();
+        bp.rollback_to(tid, sp).unwrap();
+        assert!(bp.rollback_to(tid, SavepointId(1)).is_err());
+        bp.commit_transaction(tid);
+
+        // read the committed page back from disk
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+        let page = table.read_page(&HeapPageId::new(table_id, 0)).unwrap();
+        let values: Vec<Vec<FieldVal>> = page.iter().map(|t| t.get_fields()).collect();
+        let expected: Vec<Vec<FieldVal>> = [1, 2]
+            .iter()
+            .map(|v| int_tuple(table_id, *v).get_fields())
+            .collect();
+        assert_eq!(values, expected);
+        assert!(bp.rollback_to(tid, sp).is_err());
+    }
+
+    // Adds the value to the page through bp, leaving it dirtied by tid
+    fn write_value(bp: &BufferPool, tid: TransactionId, pid: HeapPageId, value: i32) {
+        let page = bp.get_page(tid, pid, Permission::Write).unwrap();
+        let mut page = page.write().unwrap();
+        page.add_tuple(int_tuple(pid.get_table_id(), value))
+            .unwrap();
+        page.mark_dirty(true, tid);
+    }
+
+    fn disk_values(pid: HeapPageId) -> Vec<i32> {
+        let db = database::get_global_db();
+        let table = db
+            .get_catalog()
+            .get_table_from_id(pid.get_table_id())
+            .unwrap();
+        let page = table.read_page(&pid).unwrap();
+        page.iter().map(|t| t.get_int(0).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_flush_all_pages() {
+        // a pool of its own, so flushing doesn't empty the one other tests are using
+        let bp = BufferPool::new();
+        let table_id = create_test_table();
+        let (pid, other) = (HeapPageId::new(table_id, 0), HeapPageId::new(table_id, 1));
+        let committed = TransactionId::new();
+        write_value(&bp, committed, pid, 7);
+        bp.commit_transaction_no_force(committed);
+        let reader = TransactionId::new();
+        bp.get_page(reader, other, Permission::Read).unwrap();
+        bp.commit_transaction(reader);
+        let running = TransactionId::new();
+        bp.begin_transaction(running);
+        write_value(&bp, running, pid, 8);
+        bp.flush_all_pages();
+
+        // only the committed change reaches disk, and the running transaction keeps its page
+        assert_eq!(disk_values(pid), vec![7]);
+        assert!(!bp.id_to_page.read().unwrap().contains_key(&other));
+        let page = bp.get_page(running, pid, Permission::Read).unwrap();
+        assert_eq!(page.read().unwrap().iter().count(), 2);
+        bp.abort_transaction(running);
+        assert_eq!(disk_values(pid), vec![7]);
+        assert_eq!(page.read().unwrap().iter().count(), 1);
+    }
+
+    #[test]
+    fn test_flush_keeps_locked_pages() {
+        let bp = BufferPool::new();
+        let table_id = create_test_table();
+        let pid = HeapPageId::new(table_id, 0);
+        let tid = TransactionId::new();
+        bp.begin_transaction(tid);
+        // opened for writing, like HeapFile::iter_mut does, but not changed yet
+        let page = bp.get_page(tid, pid, Permission::Write).unwrap();
+        bp.flush_all_pages();
+        assert!(bp.id_to_page.read().unwrap().contains_key(&pid));
+
+        // the change made through the page it already had is the one that gets committed
+        {
+            let mut page = page.write().unwrap();
+            page.add_tuple(int_tuple(table_id, 5)).unwrap();
+            page.mark_dirty(true, tid);
+        }
+        bp.commit_transaction(tid);
+        assert_eq!(disk_values(pid), vec![5]);
+    }
+
+    #[test]
+    fn test_commit_no_force() {
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let table_id = create_test_table();
+        let pid = HeapPageId::new(table_id, 0);
+        let tid = TransactionId::new();
+        bp.insert_tuple(tid, table_id, int_tuple(table_id, 5))
+            .unwrap();
+        bp.commit_transaction_no_force(tid);
+
+        // the committed tuple is in the pool but not on disk yet
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+        assert_eq!(table.read_page(&pid).unwrap().iter().count(), 0);
+        let tid = TransactionId::new();
+        let page = bp.get_page(tid, pid, Permission::Read).unwrap();
+        let values: Vec<Vec<FieldVal>> = page
+            .read()
+            .unwrap()
+            .iter()
+            .map(|t| t.get_fields())
+            .collect();
+        assert_eq!(values, vec![int_tuple(table_id, 5).get_fields()]);
+        assert!(page.read().unwrap().get_committed_by().is_some());
+        bp.commit_transaction(tid);
+
+        // evicting writes it out
+        bp.evict_page(pid).unwrap();
+        assert!(!bp.id_to_page.read().unwrap().contains_key(&pid));
+        assert_eq!(table.read_page(&pid).unwrap().iter().count(), 1);
+
+        let tid = TransactionId::new();
+        bp.insert_tuple(tid, table_id, int_tuple(table_id, 6))
+            .unwrap();
+        assert!(bp.evict_page(pid).is_err());
+        bp.commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        // a pool and log of their own, so other tests' pages and log records are left alone
+        let bp = BufferPool::new();
+        let log_path = std::env::temp_dir().join(format!("checkpoint_log_{}", Uuid::new_v4()));
+        let log_db = database::Database::new_with_log(log_path.to_str().unwrap());
+        let table_id = create_test_table();
+        let pid = HeapPageId::new(table_id, 0);
+        for value in [1, 2] {
+            let tid = TransactionId::new();
+            write_value(&bp, tid, pid, value);
+            bp.commit_transaction_no_force(tid);
+        }
+        let running = TransactionId::new();
+        bp.begin_transaction(running);
+        write_value(&bp, running, pid, 3);
+        assert_eq!(disk_values(pid), Vec::<i32>::new());
+
+        bp.checkpoint(log_db.get_log_manager());
+        // only the committed tuples reach disk, and the running transaction keeps its page
+        assert_eq!(disk_values(pid), vec![1, 2]);
+        let page = bp.get_page(running, pid, Permission::Read).unwrap();
+        assert_eq!(page.read().unwrap().iter().count(), 3);
+        assert_eq!(page.read().unwrap().get_committed_by(), None);
+        assert!(log_db
+            .get_log_manager()
+            .read_records()
+            .contains(&crate::log::LogRecord::Checkpoint));
+        bp.abort_transaction(running);
+        assert_eq!(disk_values(pid), vec![1, 2]);
+        std::fs::remove_file(log_path).unwrap();
+    }
+
+    #[test]
+    fn test_abort_discards_dirtied_pages() {
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let table_id = create_test_table();
+        let pid = HeapPageId::new(table_id, 0);
+        let tid = TransactionId::new();
+        bp.insert_tuple(tid, table_id, int_tuple(table_id, 1))
+            .unwrap();
+        bp.commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        bp.insert_tuple(tid, table_id, int_tuple(table_id, 2))
+            .unwrap();
+        let aborted = bp.get_page(tid, pid, Permission::Read).unwrap();
+        bp.abort_transaction(tid);
+        assert!(!bp.id_to_page.read().unwrap().contains_key(&pid));
+
+        // the next read comes from disk rather than the page the aborted transaction held
+        let tid = TransactionId::new();
+        let page = bp.get_page(tid, pid, Permission::Read).unwrap();
+        assert!(!Arc::ptr_eq(&page, &aborted));
+        let values: Vec<Vec<FieldVal>> = page
+            .read()
+            .unwrap()
+            .iter()
+            .map(|t| t.get_fields())
+            .collect();
+        assert_eq!(values, vec![int_tuple(table_id, 1).get_fields()]);
+        bp.commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_snapshot_pages_are_released() {
+        let bp = BufferPool::new();
+        let table_id = create_test_table();
+        let pid = HeapPageId::new(table_id, 0);
+        let has_snapshot = |tid| bp.snapshots.read().unwrap().contains_key(&tid);
+
+        // a write aborts the snapshot transaction rather than leaving it running
+        let tid = TransactionId::with_isolation(IsolationLevel::Snapshot);
+        bp.begin_transaction(tid);
+        bp.get_page(tid, pid, Permission::Read).unwrap();
+        assert!(has_snapshot(tid));
+        assert_eq!(
+            bp.get_page(tid, pid, Permission::Write).unwrap_err(),
+            TransactionAbort::new(tid)
+        );
+        assert!(!bp.is_active(tid));
+        assert!(!has_snapshot(tid));
+
+        // reading again after being aborted keeps a snapshot until the handle is dropped
+        let txn = Transaction::new(TransactionId::with_isolation(IsolationLevel::Snapshot), &bp);
+        let tid = txn.get_tid();
+        bp.begin_transaction(tid);
+        bp.abort_transaction(tid);
+        bp.get_page(tid, pid, Permission::Read).unwrap();
+        assert!(has_snapshot(tid));
+        drop(txn);
+        assert!(!has_snapshot(tid));
+
+        // dropping the table drops the versions kept of its pages
+        let tid = TransactionId::with_isolation(IsolationLevel::Snapshot);
+        bp.get_page(tid, pid, Permission::Read).unwrap();
+        let writer = TransactionId::new();
+        write_value(&bp, writer, pid, 1);
+        bp.commit_transaction_no_force(writer);
+        assert!(bp.snapshots.read().unwrap()[&tid].contains_key(&pid));
+        bp.discard_table_pages(table_id);
+        assert!(bp.snapshots.read().unwrap()[&tid].is_empty());
+        bp.commit_transaction(tid);
+        assert!(!has_snapshot(tid));
+    }
+}
@@ -1,27 +1,118 @@
+use crate::database;
 use crate::heap_file::HeapFile;
+use crate::heap_page::HeapPageId;
+use crate::statistics::Statistics;
+use crate::transaction::TransactionId;
 use crate::tuple::TupleDesc;
-use crate::types::Type::{IntType, StringType};
+use crate::types::Type;
+use crate::types::Type::{BoolType, FloatType, IntType, LongType, StringType};
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader};
-use std::sync::{Arc, RwLock};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+
+pub const CATALOG_META_FILE: &str = "data/catalog.meta";
+pub const DATA_DIR: &str = "data";
+
+// Serializes rewrites of meta files, which catalogs sharing a file would otherwise race on
+static META_LOCK: Mutex<()> = Mutex::new(());
 
 pub struct Catalog {
     // maps table name to table
     tables: RwLock<HashMap<String, Arc<HeapFile>>>,
     // maps table id to table
     table_ids: RwLock<HashMap<usize, Arc<HeapFile>>>,
+    // page images rebuilt from the log, written out once their table is added
+    recovered_pages: RwLock<HashMap<HeapPageId, Vec<u8>>>,
+    // file recording the id of every table by name, so ids stay stable across restarts
+    meta_path: String,
+    // the latest statistics gathered for each table, keyed by table id
+    statistics: RwLock<HashMap<usize, Statistics>>,
+    // directory the tables' data files are created in
+    data_dir: String,
+    // paths of the data files the catalog opened itself, keyed by table id. drop_table
+    // deletes these, tables added from a file the caller opened keep theirs.
+    paths: RwLock<HashMap<usize, String>>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Catalog {
     pub fn new() -> Self {
+        Catalog::new_with_meta(CATALOG_META_FILE)
+    }
+
+    pub fn new_with_meta(meta_path: &str) -> Self {
         Catalog {
             tables: RwLock::new(HashMap::new()),
             table_ids: RwLock::new(HashMap::new()),
+            recovered_pages: RwLock::new(HashMap::new()),
+            meta_path: meta_path.to_string(),
+            statistics: RwLock::new(HashMap::new()),
+            data_dir: DATA_DIR.to_string(),
+            paths: RwLock::new(HashMap::new()),
         }
     }
 
+    // Sets the directory the data files of tables created or loaded from a schema go in
+    pub fn set_data_dir(&mut self, data_dir: &str) {
+        self.data_dir = data_dir.to_string();
+    }
+
+    // Path of the file backing the named table in the data directory
+    fn table_path(&self, name: &str) -> String {
+        format!("{}/{}.dat", self.data_dir, name)
+    }
+
+    // Path of the table's data file, if the catalog opened it
+    pub fn get_table_path(&self, table_id: usize) -> Option<String> {
+        self.paths.read().unwrap().get(&table_id).cloned()
+    }
+
+    // Opens the named table's data file in the data directory and wraps it, reusing the
+    // id the table was given in an earlier run
+    fn open_table_file(
+        &self,
+        name: &str,
+        td: TupleDesc,
+        table_ids: &HashMap<String, usize>,
+    ) -> Result<(HeapFile, String), String> {
+        let path = self.table_path(name);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|e| format!("could not open {}: {}", path, e))?;
+        let heap_file = match table_ids.get(name) {
+            Some(id) => HeapFile::new_with_id(file, td, *id),
+            None => HeapFile::new(file, td),
+        };
+        Ok((heap_file, path))
+    }
+
+    // Adds a table whose data file the catalog opened at path
+    fn add_table_at(&self, file: HeapFile, name: String, path: String) {
+        self.paths.write().unwrap().insert(file.get_id(), path);
+        self.add_table(file, name);
+    }
+
     pub fn add_table(&self, file: HeapFile, name: String) {
+        if !file.is_in_memory() {
+            self.persist_table_id(&name, file.get_id());
+        }
+        self.restore_recovered_pages(&file);
+        self.register_table(file, name);
+    }
+
+    // Adds the table to the name and id maps
+    fn register_table(&self, file: HeapFile, name: String) {
         let mut tables = self.tables.write().unwrap();
         let file_id = file.get_id();
         tables.insert(name.clone(), Arc::new(file));
@@ -29,56 +120,746 @@ impl Catalog {
         table_ids.insert(file_id, Arc::clone(tables.get(&name).unwrap()));
     }
 
+    // Creates the table's data file if needed and registers it, returning the table's id.
+    // Creating a table that already exists just returns its id. Fails if the data file
+    // can't be opened, e.g. because the data directory doesn't exist.
+    pub fn create_table(&self, name: String, td: TupleDesc) -> Result<usize, String> {
+        if let Some(table) = self.get_table_from_name(&name) {
+            return Ok(table.get_id());
+        }
+        let (heap_file, path) = self.open_table_file(&name, td, &self.read_table_ids())?;
+        let table_id = heap_file.get_id();
+        self.add_table_at(heap_file, name, path);
+        Ok(table_id)
+    }
+
+    // Registers a table kept entirely in memory, returning its id. Its id isn't persisted
+    // and its pages are lost on restart. Creating a table that already exists just
+    // returns its id.
+    pub fn create_temp_table(&self, name: String, td: TupleDesc) -> usize {
+        if let Some(table) = self.get_table_from_name(&name) {
+            return table.get_id();
+        }
+        let heap_file = HeapFile::new_in_memory(td);
+        let table_id = heap_file.get_id();
+        self.register_table(heap_file, name);
+        table_id
+    }
+
+    // Unregisters the table and deletes its data file if the catalog opened it. The table
+    // is locked exclusively by tid first, and the drop is rejected without waiting if any
+    // other transaction holds a lock on it. The drop isn't undone if tid aborts later.
+    pub fn drop_table(&self, name: &str, tid: TransactionId) -> Result<(), String> {
+        let table = self
+            .get_table_from_name(name)
+            .ok_or(format!("no table named {}", name))?;
+        let table_id = table.get_id();
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        if !bp.get_lock_manager().try_acquire_table_lock(tid, table_id) {
+            return Err(format!("table {} is in use by another transaction", name));
+        }
+        self.tables.write().unwrap().remove(name);
+        self.table_ids.write().unwrap().remove(&table_id);
+        self.statistics.write().unwrap().remove(&table_id);
+        bp.discard_table_pages(table_id);
+        let Some(path) = self.paths.write().unwrap().remove(&table_id) else {
+            return Ok(());
+        };
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    // Reads the persisted table name to id mapping, later entries taking precedence
+    fn read_table_ids(&self) -> HashMap<String, usize> {
+        let mut ids = HashMap::new();
+        if let Ok(meta_file) = File::open(&self.meta_path) {
+            for line in BufReader::new(meta_file).lines() {
+                let line = line.unwrap();
+                if let Some((name, id)) = line.split_once(' ') {
+                    if let Ok(id) = id.parse() {
+                        ids.insert(name.to_string(), id);
+                    }
+                }
+            }
+        }
+        ids
+    }
+
+    // Records the table's id in the meta file unless it is already there. The file is
+    // rewritten with one entry per table, dropping entries older runs left for tables
+    // whose id changed, and replaced in one rename so a crash leaves the old or new file.
+    fn persist_table_id(&self, name: &str, id: usize) {
+        let _guard = META_LOCK.lock().unwrap();
+        let mut ids = self.read_table_ids();
+        if ids.get(name) == Some(&id) {
+            return;
+        }
+        ids.insert(name.to_string(), id);
+        self.write_table_ids(&ids);
+    }
+
+    fn write_table_ids(&self, ids: &HashMap<String, usize>) {
+        if let Some(parent) = Path::new(&self.meta_path).parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        let mut entries: Vec<(&String, &usize)> = ids.iter().collect();
+        entries.sort();
+        let tmp_path = format!("{}.{}.tmp", self.meta_path, std::process::id());
+        let mut meta_file = File::create(&tmp_path).unwrap();
+        for (name, id) in entries {
+            writeln!(meta_file, "{} {}", name, id).unwrap();
+        }
+        meta_file.sync_all().unwrap();
+        std::fs::rename(&tmp_path, &self.meta_path).unwrap();
+    }
+
+    // Sets the page images produced by log recovery
+    pub fn set_recovered_pages(&self, pages: HashMap<HeapPageId, Vec<u8>>) {
+        *self.recovered_pages.write().unwrap() = pages;
+    }
+
+    // Writes any recovered pages belonging to the file back to disk
+    fn restore_recovered_pages(&self, file: &HeapFile) {
+        let mut recovered_pages = self.recovered_pages.write().unwrap();
+        let pids: Vec<HeapPageId> = recovered_pages
+            .keys()
+            .filter(|pid| pid.get_table_id() == file.get_id())
+            .cloned()
+            .collect();
+        for pid in pids {
+            let data = recovered_pages.remove(&pid).unwrap();
+            file.write_page_data(pid, data);
+        }
+    }
+
     // Retrieves the table with the specified name
     pub fn get_table_from_name(&self, name: &str) -> Option<Arc<HeapFile>> {
         let tables = self.tables.read().unwrap();
         tables.get(name).map(Arc::clone)
     }
 
+    // Checks whether a table with the specified name is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.tables.read().unwrap().contains_key(name)
+    }
+
+    // Gets the id of the table with the specified name, without cloning the table's Arc
+    pub fn get_table_id_from_name(&self, name: &str) -> Option<usize> {
+        let tables = self.tables.read().unwrap();
+        tables.get(name).map(|table| table.get_id())
+    }
+
     // Retrieves the table with the specified id
     pub fn get_table_from_id(&self, id: usize) -> Option<Arc<HeapFile>> {
         let table_ids = self.table_ids.read().unwrap();
         table_ids.get(&id).map(Arc::clone)
     }
 
+    pub fn set_statistics(&self, table_id: usize, statistics: Statistics) {
+        self.statistics
+            .write()
+            .unwrap()
+            .insert(table_id, statistics);
+    }
+
+    // Retrieves the statistics from the last time the table was analyzed
+    pub fn get_statistics(&self, table_id: usize) -> Option<Statistics> {
+        self.statistics.read().unwrap().get(&table_id).cloned()
+    }
+
+    // Names of every registered table, sorted
+    pub fn list_tables(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // The name and type of each column of the named table, in schema order
+    pub fn describe_table(&self, name: &str) -> Option<Vec<(String, Type)>> {
+        let td = self.get_table_from_name(name)?.get_tuple_desc().clone();
+        let columns = (0..td.get_num_fields())
+            .map(|i| {
+                let name = td.get_field_name(i).unwrap().clone();
+                (name, td.get_field_type(i).unwrap().clone())
+            })
+            .collect();
+        Some(columns)
+    }
+
     // Retrieves the tuple descriptor for the specified table
     pub fn get_tuple_desc(&self, table_id: usize) -> Option<TupleDesc> {
         let table = self.get_table_from_id(table_id);
         table.map(|t| t.get_tuple_desc().clone())
     }
 
-    // Loads the schema from a text file
-    pub fn load_schema(&self, schema_file_path: &str) {
-        let schema_file = File::open(schema_file_path).unwrap();
-        let reader = BufReader::new(schema_file);
-        for line in reader.lines() {
-            let line = line.unwrap();
-            let split_parens: Vec<&str> = line.split('(').collect();
-            let table_name = split_parens[0].to_string().replace(' ', "");
-            let file = OpenOptions::new()
-                .create(true)
-                .read(true)
-                .write(true)
-                .open(format!("data/{}.dat", table_name));
-
-            let fields: Vec<&str> = split_parens[1].split(',').collect();
-            let mut field_types = vec![];
-            let mut field_names = vec![];
-            for field in fields.iter() {
-                let field: Vec<&str> = field.split(':').collect();
-                let field_name = field[0].to_string().replace(' ', "");
-                let field_type = field[1].to_string().replace(' ', "");
-                let field_type = field_type.replace(')', "");
-                let field_type = match field_type.as_str() {
-                    "Int" => IntType,
-                    "String" => StringType,
-                    _ => panic!("invalid field type"),
-                };
-                field_names.push(field_name);
-                field_types.push(field_type);
+    // Loads the schema from a text file, see load_schema_from_str
+    pub fn load_schema(&self, schema_file_path: &str) -> Result<(), String> {
+        let text = std::fs::read_to_string(schema_file_path)
+            .map_err(|e| format!("could not read schema {}: {}", schema_file_path, e))?;
+        self.load_schema_from_str(&text)
+    }
+
+    // Loads the tables of a schema, one per line, reusing the ids tables were given in
+    // earlier runs. Tables already in the catalog are skipped, so loading a schema again
+    // is harmless. The data directory is created if it's missing, blank lines are skipped
+    // and a malformed line fails with the line in the error. Every line is parsed and
+    // every data file opened before any table is added, so a failure adds none of them.
+    pub fn load_schema_from_str(&self, text: &str) -> Result<(), String> {
+        let mut parsed = vec![];
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let table = parse_schema_line(line).map_err(|e| format!("{} in line {:?}", e, line))?;
+            parsed.push(table);
+        }
+        let table_ids = self.read_table_ids();
+        std::fs::create_dir_all(&self.data_dir)
+            .map_err(|e| format!("could not create {}: {}", self.data_dir, e))?;
+        let mut opened = vec![];
+        for (table_name, td, primary_key) in parsed {
+            let listed = opened.iter().any(|(_, name, _)| *name == table_name);
+            if listed || self.get_table_from_name(&table_name).is_some() {
+                continue;
+            }
+            let (mut heap_file, path) = self.open_table_file(&table_name, td, &table_ids)?;
+            if let Some(key) = primary_key {
+                heap_file.set_primary_key(key);
+            }
+            opened.push((heap_file, table_name, path));
+        }
+        for (heap_file, table_name, path) in opened {
+            self.add_table_at(heap_file, table_name, path);
+        }
+        Ok(())
+    }
+}
+
+// Parses one line of a schema, e.g. "people (id: Int*, name: String(32), age: Int? = 18)",
+// into the table's name, its TupleDesc and the index of its primary key if it has one
+fn parse_schema_line(line: &str) -> Result<(String, TupleDesc, Option<usize>), String> {
+    let (table_name, fields) = line.split_once('(').ok_or("missing '('")?;
+    let table_name = table_name.to_string().replace(' ', "");
+    if table_name.is_empty() {
+        return Err("missing table name".to_string());
+    }
+    let fields = fields.trim_end().strip_suffix(')').ok_or("missing ')'")?;
+
+    let mut field_types = vec![];
+    let mut field_names = vec![];
+    let mut nullable = vec![];
+    let mut string_lens = vec![];
+    let mut defaults = vec![];
+    let mut primary_key = None;
+    for field in fields.split(',') {
+        let (field_name, field_type) = field
+            .split_once(':')
+            .ok_or(format!("field {:?} has no type", field.trim()))?;
+        let field_name = field_name.to_string().replace(' ', "");
+        // a default follows an '=', e.g. "active: Int = 1"
+        let (field_type, default) = match field_type.split_once('=') {
+            Some((field_type, default)) => (field_type, Some(default.trim())),
+            None => (field_type, None),
+        };
+        let field_type = field_type.to_string().replace(' ', "");
+        // a trailing '*' or PRIMARY KEY marks the primary key, e.g. "id: Int*"
+        let field_type = match field_type
+            .strip_suffix('*')
+            .or(field_type.strip_suffix("PRIMARYKEY"))
+        {
+            Some(field_type) => {
+                primary_key = Some(field_names.len());
+                field_type.to_string()
+            }
+            None => field_type,
+        };
+        // a trailing '?' marks the column as nullable, e.g. "nickname: String?"
+        let is_nullable = field_type.ends_with('?');
+        let field_type = field_type.trim_end_matches('?');
+        // strings can declare their max length, e.g. "name: String(32)"
+        let (field_type, string_len) = match field_type.split_once('(') {
+            Some((field_type, len)) => {
+                let len = len
+                    .trim_end_matches(')')
+                    .parse()
+                    .map_err(|_| format!("invalid length for field {}", field_name))?;
+                (field_type, Some(len))
+            }
+            None => (field_type, None),
+        };
+        let field_type = match field_type {
+            "Int" => IntType,
+            "String" => StringType,
+            "Float" => FloatType,
+            "Bool" => BoolType,
+            "Long" => LongType,
+            _ => {
+                return Err(format!(
+                    "invalid type {:?} for field {}",
+                    field_type, field_name
+                ))
             }
-            let heap_file = HeapFile::new(file.unwrap(), TupleDesc::new(field_types, field_names));
-            self.add_table(heap_file, table_name);
+        };
+        field_names.push(field_name);
+        field_types.push(field_type);
+        nullable.push(is_nullable);
+        string_lens.push(string_len);
+        defaults.push(default);
+    }
+    if let Some(key) = primary_key.filter(|key| nullable[*key]) {
+        return Err(format!(
+            "primary key {} can't be nullable",
+            field_names[key]
+        ));
+    }
+    let mut td = TupleDesc::new_with_nullable(field_types, field_names, nullable);
+    for (i, len) in string_lens.into_iter().enumerate() {
+        if let Some(len) = len {
+            td.set_string_len(i, len);
+        }
+    }
+    for (i, default) in defaults.into_iter().enumerate() {
+        if let Some(default) = default {
+            let value = td
+                .coerce_field(i, default)
+                .map_err(|e| format!("invalid default: {}", e))?;
+            td.set_default(i, value)?;
+        }
+    }
+    Ok((table_name, td, primary_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_pool::PAGE_SIZE;
+    use crate::fields::{FieldVal, IntField, StringField};
+    use crate::heap_file::create_test_heap_file;
+    use crate::heap_page::HeapPage;
+    use crate::table::{Predicate, Table};
+    use crate::transaction::DbError;
+    use crate::tuple::{RecordId, Tuple, TupleBuilder};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_table_ids_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("catalog_test_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir(&dir).unwrap();
+        let schema_path = dir.join("schemas.txt");
+        std::fs::write(&schema_path, "people (id: Int, name: String)\n").unwrap();
+        let open_catalog = || {
+            let mut catalog = Catalog::new_with_meta(dir.join("catalog.meta").to_str().unwrap());
+            catalog.set_data_dir(dir.join("data").to_str().unwrap());
+            catalog.load_schema(schema_path.to_str().unwrap()).unwrap();
+            catalog
+        };
+
+        let catalog = open_catalog();
+        let table = catalog.get_table_from_name("people").unwrap();
+        let table_id = table.get_id();
+        let pid = HeapPageId::new(table_id, 0);
+        let mut tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(7)),
+                FieldVal::StringField(StringField::new("Alice".to_string(), 5)),
+            ],
+            table.get_tuple_desc(),
+        );
+        let mut page = HeapPage::new(pid, vec![0; PAGE_SIZE], table.get_tuple_desc().clone());
+        page.add_tuple(tuple.clone()).unwrap();
+        table.write_page(&page);
+
+        // a fresh catalog, as after a restart, gives the table the same id
+        let catalog = open_catalog();
+        let reloaded = catalog.get_table_from_id(table_id).unwrap();
+        let page = reloaded.read_page(&pid).unwrap();
+        tuple.set_record_id(RecordId::new(pid, 0));
+        assert_eq!(page.iter().cloned().collect::<Vec<_>>(), vec![tuple]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_schema_creates_data_dir() {
+        let dir = std::env::temp_dir().join(format!("schema_test_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir(&dir).unwrap();
+        let schema_path = dir.join("schemas.txt");
+        std::fs::write(&schema_path, "people (id: Int, name: String)\n\n").unwrap();
+        let data_dir = dir.join("data");
+        let mut catalog = Catalog::new_with_meta(dir.join("catalog.meta").to_str().unwrap());
+        catalog.set_data_dir(data_dir.to_str().unwrap());
+        catalog.load_schema(schema_path.to_str().unwrap()).unwrap();
+        assert_eq!(catalog.list_tables(), vec!["people".to_string()]);
+        assert!(data_dir.join("people.dat").exists());
+
+        for line in ["people id: Int", "people (id Int)", "people (id: Integer)"] {
+            std::fs::write(&schema_path, line).unwrap();
+            let err = catalog
+                .load_schema(schema_path.to_str().unwrap())
+                .unwrap_err();
+            assert!(err.contains(line), "{}", err);
+        }
+        assert!(catalog
+            .load_schema(dir.join("missing.txt").to_str().unwrap())
+            .is_err());
+        // a bad line after a good one adds neither
+        assert!(catalog
+            .load_schema_from_str("pets (id: Int)\npets id: Int")
+            .is_err());
+        assert_eq!(catalog.list_tables(), vec!["people".to_string()]);
+        assert!(!data_dir.join("pets.dat").exists());
+
+        // dropping deletes the file in the catalog's data directory
+        let tid = TransactionId::new();
+        catalog.drop_table("people", tid).unwrap();
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+        assert!(!data_dir.join("people.dat").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_schema_twice() {
+        let dir = std::env::temp_dir().join(format!("schema_test_{}", Uuid::new_v4().simple()));
+        let mut catalog = Catalog::new_with_meta(dir.join("catalog.meta").to_str().unwrap());
+        catalog.set_data_dir(dir.join("data").to_str().unwrap());
+        catalog
+            .load_schema_from_str("people (id: Int, name: String)")
+            .unwrap();
+        let people = catalog.get_table_from_name("people").unwrap();
+        catalog
+            .load_schema_from_str("people (id: Int, name: String)\npets (id: Int)")
+            .unwrap();
+        assert_eq!(catalog.list_tables().len(), 2);
+        let again = catalog.get_table_from_name("people").unwrap();
+        assert_eq!(again.get_id(), people.get_id());
+        assert!(Arc::ptr_eq(&again, &people));
+        assert!(catalog.get_table_from_id(people.get_id()).is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_schema_defaults() {
+        let name = format!("defaults_test_{}", Uuid::new_v4().simple());
+        let db = database::get_global_db();
+        db.get_catalog()
+            .load_schema_from_str(&format!(
+                "{} (id: Int, active: Int = 1, note: String(16)? = none)",
+                name
+            ))
+            .unwrap();
+        let table = db.get_catalog().get_table_from_name(&name).unwrap();
+        let td = table.get_tuple_desc().clone();
+        assert_eq!(td.get_default(0), None);
+
+        let tid = TransactionId::new();
+        let tuple = TupleBuilder::new(&td)
+            .set("id", FieldVal::IntField(IntField::new(7)))
+            .build()
+            .unwrap();
+        table.add_tuple(tid, tuple).unwrap();
+        db.get_buffer_pool().commit_transaction(tid);
+        // the defaults were written to the table's file
+        let page = table
+            .read_page(&HeapPageId::new(table.get_id(), 0))
+            .unwrap();
+        let tuple = page.iter().next().unwrap();
+        assert_eq!(tuple.get_int(1), Some(1));
+        assert_eq!(tuple.get_string(2), Some("none".to_string()));
+        // a column without a default still has to be set
+        assert!(TupleBuilder::new(&td).build().is_err());
+
+        for line in ["bad (active: Int = yes)", "bad (name: String(2) = long)"] {
+            let err = db.get_catalog().load_schema_from_str(line).unwrap_err();
+            assert!(err.contains("invalid default"), "{}", err);
+        }
+        let table_id = db.get_catalog().get_table_id_from_name(&name).unwrap();
+        std::fs::remove_file(db.get_catalog().get_table_path(table_id).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_list_and_describe_tables() {
+        let name = format!("describe_test_{}", Uuid::new_v4().simple());
+        let schema_path = std::env::temp_dir().join(format!("{}.txt", name));
+        let schema = format!(
+            "{0}_a (id: Int, name: String(32))\n{0}_b (price: Float, active: Bool?)\n",
+            name
+        );
+        std::fs::write(&schema_path, schema).unwrap();
+        let meta_path = std::env::temp_dir().join(format!("{}.meta", name));
+        let catalog = Catalog::new_with_meta(meta_path.to_str().unwrap());
+        catalog.load_schema(schema_path.to_str().unwrap()).unwrap();
+
+        let (a, b) = (format!("{}_a", name), format!("{}_b", name));
+        assert_eq!(catalog.list_tables(), vec![a.clone(), b.clone()]);
+        assert_eq!(
+            catalog.describe_table(&a),
+            Some(vec![
+                ("id".to_string(), IntType),
+                ("name".to_string(), StringType)
+            ])
+        );
+        assert_eq!(catalog.describe_table(&b).unwrap()[1].1, BoolType);
+        assert_eq!(catalog.describe_table("missing"), None);
+
+        for table in [a, b] {
+            let table_id = catalog.get_table_id_from_name(&table).unwrap();
+            std::fs::remove_file(catalog.get_table_path(table_id).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_create_and_drop_table() {
+        let db = database::get_global_db();
+        let catalog = db.get_catalog();
+        let name = format!("ddl_test_{}", Uuid::new_v4().simple());
+        let td = TupleDesc::new(vec![IntType], vec!["id".to_string()]);
+        let table_id = catalog.create_table(name.clone(), td.clone()).unwrap();
+        assert_eq!(catalog.create_table(name.clone(), td.clone()), Ok(table_id));
+        let path = catalog.get_table_path(table_id).unwrap();
+        assert!(Path::new(&path).exists());
+
+        let table = catalog.get_table_from_name(&name).unwrap();
+        let older = TransactionId::new();
+        let tid = TransactionId::new();
+        let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(1))], &td);
+        table.add_tuple(tid, tuple).unwrap();
+        // the insert's locks are still held, so droppers are turned away without waiting,
+        // older or younger, and without being aborted
+        let bp = db.get_buffer_pool();
+        for dropper in [older, TransactionId::new()] {
+            bp.begin_transaction(dropper);
+            let err = catalog.drop_table(&name, dropper).unwrap_err();
+            assert!(err.contains("in use"), "{}", err);
+            assert!(bp.is_active(dropper));
+            assert!(bp
+                .get_lock_manager()
+                .dump_locks()
+                .iter()
+                .all(|l| l.1 != dropper));
+            bp.commit_transaction(dropper);
         }
+        assert!(catalog.get_table_from_name(&name).is_some());
+        bp.commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        catalog.drop_table(&name, tid).unwrap();
+        bp.commit_transaction(tid);
+        assert!(catalog.get_table_from_name(&name).is_none());
+        assert!(catalog.get_table_from_id(table_id).is_none());
+        assert!(!Path::new(&path).exists());
+        assert!(catalog.drop_table(&name, TransactionId::new()).is_err());
+
+        // a table added from a file the caller opened keeps its file
+        let name = create_test_heap_file("ddl_test", td.clone());
+        let table_id = catalog.get_table_id_from_name(&name).unwrap();
+        assert_eq!(catalog.get_table_path(table_id), None);
+        let path = std::env::temp_dir().join(format!("{}.dat", name));
+        let tid = TransactionId::new();
+        catalog.drop_table(&name, tid).unwrap();
+        bp.commit_transaction(tid);
+        assert!(path.exists());
+        std::fs::remove_file(path).unwrap();
+
+        // a data file that can't be opened is an error rather than a panic
+        let dir = std::env::temp_dir().join(format!("ddl_test_{}", Uuid::new_v4().simple()));
+        let mut catalog = Catalog::new_with_meta(dir.join("catalog.meta").to_str().unwrap());
+        catalog.set_data_dir(dir.join("missing").to_str().unwrap());
+        let err = catalog.create_table("people".to_string(), td).unwrap_err();
+        assert!(err.contains("could not open"), "{}", err);
+        assert!(catalog.get_table_from_name("people").is_none());
+    }
+
+    #[test]
+    fn test_primary_key_rejects_duplicates() {
+        let name = format!("pk_test_{}", Uuid::new_v4().simple());
+        let schema_path = std::env::temp_dir().join(format!("{}.txt", name));
+        std::fs::write(
+            &schema_path,
+            format!("{} (id: Int PRIMARY KEY, name: String)\n", name),
+        )
+        .unwrap();
+        let db = database::get_global_db();
+        db.get_catalog()
+            .load_schema(schema_path.to_str().unwrap())
+            .unwrap();
+        let table = db.get_catalog().get_table_from_name(&name).unwrap();
+        assert_eq!(table.get_primary_key(), Some(0));
+
+        let person = |id, name: &str| {
+            Tuple::new(
+                vec![
+                    FieldVal::IntField(IntField::new(id)),
+                    FieldVal::StringField(StringField::new(name.to_string(), name.len() as u32)),
+                ],
+                table.get_tuple_desc(),
+            )
+        };
+        let tid = TransactionId::new();
+        table.add_tuple(tid, person(1, "Alice")).unwrap();
+        table.add_tuple(tid, person(2, "Alice")).unwrap();
+        let err = table.add_tuple(tid, person(1, "Bob")).unwrap_err();
+        assert!(err.to_string().contains("duplicate"));
+        db.get_buffer_pool().commit_transaction(tid);
+
+        // of two transactions inserting the same new key at once, only one gets it in
+        let results: Vec<Result<(), DbError>> = std::thread::scope(|s| {
+            let handles: Vec<_> = ["Carol", "Dave"]
+                .map(|name| {
+                    let (db, table, person) = (&db, &table, &person);
+                    s.spawn(move || {
+                        db.run_transaction(|tid| {
+                            table.add_tuple(tid, person(3, name))?;
+                            std::thread::sleep(std::time::Duration::from_millis(20));
+                            Ok(())
+                        })
+                    })
+                })
+                .into_iter()
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(
+            results.iter().filter(|r| r.is_ok()).count(),
+            1,
+            "{:?}",
+            results
+        );
+        let err = results.into_iter().find_map(|r| r.err()).unwrap();
+        assert!(err.to_string().contains("duplicate"), "{}", err);
+        let tid = TransactionId::new();
+        let threes = table
+            .iter(tid)
+            .flat_map(|page| {
+                page.unwrap()
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .filter(|t| t.get_int(0) == Some(3))
+            .count();
+        assert_eq!(threes, 1);
+        db.get_buffer_pool().commit_transaction(tid);
+
+        // updates can't take a key that's in use or give one to several tuples
+        let people = Table::new(name.clone(), String::new()).unwrap();
+        let tid = TransactionId::new();
+        let set_id = |predicate, id| {
+            people.update_where("id", predicate, FieldVal::IntField(IntField::new(id)), tid)
+        };
+        let err = set_id(Predicate::EqualsInt(2), 1).unwrap_err();
+        assert!(err.to_string().contains("duplicate"), "{}", err);
+        assert!(set_id(Predicate::GreaterThan(0), 10).is_err());
+        assert_eq!(set_id(Predicate::EqualsInt(2), 2), Ok(1));
+        assert_eq!(set_id(Predicate::EqualsInt(2), 4), Ok(1));
+        let four = people
+            .scan_all(tid)
+            .unwrap()
+            .find(|t| t.get_int(0) == Some(4))
+            .unwrap();
+        let err = people
+            .update_tuple(four.get_record_id(), person(1, "Bob").get_fields(), tid)
+            .unwrap_err();
+        assert!(err.to_string().contains("duplicate"), "{}", err);
+        let mut ids: Vec<i32> = people
+            .scan_all(tid)
+            .unwrap()
+            .map(|t| t.get_int(0).unwrap())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 3, 4]);
+
+        // other keys and NULL can be checked while tid holds its locks, without waiting
+        let other = TransactionId::new();
+        let int = |id| FieldVal::IntField(IntField::new(id));
+        assert_eq!(table.check_primary_key(other, &int(5), None), Ok(()));
+        assert_eq!(
+            table.check_primary_key(other, &FieldVal::Null, None),
+            Ok(())
+        );
+        db.get_buffer_pool().commit_transaction(other);
+        db.get_buffer_pool().commit_transaction(tid);
+        // and a nullable column can't be the primary key
+        let err = parse_schema_line("pets (id: Int? PRIMARY KEY)").unwrap_err();
+        assert!(err.contains("nullable"), "{}", err);
+
+        let tid = TransactionId::new();
+        db.get_catalog().drop_table(&name, tid).unwrap();
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_persist_table_ids() {
+        let dir = std::env::temp_dir().join(format!("meta_test_{}", Uuid::new_v4().simple()));
+        let meta_path = dir.join("catalog.meta");
+        std::fs::create_dir(&dir).unwrap();
+        // an old run left two entries for the same table
+        std::fs::write(&meta_path, "people 1\npeople 2\npets 3\n").unwrap();
+        let catalog = Catalog::new_with_meta(meta_path.to_str().unwrap());
+        assert_eq!(catalog.read_table_ids().get("people"), Some(&2));
+
+        let td = TupleDesc::new(vec![IntType], vec!["id".to_string()]);
+        let file = File::create(dir.join("people.dat")).unwrap();
+        catalog.add_table(
+            HeapFile::new_with_id(file, td.clone(), 4),
+            "people".to_string(),
+        );
+        assert_eq!(
+            std::fs::read_to_string(&meta_path).unwrap(),
+            "people 4\npets 3\n"
+        );
+        // tables kept in memory don't outlive the process, so their ids aren't recorded
+        catalog.add_table(HeapFile::new_in_memory(td), "scratch".to_string());
+        assert_eq!(catalog.read_table_ids().get("scratch"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_create_temp_table() {
+        let name = format!("temp_{}", Uuid::new_v4());
+        let td = TupleDesc::new(vec![IntType], vec!["id".to_string()]);
+        let db = database::get_global_db();
+        let table_id = db.get_catalog().create_temp_table(name.clone(), td.clone());
+        assert_eq!(
+            db.get_catalog().create_temp_table(name.clone(), td.clone()),
+            table_id
+        );
+        assert_eq!(
+            db.get_catalog().get_table_id_from_name(&name),
+            Some(
+                db.get_catalog()
+                    .get_table_from_name(&name)
+                    .unwrap()
+                    .get_id()
+            )
+        );
+        assert_eq!(
+            db.get_catalog().get_table_id_from_name("no_such_table"),
+            None
+        );
+
+        let tid = TransactionId::new();
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+        for i in 0..3 {
+            let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td);
+            table.add_tuple(tid, tuple).unwrap();
+        }
+        db.get_buffer_pool().commit_transaction(tid);
+        db.get_buffer_pool().discard_table_pages(table_id);
+
+        // the committed page is read back from memory rather than a data file
+        let page = table.read_page(&HeapPageId::new(table_id, 0)).unwrap();
+        let ids: Vec<i32> = page.iter().map(|t| t.get_int(0).unwrap()).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert_eq!(table.num_pages(), 1);
+        assert_eq!(db.get_catalog().get_table_path(table_id), None);
+        assert!(!Path::new(&format!("{}/{}.dat", DATA_DIR, name)).exists());
+        assert!(!Catalog::new().read_table_ids().contains_key(&name));
     }
 }
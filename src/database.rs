@@ -1,7 +1,17 @@
 use crate::buffer_pool::BufferPool;
 use crate::catalog::Catalog;
+use crate::log::{LogManager, LOG_FILE};
+use crate::transaction::{DbError, Transaction, TransactionId};
 use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// How many times run_transaction retries an aborted transaction by default
+pub const DEFAULT_MAX_RETRIES: usize = 10;
+// How long run_transaction waits before its first retry, doubled for each one after
+pub const RETRY_BACKOFF: Duration = Duration::from_millis(10);
 
 lazy_static! {
     // Global database instance
@@ -16,13 +26,35 @@ pub fn get_global_db() -> Arc<Database> {
 pub struct Database {
     buffer_pool: BufferPool,
     catalog: Catalog,
+    log_manager: LogManager,
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Database {
     pub fn new() -> Self {
+        Database::new_with_log(LOG_FILE)
+    }
+
+    // Creates a database backed by the log at log_path, recovering from it first.
+    // Recovered pages are written out as their tables are added to the catalog, and the
+    // tids in the log aren't reused.
+    pub fn new_with_log(log_path: &str) -> Self {
+        let log_manager = LogManager::new(log_path);
+        let records = log_manager.read_records();
+        if let Some(tid) = records.iter().filter_map(|r| r.get_tid()).max() {
+            TransactionId::skip_past(tid.get_tid());
+        }
+        let catalog = Catalog::new();
+        catalog.set_recovered_pages(log_manager.recover());
         Database {
             buffer_pool: BufferPool::new(),
-            catalog: Catalog::new(),
+            catalog,
+            log_manager,
         }
     }
 
@@ -33,4 +65,240 @@ impl Database {
     pub fn get_catalog(&self) -> &Catalog {
         &self.catalog
     }
+
+    pub fn get_log_manager(&self) -> &LogManager {
+        &self.log_manager
+    }
+
+    // Starts a new transaction, which is aborted if it's dropped before committing
+    pub fn begin_transaction(&self) -> Transaction<'_> {
+        let tid = TransactionId::new();
+        self.buffer_pool.begin_transaction(tid);
+        Transaction::new(tid, &self.buffer_pool)
+    }
+
+    // Runs f in a new transaction and commits it, retrying with exponential backoff if the
+    // transaction is aborted, e.g. by WAIT-DIE, up to DEFAULT_MAX_RETRIES times
+    pub fn run_transaction<F>(&self, f: F) -> Result<(), DbError>
+    where
+        F: Fn(TransactionId) -> Result<(), DbError>,
+    {
+        self.run_transaction_with_retries(DEFAULT_MAX_RETRIES, f)
+    }
+
+    // Like run_transaction, but gives up after max_retries retries and returns the last
+    // abort. Any other error from f aborts the transaction and is returned without
+    // retrying. Every attempt runs under the first one's tid, so under WAIT-DIE a retried
+    // transaction keeps its age and ends up the oldest rather than being aborted forever.
+    pub fn run_transaction_with_retries<F>(&self, max_retries: usize, f: F) -> Result<(), DbError>
+    where
+        F: Fn(TransactionId) -> Result<(), DbError>,
+    {
+        let tid = TransactionId::new();
+        let mut backoff = RETRY_BACKOFF;
+        let mut retries = 0;
+        loop {
+            self.buffer_pool.begin_transaction(tid);
+            let txn = Transaction::new(tid, &self.buffer_pool);
+            let result = match f(tid) {
+                Ok(()) => txn.commit().map_err(DbError::from),
+                Err(e) => {
+                    // aborts the transaction unless the error already did
+                    drop(txn);
+                    Err(e)
+                }
+            };
+            match result {
+                Err(DbError::Abort(abort)) if abort.is_retryable() && retries < max_retries => {}
+                result => return result,
+            }
+            retries += 1;
+            std::thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+
+    // Writes out every committed change and truncates the log, see BufferPool::checkpoint
+    pub fn checkpoint(&self) {
+        self.buffer_pool.checkpoint(&self.log_manager);
+    }
+
+    // Starts a thread that checkpoints every interval until the returned handle is stopped
+    pub fn start_checkpointer(self: &Arc<Self>, interval: Duration) -> Checkpointer {
+        let db = Arc::clone(self);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread_stopped = Arc::clone(&stopped);
+        let handle = std::thread::spawn(move || loop {
+            let deadline = Instant::now() + interval;
+            // parked threads can wake early, so wait out the rest of the interval
+            while !thread_stopped.load(Ordering::SeqCst) && Instant::now() < deadline {
+                std::thread::park_timeout(deadline.saturating_duration_since(Instant::now()));
+            }
+            if thread_stopped.load(Ordering::SeqCst) {
+                return;
+            }
+            db.checkpoint();
+        });
+        Checkpointer { stopped, handle }
+    }
+
+    // Writes out every committed change still in the buffer pool, should be called before
+    // the process exits
+    pub fn shutdown(&self) {
+        self.buffer_pool.flush_all_pages();
+    }
+}
+
+// Handle to the thread started by Database::start_checkpointer
+pub struct Checkpointer {
+    stopped: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Checkpointer {
+    // Stops the thread, waiting for a checkpoint in progress to finish
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.handle.thread().unpark();
+        self.handle.join().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::{FieldVal, IntField};
+    use crate::log::LogRecord;
+    use crate::transaction::TransactionAbort;
+    use crate::tuple::{Tuple, TupleDesc};
+    use crate::types::Type;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_run_transaction_retries_aborts() {
+        let db = get_global_db();
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let name = format!("retry_{}", Uuid::new_v4());
+        let table_id = db.get_catalog().create_temp_table(name, td.clone());
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+
+        // every thread writes the same page, so the younger ones keep getting aborted
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let (db, table, td) = (&db, &table, &td);
+                s.spawn(move || {
+                    db.run_transaction(|tid| {
+                        for i in 0..5 {
+                            let value = FieldVal::IntField(IntField::new(t * 10 + i));
+                            table.add_tuple(tid, Tuple::new(vec![value], td))?;
+                            std::thread::sleep(Duration::from_millis(5));
+                        }
+                        Ok(())
+                    })
+                    .unwrap();
+                });
+            }
+        });
+        let tid = TransactionId::new();
+        assert_eq!(table.num_tuples(tid), Ok(20));
+        db.get_buffer_pool().commit_transaction(tid);
+
+        // updates and deletes of the same page are retried the same way
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let (db, table, td) = (&db, &table, &td);
+                s.spawn(move || {
+                    db.run_transaction(|tid| {
+                        let mut mine = vec![];
+                        for page in table.iter(tid) {
+                            let page = page?;
+                            let page = page.read().unwrap();
+                            mine.extend(
+                                page.iter()
+                                    // the tuples this thread inserted above
+                                    .filter(|tuple| tuple.get_int(0).unwrap() / 10 == t)
+                                    .cloned(),
+                            );
+                        }
+                        for tuple in mine {
+                            if t % 2 == 0 {
+                                let value = tuple.get_int(0).unwrap() + 100;
+                                let mut updated =
+                                    Tuple::new(vec![FieldVal::IntField(IntField::new(value))], td);
+                                updated.set_record_id(tuple.get_record_id());
+                                table.update_tuple(tid, updated)?;
+                            } else {
+                                table.delete_tuple(tid, tuple)?;
+                            }
+                            std::thread::sleep(Duration::from_millis(5));
+                        }
+                        Ok(())
+                    })
+                    .unwrap();
+                });
+            }
+        });
+        let tid = TransactionId::new();
+        let mut values = vec![];
+        for page in table.iter(tid) {
+            values.extend(
+                page.unwrap()
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|t| t.get_int(0).unwrap()),
+            );
+        }
+        db.get_buffer_pool().commit_transaction(tid);
+        values.sort();
+        assert_eq!(
+            values,
+            vec![100, 101, 102, 103, 104, 120, 121, 122, 123, 124]
+        );
+
+        // retries keep the first attempt's tid
+        let tids = std::sync::Mutex::new(vec![]);
+        db.run_transaction(|tid| {
+            let mut tids = tids.lock().unwrap();
+            tids.push(tid);
+            if tids.len() == 1 {
+                db.get_buffer_pool().abort_transaction(tid);
+                return Err(TransactionAbort::new(tid).into());
+            }
+            Ok(())
+        })
+        .unwrap();
+        let tids = tids.into_inner().unwrap();
+        assert_eq!(tids.len(), 2);
+        assert_eq!(tids[0], tids[1]);
+
+        // errors that aren't aborts aren't retried
+        let attempts = std::sync::atomic::AtomicUsize::new(0);
+        let result = db.run_transaction(|_| {
+            attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(DbError::Other("bad input".to_string()))
+        });
+        assert_eq!(result, Err(DbError::Other("bad input".to_string())));
+        assert_eq!(attempts.into_inner(), 1);
+    }
+
+    #[test]
+    fn test_start_checkpointer() {
+        let log_path = std::env::temp_dir().join(format!("log_{}", Uuid::new_v4()));
+        let db = Arc::new(Database::new_with_log(log_path.to_str().unwrap()));
+        let tid = TransactionId::new();
+        db.get_log_manager().log_commit(tid);
+        let checkpointer = db.start_checkpointer(Duration::from_millis(5));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while db.get_log_manager().read_records() != vec![LogRecord::Checkpoint] {
+            assert!(Instant::now() < deadline, "no checkpoint was taken");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        checkpointer.stop();
+        // the finished transaction before the checkpoint was truncated away
+        assert_eq!(
+            db.get_log_manager().read_records(),
+            vec![LogRecord::Checkpoint]
+        );
+    }
 }
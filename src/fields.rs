@@ -1,13 +1,34 @@
 use crate::types::{Type, STRING_SIZE};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 
 // Wrapper for different types of fields
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum FieldVal {
     IntField(IntField),
     StringField(StringField),
+    FloatField(FloatField),
+    BoolField(BoolField),
+    LongField(LongField),
+    Null,
 }
 
 impl FieldVal {
+    // Checks whether this is a NULL value
+    pub fn is_null(&self) -> bool {
+        matches!(self, FieldVal::Null)
+    }
+    // Gets the type of the inner field, or None for NULL
+    pub fn get_type(&self) -> Option<Type> {
+        match self {
+            FieldVal::IntField(int_field) => Some(int_field.get_type()),
+            FieldVal::StringField(string_field) => Some(string_field.get_type()),
+            FieldVal::FloatField(float_field) => Some(float_field.get_type()),
+            FieldVal::BoolField(bool_field) => Some(bool_field.get_type()),
+            FieldVal::LongField(long_field) => Some(long_field.get_type()),
+            FieldVal::Null => None,
+        }
+    }
     // Extracts the inner IntField
     pub fn into_int(self) -> Option<IntField> {
         match self {
@@ -22,6 +43,65 @@ impl FieldVal {
             _ => None,
         }
     }
+    // Extracts the inner FloatField
+    pub fn into_float(self) -> Option<FloatField> {
+        match self {
+            FieldVal::FloatField(float_field) => Some(float_field),
+            _ => None,
+        }
+    }
+    // Extracts the inner BoolField
+    pub fn into_bool(self) -> Option<BoolField> {
+        match self {
+            FieldVal::BoolField(bool_field) => Some(bool_field),
+            _ => None,
+        }
+    }
+    // Extracts the inner LongField
+    pub fn into_long(self) -> Option<LongField> {
+        match self {
+            FieldVal::LongField(long_field) => Some(long_field),
+            _ => None,
+        }
+    }
+}
+
+impl FieldVal {
+    // position of the value's type in the order across types
+    fn type_rank(&self) -> u8 {
+        match self {
+            FieldVal::Null => 0,
+            FieldVal::BoolField(_) => 1,
+            FieldVal::IntField(_) => 2,
+            FieldVal::LongField(_) => 3,
+            FieldVal::FloatField(_) => 4,
+            FieldVal::StringField(_) => 5,
+        }
+    }
+}
+
+// Values of the same type compare by value, with floats in IEEE total order and strings
+// of equal value by their declared length, so that the order agrees with Eq. Values of
+// different types compare by type: NULL < Bool < Int < Long < Float < String.
+impl Ord for FieldVal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (FieldVal::IntField(a), FieldVal::IntField(b)) => a.value.cmp(&b.value),
+            (FieldVal::StringField(a), FieldVal::StringField(b)) => {
+                a.value.cmp(&b.value).then(a.len.cmp(&b.len))
+            }
+            (FieldVal::FloatField(a), FieldVal::FloatField(b)) => a.value.total_cmp(&b.value),
+            (FieldVal::BoolField(a), FieldVal::BoolField(b)) => a.value.cmp(&b.value),
+            (FieldVal::LongField(a), FieldVal::LongField(b)) => a.value.cmp(&b.value),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+}
+
+impl PartialOrd for FieldVal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 // Trait for different types of fields
@@ -32,7 +112,7 @@ pub trait Field {
     fn serialize(&self) -> Vec<u8>;
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct IntField {
     value: i32,
 }
@@ -55,7 +135,7 @@ impl Field for IntField {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct StringField {
     value: String,
     len: u32,
@@ -70,6 +150,24 @@ impl StringField {
     pub fn get_value(&self) -> String {
         self.value.clone()
     }
+
+    // Serialize into a column holding at most max_len bytes of string
+    pub fn serialize_with_len(&self, max_len: usize) -> Vec<u8> {
+        let mut bytes = vec![0; max_len + 4];
+        // copy as many whole characters as fit from the string and pad with 0s, so the
+        // stored bytes are always valid UTF-8
+        let limit = std::cmp::min(self.len as usize, max_len);
+        let copy_len = self
+            .value
+            .char_indices()
+            .map(|(i, c)| i + c.len_utf8())
+            .take_while(|end| *end <= limit)
+            .last()
+            .unwrap_or(0);
+        bytes[0..4].copy_from_slice(&(copy_len as u32).to_be_bytes());
+        bytes[4..4 + copy_len].copy_from_slice(&self.value.as_bytes()[..copy_len]);
+        bytes
+    }
 }
 
 impl Field for StringField {
@@ -78,13 +176,91 @@ impl Field for StringField {
     }
 
     fn serialize(&self) -> Vec<u8> {
-        let mut bytes = vec![0; STRING_SIZE + 4];
-        bytes[0..4].copy_from_slice(&self.len.to_be_bytes());
-        // copy as many bytes as possible from string and pad with 0s
-        let str_bytes = self.value.as_bytes();
-        let copy_len = std::cmp::min(str_bytes.len(), STRING_SIZE);
-        bytes[4..4 + copy_len].copy_from_slice(&str_bytes[..copy_len]);
-        bytes
+        self.serialize_with_len(STRING_SIZE)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FloatField {
+    value: f64,
+}
+
+impl FloatField {
+    pub fn new(value: f64) -> Self {
+        FloatField { value }
+    }
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+}
+
+// floats are compared by their bit pattern so that FieldVal can remain Eq
+impl PartialEq for FloatField {
+    fn eq(&self, other: &Self) -> bool {
+        self.value.to_bits() == other.value.to_bits()
+    }
+}
+
+impl Eq for FloatField {}
+
+impl Hash for FloatField {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.to_bits().hash(state);
+    }
+}
+
+impl Field for FloatField {
+    fn get_type(&self) -> Type {
+        Type::FloatType
+    }
+    fn serialize(&self) -> Vec<u8> {
+        self.value.to_be_bytes().to_vec()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct BoolField {
+    value: bool,
+}
+
+impl BoolField {
+    pub fn new(value: bool) -> Self {
+        BoolField { value }
+    }
+    pub fn get_value(&self) -> bool {
+        self.value
+    }
+}
+
+impl Field for BoolField {
+    fn get_type(&self) -> Type {
+        Type::BoolType
+    }
+    fn serialize(&self) -> Vec<u8> {
+        vec![self.value as u8]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct LongField {
+    value: i64,
+}
+
+impl LongField {
+    pub fn new(value: i64) -> Self {
+        LongField { value }
+    }
+    pub fn get_value(&self) -> i64 {
+        self.value
+    }
+}
+
+impl Field for LongField {
+    fn get_type(&self) -> Type {
+        Type::LongType
+    }
+    fn serialize(&self) -> Vec<u8> {
+        self.value.to_be_bytes().to_vec()
     }
 }
 
@@ -109,4 +285,87 @@ mod test {
 
         assert_eq!(string_field.serialize(), serialized);
     }
+
+    #[test]
+    fn test_string_field_truncates_on_char_boundary() {
+        // 3-byte characters, so STRING_SIZE falls in the middle of one
+        let value = "€".repeat(STRING_SIZE);
+        let string_field = StringField::new(value.clone(), value.len() as u32);
+        let parsed = Type::StringType
+            .parse(&string_field.serialize())
+            .unwrap()
+            .into_string()
+            .unwrap();
+        let expected = "€".repeat(STRING_SIZE / 3);
+        assert_eq!(parsed.get_value(), expected);
+        assert_eq!(
+            parsed,
+            StringField::new(expected.clone(), expected.len() as u32)
+        );
+    }
+
+    #[test]
+    fn test_float_field() {
+        let float_field = FloatField::new(1.5);
+        assert_eq!(float_field.get_type(), Type::FloatType);
+        assert_eq!(float_field.serialize(), 1.5f64.to_be_bytes().to_vec());
+        assert_eq!(
+            Type::FloatType.parse(&float_field.serialize()),
+            Ok(FieldVal::FloatField(float_field))
+        );
+    }
+
+    #[test]
+    fn test_bool_field() {
+        for value in [true, false] {
+            let bool_field = BoolField::new(value);
+            assert_eq!(bool_field.get_type(), Type::BoolType);
+            assert_eq!(bool_field.serialize(), vec![value as u8]);
+            assert_eq!(
+                Type::BoolType.parse(&bool_field.serialize()),
+                Ok(FieldVal::BoolField(bool_field))
+            );
+        }
+        assert!(Type::BoolType.parse(&[2]).is_err());
+        assert!(Type::BoolType.parse(&[]).is_err());
+    }
+
+    #[test]
+    fn test_long_field() {
+        for value in [i64::MIN, -1, i32::MAX as i64 + 1, i64::MAX] {
+            let long_field = LongField::new(value);
+            assert_eq!(long_field.get_type(), Type::LongType);
+            assert_eq!(long_field.serialize(), value.to_be_bytes().to_vec());
+            assert_eq!(
+                Type::LongType.parse(&long_field.serialize()),
+                Ok(FieldVal::LongField(long_field))
+            );
+        }
+        assert!(Type::LongType.parse(&[0; 7]).is_err());
+    }
+
+    #[test]
+    fn test_field_ordering() {
+        let int = |v| FieldVal::IntField(IntField::new(v));
+        let string =
+            |v: &str| FieldVal::StringField(StringField::new(v.to_string(), v.len() as u32));
+        assert!(int(-3) < int(2));
+        assert_eq!(int(2).cmp(&int(2)), Ordering::Equal);
+        assert!(string("apple") < string("banana"));
+        assert!(string("b") > string("abc"));
+        // equal strings with different lengths aren't Eq, so they don't compare Equal
+        let padded = FieldVal::StringField(StringField::new("apple".to_string(), 8));
+        assert_ne!(padded, string("apple"));
+        assert_eq!(padded.cmp(&string("apple")), Ordering::Greater);
+        assert!(padded < string("banana"));
+
+        // across types the order is by type, whatever the values
+        assert!(FieldVal::Null < FieldVal::BoolField(BoolField::new(true)));
+        assert!(FieldVal::BoolField(BoolField::new(true)) < int(i32::MIN));
+        assert!(int(i32::MAX) < FieldVal::FloatField(FloatField::new(-1.0)));
+        assert!(FieldVal::FloatField(FloatField::new(1e9)) < string(""));
+        let mut values = vec![string("a"), int(5), FieldVal::Null, int(1)];
+        values.sort();
+        assert_eq!(values, vec![FieldVal::Null, int(1), int(5), string("a")]);
+    }
 }
@@ -1,27 +1,314 @@
 use crate::buffer_pool::PAGE_SIZE;
 use crate::database;
-use crate::heap_page::{HeapPage, HeapPageId, Permission};
-use crate::transaction::TransactionId;
-use crate::tuple::{Tuple, TupleDesc};
+use crate::fields::FieldVal;
+use crate::heap_page::{crc32, HeapPage, HeapPageId, Permission, CHECKSUM_SIZE, PAGE_VERSION};
+use crate::transaction::{DbError, TransactionAbort, TransactionId};
+use crate::tuple::{RecordId, Tuple, TupleDesc};
+use crate::types::Type;
 
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 use uuid::Uuid;
 
-// Representation of a table stored in a file on disk
+// How many pages read_page will add past the end of a file by default
+pub const DEFAULT_MAX_GROWTH: usize = 1024;
+
+// Where a HeapFile's pages live
+enum Storage {
+    Disk(File),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Storage {
+    fn len(&self) -> u64 {
+        match self {
+            Storage::Disk(file) => file.metadata().unwrap().len(),
+            Storage::Memory(cursor) => cursor.get_ref().len() as u64,
+        }
+    }
+
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        match self {
+            Storage::Disk(file) => file.set_len(len),
+            Storage::Memory(cursor) => {
+                cursor.get_mut().resize(len as usize, 0);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Read for Storage {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Storage::Disk(file) => file.read(buf),
+            Storage::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Write for Storage {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Storage::Disk(file) => file.write(buf),
+            Storage::Memory(cursor) => cursor.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Storage::Disk(file) => file.flush(),
+            Storage::Memory(cursor) => cursor.flush(),
+        }
+    }
+}
+
+impl Seek for Storage {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Storage::Disk(file) => file.seek(pos),
+            Storage::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+// Representation of a table stored in a file on disk, or in memory for temporary tables
 pub struct HeapFile {
-    file: Mutex<File>,
+    file: Mutex<Storage>,
     td: TupleDesc,
     id: usize,
+    // index of the column whose values must be unique, if any
+    primary_key: Option<usize>,
+    // record ids of the tuples that have held each primary key value, built by scanning
+    // the file on the first write. Entries go stale when their tuple is deleted, changed
+    // or rolled back, so check_primary_key checks each against its page.
+    key_index: Mutex<Option<HashMap<FieldVal, Vec<RecordId>>>>,
+    // last seen number of empty slots on each page, None if the page hasn't been seen.
+    // Only a hint for add_tuple, the page itself is always checked before inserting.
+    free_space: RwLock<Vec<Option<usize>>>,
+    // most pages read_page may add past the end of the file, so a bogus page number from
+    // a corrupt record id fails instead of growing the file without bound
+    max_growth: usize,
+    // int column whose min and max are tracked per page, see HeapPage::get_key_range
+    key_field: Option<usize>,
+    // last seen key range of each page, kept in step with the pages like free_space.
+    // A page missing from the map hasn't been seen, None means it holds no keys.
+    key_ranges: RwLock<HashMap<usize, Option<(i32, i32)>>>,
+    // size of each page in the file, fixed once the file has pages
+    page_size: usize,
+    // number of pages add_tuple and add_tuples have looked at
+    #[cfg(test)]
+    pages_checked: AtomicUsize,
 }
 
 impl HeapFile {
     pub fn new(file: File, td: TupleDesc) -> Self {
+        HeapFile::new_with_id(file, td, Uuid::new_v4().as_u128() as usize)
+    }
+
+    // Creates a HeapFile with a known id, e.g. one that was used in a previous run
+    pub fn new_with_id(file: File, td: TupleDesc, id: usize) -> Self {
+        HeapFile::new_with_storage(Storage::Disk(file), td, id)
+    }
+
+    // Creates a HeapFile whose pages are kept in memory and lost when it's dropped
+    pub fn new_in_memory(td: TupleDesc) -> Self {
+        let storage = Storage::Memory(Cursor::new(vec![]));
+        HeapFile::new_with_storage(storage, td, Uuid::new_v4().as_u128() as usize)
+    }
+
+    fn new_with_storage(storage: Storage, td: TupleDesc, id: usize) -> Self {
         HeapFile {
-            file: Mutex::new(file),
+            file: Mutex::new(storage),
             td,
-            id: Uuid::new_v4().as_u128() as usize,
+            id,
+            primary_key: None,
+            key_index: Mutex::new(None),
+            free_space: RwLock::new(Vec::new()),
+            max_growth: DEFAULT_MAX_GROWTH,
+            key_field: None,
+            key_ranges: RwLock::new(HashMap::new()),
+            page_size: PAGE_SIZE,
+            #[cfg(test)]
+            pages_checked: AtomicUsize::new(0),
+        }
+    }
+
+    // Records how many empty slots the page has and its key range, called whenever a page
+    // of this file is read into the buffer pool, changed or reverted
+    pub fn record_page_metadata(&self, page: &HeapPage) {
+        let page_no = page.get_id().get_page_number();
+        let mut free_space = self.free_space.write().unwrap();
+        if free_space.len() <= page_no {
+            free_space.resize(page_no + 1, None);
+        }
+        free_space[page_no] = Some(page.get_num_empty_slots());
+        if self.key_field.is_some() {
+            let mut key_ranges = self.key_ranges.write().unwrap();
+            key_ranges.insert(page_no, page.get_key_range());
+        }
+    }
+
+    // Checks whether the page might hold a key between low and high inclusive, judging by
+    // its last seen key range. Pages that haven't been seen might.
+    pub fn page_may_contain_keys(&self, page_no: usize, low: i32, high: i32) -> bool {
+        match self.key_ranges.read().unwrap().get(&page_no) {
+            Some(Some((min, max))) => *min <= high && low <= *max,
+            Some(None) => false,
+            None => true,
+        }
+    }
+
+    // The first page at or after page_no that might have an empty slot
+    fn next_page_with_space(&self, page_no: usize) -> usize {
+        let free_space = self.free_space.read().unwrap();
+        (page_no..free_space.len())
+            .find(|i| free_space[*i] != Some(0))
+            .unwrap_or(free_space.len().max(page_no))
+    }
+
+    // Makes the ith column this table's primary key
+    pub fn set_primary_key(&mut self, i: usize) {
+        self.primary_key = Some(i);
+    }
+
+    pub fn get_primary_key(&self) -> Option<usize> {
+        self.primary_key
+    }
+
+    // Sets how many pages past the end of the file read_page may grow it by
+    pub fn set_max_growth(&mut self, pages: usize) {
+        self.max_growth = pages;
+    }
+
+    // Sets the size of the file's pages, failing if the file already has pages of another
+    // size or a page of this size couldn't hold a single tuple
+    pub fn set_page_size(&mut self, page_size: usize) -> Result<(), String> {
+        if page_size < CHECKSUM_SIZE + self.td.get_size() + 1 {
+            return Err(format!("page size {} can't hold a single tuple", page_size));
+        }
+        if page_size != self.page_size && self.num_pages() > 0 {
+            return Err(format!(
+                "table {} already has pages of {} bytes",
+                self.id, self.page_size
+            ));
+        }
+        self.page_size = page_size;
+        Ok(())
+    }
+
+    pub fn get_page_size(&self) -> usize {
+        self.page_size
+    }
+
+    // Makes the ith column the one each page tracks the range of, failing if it isn't an int
+    pub fn set_key_field(&mut self, i: usize) -> Result<(), String> {
+        if self.td.get_field_type(i) != Some(&Type::IntType) {
+            return Err(format!("key field {} is not an int column", i));
+        }
+        self.key_field = Some(i);
+        Ok(())
+    }
+
+    pub fn get_key_field(&self) -> Option<usize> {
+        self.key_field
+    }
+
+    // Checks that no tuple in the file other than the one at replacing, if given, already
+    // has value as its primary key. The value is locked first, see
+    // LockManager::acquire_key_lock, so a concurrent write of the same value waits for this
+    // transaction to finish or dies under WAIT-DIE, while writes of other values go ahead.
+    // NULL never clashes with anything. Building key_index takes an exclusive table lock,
+    // so the scan sees every tuple and none are added until this transaction finishes.
+    pub fn check_primary_key(
+        &self,
+        tid: TransactionId,
+        value: &FieldVal,
+        replacing: Option<RecordId>,
+    ) -> Result<(), DbError> {
+        let Some(key) = self.primary_key else {
+            return Ok(());
+        };
+        if *value == FieldVal::Null {
+            return Ok(());
+        }
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        bp.get_lock_manager()
+            .acquire_key_lock(tid, self.get_id(), value)?;
+        if self.key_index.lock().unwrap().is_none() {
+            bp.get_lock_manager()
+                .acquire_table_lock(tid, self.get_id(), true)?;
+            let mut index: HashMap<FieldVal, Vec<RecordId>> = HashMap::new();
+            for page in self.iter(tid) {
+                let page = page?;
+                for t in page.read().unwrap().iter() {
+                    let key_value = t.get_field(key).unwrap().clone();
+                    index.entry(key_value).or_default().push(t.get_record_id());
+                }
+            }
+            *self.key_index.lock().unwrap() = Some(index);
+        }
+        let rids = self
+            .key_index
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .get(value)
+            .cloned();
+        for rid in rids.unwrap_or_default() {
+            if Some(rid) == replacing {
+                continue;
+            }
+            // locked whatever the isolation level, so an uncommitted delete of the value is
+            // waited out rather than taken at its word
+            bp.get_lock_manager()
+                .acquire_lock(tid, rid.get_page_id(), false)?;
+            let page = bp.get_page(tid, rid.get_page_id(), Permission::Read)?;
+            let page = page.read().unwrap();
+            let slot = rid.get_tuple_no();
+            if page.is_slot_used(slot) && page.get_tuple(slot).get_field(key) == Some(value) {
+                return Err(DbError::Other(format!(
+                    "duplicate value for primary key {}",
+                    self.td.get_field_name(key).unwrap()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Checks the primary key of a tuple about to be written, see check_primary_key
+    fn check_tuple_key(
+        &self,
+        tid: TransactionId,
+        tuple: &Tuple,
+        replacing: Option<RecordId>,
+    ) -> Result<(), DbError> {
+        match self.primary_key {
+            Some(key) => self.check_primary_key(tid, tuple.get_field(key).unwrap(), replacing),
+            None => Ok(()),
+        }
+    }
+
+    // Adds the tuples' primary keys to key_index at their record ids, called whenever a
+    // tuple's key might have been set, e.g. by an insert or update
+    pub fn record_primary_keys<'t>(&self, tuples: impl IntoIterator<Item = &'t Tuple>) {
+        let Some(key) = self.primary_key else {
+            return;
+        };
+        let mut key_index = self.key_index.lock().unwrap();
+        let Some(index) = key_index.as_mut() else {
+            // built from the pages when it's first needed
+            return;
+        };
+        for tuple in tuples {
+            let value = tuple.get_field(key).unwrap().clone();
+            index.entry(value).or_default().push(tuple.get_record_id());
         }
     }
 
@@ -30,85 +317,364 @@ impl HeapFile {
         self.id
     }
 
+    // Whether the file's pages are kept in memory rather than on disk
+    pub fn is_in_memory(&self) -> bool {
+        matches!(*self.file.lock().unwrap(), Storage::Memory(_))
+    }
+
     // Retrieves the tuple descriptor for this table
     pub fn get_tuple_desc(&self) -> &TupleDesc {
         &self.td
     }
 
-    // Retrieves the page with the specified pid from disk
-    pub fn read_page(&self, pid: &HeapPageId) -> HeapPage {
-        let mut data = vec![0; PAGE_SIZE];
+    // Retrieves the page with the specified pid from disk, failing if its checksum doesn't
+    // match. Pages whose version and checksum are both empty were never written with one,
+    // either because they were only just added or because they predate checksums, and
+    // aren't checked. I/O errors are returned too.
+    pub fn read_page(&self, pid: &HeapPageId) -> Result<HeapPage, String> {
+        let mut data = vec![0; self.page_size];
         let mut file = self.file.lock().unwrap();
-        let mut num_pages =
-            (file.metadata().unwrap().len() as f64 / PAGE_SIZE as f64).ceil() as usize;
+        let num_pages = file.len() as usize / self.page_size;
         let page_no = pid.get_page_number();
-        while num_pages <= page_no {
-            file.seek(SeekFrom::Start((num_pages * PAGE_SIZE) as u64))
-                .unwrap();
-            file.write_all(&data).unwrap();
-            num_pages += 1;
+        if page_no >= num_pages.saturating_add(self.max_growth) {
+            return Err(format!(
+                "page {} of table {} is too far past the end of its {} pages",
+                page_no,
+                pid.get_table_id(),
+                num_pages
+            ));
+        }
+        // grow the file with empty pages up to and including the requested one in one write
+        let io_error = |e: std::io::Error| {
+            format!(
+                "could not read page {} of table {}: {}",
+                page_no,
+                pid.get_table_id(),
+                e
+            )
+        };
+        if num_pages <= page_no {
+            file.seek(SeekFrom::Start((num_pages * self.page_size) as u64))
+                .map_err(io_error)?;
+            file.write_all(&vec![0; (page_no + 1 - num_pages) * self.page_size])
+                .map_err(io_error)?;
         }
 
-        file.seek(SeekFrom::Start((page_no * PAGE_SIZE) as u64))
-            .unwrap();
-        file.read_exact(&mut data).unwrap();
-        HeapPage::new(*pid, data, self.td.clone())
+        file.seek(SeekFrom::Start((page_no * self.page_size) as u64))
+            .map_err(io_error)?;
+        file.read_exact(&mut data).map_err(io_error)?;
+
+        let version = data[self.page_size - CHECKSUM_SIZE];
+        let (contents, checksum) = data.split_at(self.page_size - CHECKSUM_SIZE + 1);
+        let checksum = u32::from_be_bytes(checksum.try_into().unwrap());
+        match version {
+            PAGE_VERSION if checksum == crc32(contents) => {}
+            PAGE_VERSION => {
+                return Err(format!(
+                    "checksum mismatch on page {} of table {}",
+                    page_no,
+                    pid.get_table_id()
+                ))
+            }
+            0 if checksum == 0 => {}
+            _ => {
+                return Err(format!(
+                    "unknown format version {} on page {} of table {}",
+                    version,
+                    page_no,
+                    pid.get_table_id()
+                ))
+            }
+        }
+        let mut page = HeapPage::try_new(*pid, data, self.td.clone())?;
+        page.set_key_field(self.key_field);
+        Ok(page)
     }
 
     // Writes the specified page to disk
     pub fn write_page(&self, page: &HeapPage) {
         let pid = page.get_id();
-        let data = page.get_page_data();
+        // pages of different sizes would overlap in the file
+        assert_eq!(
+            page.get_page_size(),
+            self.page_size,
+            "page {} of table {} is the wrong size",
+            pid.get_page_number(),
+            self.id
+        );
+        self.write_page_data(pid, page.get_page_data());
+    }
+
+    // Writes a raw page image to disk, e.g. one recovered from the log, with a fresh
+    // version and checksum. An image that doesn't parse is reported when the page is next read.
+    pub fn write_page_data(&self, pid: HeapPageId, mut data: Vec<u8>) {
+        assert_eq!(data.len(), self.page_size, "page image is the wrong size");
+        let crc_start = self.page_size - CHECKSUM_SIZE + 1;
+        data[crc_start - 1] = PAGE_VERSION;
+        let checksum = crc32(&data[..crc_start]);
+        data[crc_start..].copy_from_slice(&checksum.to_be_bytes());
         let mut file = self.file.lock().unwrap();
-        file.seek(SeekFrom::Start((pid.get_page_number() * PAGE_SIZE) as u64))
-            .unwrap();
+        file.seek(SeekFrom::Start(
+            (pid.get_page_number() * self.page_size) as u64,
+        ))
+        .unwrap();
         file.write_all(&data).unwrap();
     }
 
     // Calculates the number of pages in this HeapFile
+    // Counts the whole pages in the file. A partial page left at the end, e.g. by a torn
+    // write, doesn't count and is overwritten when the file grows.
     pub fn num_pages(&self) -> usize {
         let file = self.file.lock().unwrap();
-        (file.metadata().unwrap().len() as f64 / PAGE_SIZE as f64).ceil() as usize
+        file.len() as usize / self.page_size
     }
 
-    // Adds the specified tuple to the file
-    pub fn add_tuple(&self, tid: TransactionId, tuple: Tuple) {
+    // Counts the live tuples across every page from their slot usage, failing if the
+    // transaction is aborted while acquiring read locks
+    pub fn num_tuples(&self, tid: TransactionId) -> Result<usize, TransactionAbort> {
+        let mut count = 0;
+        for page in self.iter(tid) {
+            let page = page?;
+            let page = page.read().unwrap();
+            count += page.get_num_slots() - page.get_num_empty_slots();
+        }
+        Ok(count)
+    }
+
+    // Adds the tuple to the first page with room and returns its record id.
+    // Fails without touching any page if the tuple doesn't fit this file's schema, and
+    // with DbError::Abort if the transaction is aborted while locking pages.
+    pub fn add_tuple(&self, tid: TransactionId, tuple: Tuple) -> Result<RecordId, DbError> {
+        self.check_schema(&tuple)?;
+        self.check_tuple_key(tid, &tuple, None)?;
         let table_id = self.get_id();
         let db = database::get_global_db();
         let bp = db.get_buffer_pool();
-        let mut page_no = 0;
+        let mut page_no = self.next_page_with_space(0);
 
-        // find the first page with an empty slot
+        // find the first page with an empty slot, skipping pages known to be full
         loop {
+            #[cfg(test)]
+            self.pages_checked.fetch_add(1, Ordering::SeqCst);
             let pid = HeapPageId::new(table_id, page_no);
-            let page = bp.get_page(tid, pid, Permission::Read).unwrap();
+            let page = bp.get_page(tid, pid, Permission::Read)?;
             let page_read = page.read().unwrap();
             if page_read.get_num_empty_slots() > 0 {
                 drop(page_read);
-                let page = bp.get_page(tid, pid, Permission::Write).unwrap();
+                let page = bp.get_page(tid, pid, Permission::Write)?;
                 let mut page_writer = page.write().unwrap();
-                page_writer.add_tuple(tuple).unwrap();
+                let rid = page_writer.add_tuple(tuple).unwrap();
+                page_writer.mark_dirty(true, tid);
+                self.record_page_metadata(&page_writer);
+                self.record_primary_keys([page_writer.get_tuple(rid.get_tuple_no())]);
+                return Ok(rid);
+            }
+            self.record_page_metadata(&page_read);
+            page_no = self.next_page_with_space(page_no + 1);
+        }
+    }
+
+    // Adds the tuple to the last page, or a new page after it if the last is full, without
+    // looking for empty slots in earlier pages. Meant for tables that are never deleted from,
+    // where earlier pages stay full anyway.
+    pub fn append_tuple(&self, tid: TransactionId, tuple: Tuple) -> Result<RecordId, DbError> {
+        self.check_schema(&tuple)?;
+        self.check_tuple_key(tid, &tuple, None)?;
+        let table_id = self.get_id();
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        // pages may only exist in the buffer pool so far
+        let num_pages = self.free_space.read().unwrap().len().max(self.num_pages());
+        let mut page_no = num_pages.saturating_sub(1);
+        loop {
+            #[cfg(test)]
+            self.pages_checked.fetch_add(1, Ordering::SeqCst);
+            let pid = HeapPageId::new(table_id, page_no);
+            let page = bp.get_page(tid, pid, Permission::Write)?;
+            let mut page_writer = page.write().unwrap();
+            if page_writer.get_num_empty_slots() > 0 {
+                let rid = page_writer.add_tuple(tuple).unwrap();
                 page_writer.mark_dirty(true, tid);
-                return;
+                self.record_page_metadata(&page_writer);
+                self.record_primary_keys([page_writer.get_tuple(rid.get_tuple_no())]);
+                return Ok(rid);
             }
+            self.record_page_metadata(&page_writer);
             page_no += 1;
         }
     }
 
-    // TODO: Deletes the specified tuple from the file
-    pub fn delete_tuple(&self, tid: TransactionId, tuple: Tuple) {
+    fn check_schema(&self, tuple: &Tuple) -> Result<(), String> {
+        if !self.td.matches_types(tuple.get_tuple_desc()) {
+            return Err(format!(
+                "tuple schema {:?} does not match table schema {:?}",
+                tuple.get_tuple_desc(),
+                self.td
+            ));
+        }
+        self.td.validate_fields(&tuple.get_fields())
+    }
+
+    // Adds the tuples in order, filling every empty slot of a page under one write lock
+    // before moving on to the next, and returns their record ids. Fails without touching
+    // any page if a tuple doesn't fit this file's schema. Tables with a primary key insert
+    // one tuple at a time so duplicates within the batch are caught.
+    pub fn add_tuples(
+        &self,
+        tid: TransactionId,
+        tuples: Vec<Tuple>,
+    ) -> Result<Vec<RecordId>, DbError> {
+        for tuple in tuples.iter() {
+            self.check_schema(tuple)?;
+        }
+        if self.primary_key.is_some() {
+            return tuples.into_iter().map(|t| self.add_tuple(tid, t)).collect();
+        }
+        let table_id = self.get_id();
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let mut rids = Vec::with_capacity(tuples.len());
+        let mut tuples = tuples.into_iter().peekable();
+        let mut page_no = self.next_page_with_space(0);
+        while tuples.peek().is_some() {
+            #[cfg(test)]
+            self.pages_checked.fetch_add(1, Ordering::SeqCst);
+            let pid = HeapPageId::new(table_id, page_no);
+            let page = bp.get_page(tid, pid, Permission::Read)?;
+            let has_space = page.read().unwrap().get_num_empty_slots() > 0;
+            if has_space {
+                let page = bp.get_page(tid, pid, Permission::Write)?;
+                let mut page_writer = page.write().unwrap();
+                while page_writer.get_num_empty_slots() > 0 {
+                    let Some(tuple) = tuples.next() else {
+                        break;
+                    };
+                    rids.push(page_writer.add_tuple(tuple).unwrap());
+                }
+                page_writer.mark_dirty(true, tid);
+                self.record_page_metadata(&page_writer);
+            } else {
+                self.record_page_metadata(&page.read().unwrap());
+            }
+            page_no = self.next_page_with_space(page_no + 1);
+        }
+        Ok(rids)
+    }
+
+    // Deletes the specified tuple from the file, using its record id to find the page
+    pub fn delete_tuple(&self, tid: TransactionId, tuple: Tuple) -> Result<(), DbError> {
         let db = database::get_global_db();
         let bp = db.get_buffer_pool();
         let rid = tuple.get_record_id();
         let pid = rid.get_page_id();
-        let page = bp.get_page(tid, pid, Permission::Write).unwrap();
+        if pid.get_table_id() != self.get_id() {
+            return Err(DbError::Other("Tuple not in this table".to_string()));
+        }
+        let page = bp.get_page(tid, pid, Permission::Write)?;
+        let mut page_writer = page.write().unwrap();
+        page_writer.delete_tuple(tuple)?;
+        page_writer.mark_dirty(true, tid);
+        self.record_page_metadata(&page_writer);
+        Ok(())
+    }
+
+    // Overwrites the tuple stored at the tuple's record id in place. Fails if another tuple
+    // already has its primary key.
+    pub fn update_tuple(&self, tid: TransactionId, tuple: Tuple) -> Result<(), DbError> {
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let pid = tuple.get_record_id().get_page_id();
+        if pid.get_table_id() != self.get_id() {
+            return Err(DbError::Other("Tuple not in this table".to_string()));
+        }
+        let rid = tuple.get_record_id();
+        self.check_tuple_key(tid, &tuple, Some(rid))?;
+        let page = bp.get_page(tid, pid, Permission::Write)?;
         let mut page_writer = page.write().unwrap();
-        page_writer.delete_tuple(tuple).unwrap();
+        page_writer.update_tuple(tuple)?;
         page_writer.mark_dirty(true, tid);
+        self.record_page_metadata(&page_writer);
+        self.record_primary_keys([page_writer.get_tuple(rid.get_tuple_no())]);
+        Ok(())
+    }
+
+    // Rewrites the file with its tuples packed into as few pages as possible, updating the
+    // record ids of any that move, and truncates the empty pages left at the end.
+    // The table is locked exclusively. The rewrite is logged as a change of its own and goes
+    // straight to disk, see BufferPool::write_pages_logged, so it isn't undone if the
+    // transaction aborts, and pages with uncommitted changes are refused. Indexes on the
+    // table are left pointing at the old record ids, see Table::compact.
+    pub fn compact(&self, tid: TransactionId) -> Result<(), DbError> {
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        bp.get_lock_manager()
+            .acquire_table_lock(tid, self.get_id(), true)?;
+        let num_pages = self.num_pages();
+        let mut pages = vec![];
+        for page_no in 0..num_pages {
+            let pid = HeapPageId::new(self.get_id(), page_no);
+            let page = bp.get_page(tid, pid, Permission::Write)?;
+            if page.read().unwrap().is_dirty() {
+                return Err(DbError::Other(format!(
+                    "page {} of table {} has uncommitted changes",
+                    page_no,
+                    self.get_id()
+                )));
+            }
+            pages.push(page);
+        }
+
+        let mut tuples = vec![];
+        for page in pages.iter() {
+            tuples.extend(page.read().unwrap().iter().cloned());
+        }
+        let mut tuples = tuples.into_iter();
+        let mut used_pages = 0;
+        let mut rewrites = vec![];
+        for page in pages.iter() {
+            let before = page.read().unwrap().clone();
+            let mut after = before.clone();
+            after.restore(vec![0; self.page_size]);
+            while after.get_num_empty_slots() > 0 {
+                let Some(tuple) = tuples.next() else {
+                    break;
+                };
+                after.add_tuple(tuple)?;
+            }
+            if after.iter().next().is_some() {
+                used_pages += 1;
+            }
+            // emptied pages are logged too, so a crash before the file is cut short can't
+            // leave their old tuples behind as duplicates
+            rewrites.push((before, after));
+        }
+        bp.write_pages_logged(self, &rewrites);
+        // the moved tuples' record ids changed, so the key index is rebuilt when next needed
+        *self.key_index.lock().unwrap() = None;
+        for (page, (_, after)) in pages.iter().zip(rewrites).take(used_pages) {
+            let mut page = page.write().unwrap();
+            *page = after;
+            page.set_before_image();
+            page.set_committed_by(None);
+            self.record_page_metadata(&page);
+        }
+
+        for page_no in used_pages..num_pages {
+            bp.discard_page(HeapPageId::new(self.get_id(), page_no));
+        }
+        self.free_space.write().unwrap().truncate(used_pages);
+        self.key_ranges
+            .write()
+            .unwrap()
+            .retain(|page_no, _| *page_no < used_pages);
+        let mut file = self.file.lock().unwrap();
+        file.set_len((used_pages * self.page_size) as u64)
+            .map_err(|e| DbError::Other(e.to_string()))
     }
 
-    // Retrieves an iterator over the pages in this file
-    pub fn iter(&self, tid: TransactionId) -> HeapFileIterator {
+    // Retrieves an iterator over the pages in this file, yielding an error for a page
+    // whose lock could not be acquired because the transaction was aborted
+    pub fn iter(&self, tid: TransactionId) -> HeapFileIterator<'_> {
         HeapFileIterator {
             heap_file: self,
             current_page_index: 0,
@@ -116,8 +682,8 @@ impl HeapFile {
         }
     }
 
-    // Retrieves an iterator over the pages in this file
-    pub fn iter_mut(&self, tid: TransactionId) -> HeapFileIteratorMut {
+    // Retrieves an iterator over the pages in this file, locked for writing
+    pub fn iter_mut(&self, tid: TransactionId) -> HeapFileIteratorMut<'_> {
         HeapFileIteratorMut {
             heap_file: self,
             current_page_index: 0,
@@ -133,14 +699,14 @@ pub struct HeapFileIterator<'a> {
 }
 
 impl<'a> Iterator for HeapFileIterator<'a> {
-    type Item = Arc<RwLock<HeapPage>>;
+    type Item = Result<Arc<RwLock<HeapPage>>, TransactionAbort>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current_page_index < self.heap_file.num_pages() {
             let pid = HeapPageId::new(self.heap_file.get_id(), self.current_page_index);
             let db = database::get_global_db();
             let bp = db.get_buffer_pool();
-            let page = bp.get_page(self.tid, pid, Permission::Read).unwrap();
+            let page = bp.get_page(self.tid, pid, Permission::Read);
             self.current_page_index += 1;
             Some(page)
         } else {
@@ -156,14 +722,14 @@ pub struct HeapFileIteratorMut<'a> {
 }
 
 impl<'a> Iterator for HeapFileIteratorMut<'a> {
-    type Item = Arc<RwLock<HeapPage>>;
+    type Item = Result<Arc<RwLock<HeapPage>>, TransactionAbort>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.current_page_index < self.heap_file.num_pages() {
             let pid = HeapPageId::new(self.heap_file.get_id(), self.current_page_index);
             let db = database::get_global_db();
             let bp = db.get_buffer_pool();
-            let page = bp.get_page(self.tid, pid, Permission::Write).unwrap();
+            let page = bp.get_page(self.tid, pid, Permission::Write);
             self.current_page_index += 1;
             Some(page)
         } else {
@@ -171,3 +737,413 @@ impl<'a> Iterator for HeapFileIteratorMut<'a> {
         }
     }
 }
+
+// Opens the file at path for a test's HeapFile, emptying it if it already exists
+#[cfg(test)]
+pub fn open_test_file(path: &std::path::Path) -> File {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(path)
+        .unwrap()
+}
+
+// Registers a table backed by a fresh temporary file with the global catalog, under a
+// unique name starting with prefix, and returns the name
+#[cfg(test)]
+pub fn create_test_heap_file(prefix: &str, td: TupleDesc) -> String {
+    let name = format!("{}_{}", prefix, Uuid::new_v4());
+    let file = open_test_file(&std::env::temp_dir().join(format!("{}.dat", name)));
+    database::get_global_db()
+        .get_catalog()
+        .add_table(HeapFile::new(file, td), name.clone());
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::{FieldVal, FloatField, IntField, StringField};
+    use crate::log::LogRecord;
+    use crate::transaction::IsolationLevel;
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn test_read_page_detects_corruption() {
+        let path = std::env::temp_dir().join(format!("checksum_{}.dat", Uuid::new_v4()));
+        let open = || {
+            OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(&path)
+                .unwrap()
+        };
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let heap_file = HeapFile::new(open(), td.clone());
+        let pid = HeapPageId::new(heap_file.get_id(), 0);
+        let mut page = HeapPage::new(pid, vec![0; PAGE_SIZE], td.clone());
+        let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(42))], &td);
+        page.add_tuple(tuple).unwrap();
+        heap_file.write_page(&page);
+        assert_eq!(
+            heap_file.read_page(&pid).unwrap().get_page_data(),
+            page.get_page_data()
+        );
+
+        // flip a bit in the tuple data
+        let mut file = open();
+        let mut byte = [0; 1];
+        file.seek(SeekFrom::Start(100)).unwrap();
+        file.read_exact(&mut byte).unwrap();
+        file.seek(SeekFrom::Start(100)).unwrap();
+        file.write_all(&[byte[0] ^ 1]).unwrap();
+        let err = heap_file.read_page(&pid).unwrap_err();
+        assert!(err.contains("checksum"));
+
+        // an empty checksum is still checked on a page written with one
+        heap_file.write_page(&page);
+        file.seek(SeekFrom::Start((PAGE_SIZE - CHECKSUM_SIZE + 1) as u64))
+            .unwrap();
+        file.write_all(&[0; CHECKSUM_SIZE - 1]).unwrap();
+        assert!(heap_file.read_page(&pid).unwrap_err().contains("checksum"));
+
+        // as is the version
+        heap_file.write_page(&page);
+        file.seek(SeekFrom::Start((PAGE_SIZE - CHECKSUM_SIZE) as u64))
+            .unwrap();
+        file.write_all(&[PAGE_VERSION + 1]).unwrap();
+        assert!(heap_file.read_page(&pid).unwrap_err().contains("version"));
+
+        // pages from before checksums have an empty trailer and are read unchecked
+        file.seek(SeekFrom::Start((PAGE_SIZE - CHECKSUM_SIZE) as u64))
+            .unwrap();
+        file.write_all(&[0; CHECKSUM_SIZE]).unwrap();
+        assert_eq!(heap_file.read_page(&pid).unwrap().iter().count(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_add_tuple_wrong_schema() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let name = create_test_heap_file("schema", td.clone());
+        let db = database::get_global_db();
+        let heap_file = db.get_catalog().get_table_from_name(&name).unwrap();
+        let tid = TransactionId::new();
+        let id = FieldVal::IntField(IntField::new(1));
+
+        // too many fields
+        let wide_td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType],
+            vec!["id".to_string(), "age".to_string()],
+        );
+        let wide = Tuple::new(vec![id.clone(), id.clone()], &wide_td);
+        assert!(heap_file.add_tuple(tid, wide).is_err());
+        // right schema but the value has the wrong type
+        let float = FieldVal::FloatField(FloatField::new(1.0));
+        assert!(heap_file
+            .add_tuple(tid, Tuple::new(vec![float], &td))
+            .is_err());
+        // nothing was written
+        assert!(db
+            .get_buffer_pool()
+            .get_lock_manager()
+            .get_locked_pages(tid)
+            .is_empty());
+
+        assert!(heap_file.add_tuple(tid, Tuple::new(vec![id], &td)).is_ok());
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_compact() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::StringType],
+            vec!["id".to_string(), "name".to_string()],
+        );
+        let name = create_test_heap_file("compact", td.clone());
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let heap_file = db.get_catalog().get_table_from_name(&name).unwrap();
+
+        let tid = TransactionId::new();
+        for i in 0..100 {
+            let name = format!("name_{}", i);
+            let len = name.len() as u32;
+            let fields = vec![
+                FieldVal::IntField(IntField::new(i)),
+                FieldVal::StringField(StringField::new(name, len)),
+            ];
+            heap_file.add_tuple(tid, Tuple::new(fields, &td)).unwrap();
+        }
+        bp.commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = heap_file
+            .iter(tid)
+            .flat_map(|page| {
+                page.unwrap()
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        for tuple in tuples.into_iter().step_by(2) {
+            heap_file.delete_tuple(tid, tuple).unwrap();
+        }
+        bp.commit_transaction(tid);
+
+        // a snapshot taken before compacting keeps seeing the old layout
+        let snapshot = TransactionId::with_isolation(IsolationLevel::Snapshot);
+        let last_page = HeapPageId::new(heap_file.get_id(), heap_file.num_pages() - 1);
+        let old_last_page = bp.get_snapshot_page(snapshot, last_page).unwrap();
+
+        let pages_before = heap_file.num_pages();
+        let tid = TransactionId::new();
+        heap_file.compact(tid).unwrap();
+        bp.commit_transaction(tid);
+        assert!(heap_file.num_pages() < pages_before);
+        assert_eq!(
+            bp.get_snapshot_page(snapshot, last_page).unwrap(),
+            old_last_page
+        );
+        bp.commit_transaction(snapshot);
+
+        // the rewrite was logged, emptied pages included, and committed
+        let records = db.get_log_manager().read_records();
+        let rewrite_tid = records
+            .iter()
+            .rev()
+            .find_map(|r| match r {
+                LogRecord::Update { tid, pid, .. } if *pid == last_page => Some(*tid),
+                _ => None,
+            })
+            .unwrap();
+        assert!(records.contains(&LogRecord::Commit(rewrite_tid)));
+
+        let tid = TransactionId::new();
+        let mut ids = vec![];
+        for page in heap_file.iter(tid) {
+            let page = page.unwrap();
+            let page = page.read().unwrap();
+            for (i, tuple) in page.iter().enumerate() {
+                assert_eq!(tuple.get_record_id(), RecordId::new(page.get_id(), i));
+                ids.push(tuple.get_int(0).unwrap());
+            }
+        }
+        bp.commit_transaction(tid);
+        assert_eq!(ids, (0..100).filter(|i| i % 2 == 1).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_add_tuple_skips_full_pages() {
+        let db = database::get_global_db();
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let name = format!("free_space_{}", Uuid::new_v4());
+        let table_id = db.get_catalog().create_temp_table(name, td.clone());
+        let heap_file = db.get_catalog().get_table_from_id(table_id).unwrap();
+        let tid = TransactionId::new();
+        let num_tuples = 10_000;
+        for i in 0..num_tuples {
+            let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td);
+            heap_file.add_tuple(tid, tuple).unwrap();
+        }
+        assert!(heap_file.num_pages() > 5);
+        // each insert looks at about one page instead of every page before it
+        let pages_checked = heap_file.pages_checked.load(Ordering::SeqCst);
+        assert!(pages_checked < num_tuples as usize + heap_file.num_pages());
+
+        // a slot freed by a delete is reused
+        let page = db
+            .get_buffer_pool()
+            .get_page(tid, HeapPageId::new(table_id, 0), Permission::Read)
+            .unwrap();
+        let victim = page.read().unwrap().get_tuple(3).clone();
+        drop(page);
+        heap_file.delete_tuple(tid, victim.clone()).unwrap();
+        let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(-1))], &td);
+        let rid = heap_file.add_tuple(tid, tuple).unwrap();
+        assert_eq!(rid, victim.get_record_id());
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_append_tuple() {
+        let db = database::get_global_db();
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let name = format!("append_{}", Uuid::new_v4());
+        let table_id = db.get_catalog().create_temp_table(name, td.clone());
+        let heap_file = db.get_catalog().get_table_from_id(table_id).unwrap();
+        let tid = TransactionId::new();
+        let num_tuples = 5000;
+        for i in 0..num_tuples {
+            let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td);
+            heap_file.append_tuple(tid, tuple).unwrap();
+        }
+        let num_pages = heap_file.num_pages();
+        assert!(num_pages > 5);
+        // each append looks at the last page, plus the new page whenever it was full
+        let pages_checked = heap_file.pages_checked.load(Ordering::SeqCst);
+        assert_eq!(pages_checked, num_tuples as usize + num_pages - 1);
+        assert_eq!(heap_file.num_tuples(tid), Ok(num_tuples as usize));
+
+        // a gap left by a delete is not filled
+        let page = db
+            .get_buffer_pool()
+            .get_page(tid, HeapPageId::new(table_id, 0), Permission::Read)
+            .unwrap();
+        let victim = page.read().unwrap().get_tuple(3).clone();
+        drop(page);
+        heap_file.delete_tuple(tid, victim.clone()).unwrap();
+        let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(-1))], &td);
+        let rid = heap_file.append_tuple(tid, tuple).unwrap();
+        assert_eq!(rid.get_page_id().get_page_number(), num_pages - 1);
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_add_tuples_fills_pages_in_batches() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let heap_file = HeapFile::new_in_memory(td.clone());
+        let table_id = heap_file.get_id();
+        let db = database::get_global_db();
+        db.get_catalog()
+            .add_table(heap_file, format!("batch_{}", Uuid::new_v4()));
+        let heap_file = db.get_catalog().get_table_from_id(table_id).unwrap();
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = (0..1000)
+            .map(|i| Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td))
+            .collect();
+        let rids = heap_file.add_tuples(tid, tuples).unwrap();
+        assert_eq!(rids.len(), 1000);
+        // each page is looked at once rather than once per tuple
+        let pages_checked = heap_file.pages_checked.load(Ordering::SeqCst);
+        assert_eq!(pages_checked, heap_file.num_pages());
+        assert!(pages_checked < 10);
+        db.get_buffer_pool().commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        let ids: Vec<i32> = heap_file
+            .iter(tid)
+            .flat_map(|page| {
+                let page = page.unwrap();
+                let ids: Vec<i32> = page
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|t| t.get_int(0).unwrap())
+                    .collect();
+                ids
+            })
+            .collect();
+        assert_eq!(ids, (0..1000).collect::<Vec<_>>());
+
+        let bad = Tuple::new(vec![FieldVal::Null], &td);
+        assert!(heap_file.add_tuples(tid, vec![bad]).is_err());
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_num_tuples() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let heap_file = HeapFile::new_in_memory(td.clone());
+        let table_id = heap_file.get_id();
+        let db = database::get_global_db();
+        db.get_catalog()
+            .add_table(heap_file, format!("num_tuples_{}", Uuid::new_v4()));
+        let heap_file = db.get_catalog().get_table_from_id(table_id).unwrap();
+        let tid = TransactionId::new();
+        assert_eq!(heap_file.num_tuples(tid), Ok(0));
+        let tuples: Vec<Tuple> = (0..1500)
+            .map(|i| Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td))
+            .collect();
+        let rids = heap_file.add_tuples(tid, tuples.clone()).unwrap();
+        assert!(heap_file.num_pages() > 1);
+        assert_eq!(heap_file.num_tuples(tid), Ok(1500));
+
+        for (mut tuple, rid) in tuples.into_iter().zip(rids).take(10) {
+            tuple.set_record_id(rid);
+            heap_file.delete_tuple(tid, tuple).unwrap();
+        }
+        assert_eq!(heap_file.num_tuples(tid), Ok(1490));
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_read_page_grows_file() {
+        let path = std::env::temp_dir().join(format!("grow_{}.dat", Uuid::new_v4()));
+        let file = open_test_file(&path);
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let heap_file = HeapFile::new(file, td);
+        let page = heap_file
+            .read_page(&HeapPageId::new(heap_file.get_id(), 100))
+            .unwrap();
+        assert_eq!(page.iter().count(), 0);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            101 * PAGE_SIZE as u64
+        );
+        assert_eq!(heap_file.num_pages(), 101);
+        for page_no in [0, 50, 99] {
+            let pid = HeapPageId::new(heap_file.get_id(), page_no);
+            assert_eq!(heap_file.read_page(&pid).unwrap().iter().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_read_page_partial_and_failed_io() {
+        // a torn write left half a page at the end
+        let path = std::env::temp_dir().join(format!("torn_{}.dat", Uuid::new_v4()));
+        let file = open_test_file(&path);
+        std::fs::write(&path, vec![0; PAGE_SIZE + PAGE_SIZE / 2]).unwrap();
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let heap_file = HeapFile::new(file, td.clone());
+        assert_eq!(heap_file.num_pages(), 1);
+        let page = heap_file
+            .read_page(&HeapPageId::new(heap_file.get_id(), 1))
+            .unwrap();
+        assert_eq!(page.iter().count(), 0);
+        assert_eq!(heap_file.num_pages(), 2);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            2 * PAGE_SIZE as u64
+        );
+
+        // growing a file opened read only fails instead of panicking
+        let heap_file = HeapFile::new(File::open(&path).unwrap(), td);
+        let err = heap_file
+            .read_page(&HeapPageId::new(heap_file.get_id(), 2))
+            .unwrap_err();
+        assert!(err.contains("could not read page 2"), "{}", err);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_page_rejects_far_pages() {
+        let path = std::env::temp_dir().join(format!("far_{}.dat", Uuid::new_v4()));
+        let file = open_test_file(&path);
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let mut heap_file = HeapFile::new(file, td);
+        let pid = |page_no| HeapPageId::new(heap_file.get_id(), page_no);
+        heap_file.read_page(&pid(0)).unwrap();
+        assert!(heap_file.read_page(&pid(1_000_000)).is_err());
+        assert!(heap_file.read_page(&pid(usize::MAX)).is_err());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), PAGE_SIZE as u64);
+
+        heap_file.set_max_growth(2);
+        let pid = |page_no| HeapPageId::new(heap_file.get_id(), page_no);
+        assert!(heap_file.read_page(&pid(3)).is_err());
+        heap_file.read_page(&pid(2)).unwrap();
+        assert_eq!(heap_file.num_pages(), 3);
+    }
+}
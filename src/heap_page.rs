@@ -1,6 +1,26 @@
-use crate::buffer_pool::PAGE_SIZE;
+use crate::fields::FieldVal;
 use crate::transaction::TransactionId;
-use crate::tuple::{Tuple, TupleDesc};
+use crate::tuple::{RecordId, Tuple, TupleDesc};
+
+// The last bytes of every page hold a format version byte followed by a CRC32 of
+// everything before it. Pages written before checksums existed have them zeroed.
+pub const CHECKSUM_SIZE: usize = 5;
+
+// Format version of pages whose checksum is checked
+pub const PAGE_VERSION: u8 = 1;
+
+// CRC32 (IEEE) of the bytes
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFF_u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash, Copy)]
 pub enum Permission {
@@ -41,6 +61,7 @@ impl HeapPageId {
  * Format is header bytes + tuple bytes. Header bytes indicate
  * whether or not a tuple is present in that slot on the page.
  * The number of bytes for header is equal to ceiling(# tuple slots / 8)
+ * The last CHECKSUM_SIZE bytes are reserved for the version and checksum written by the HeapFile.
  */
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct HeapPage {
@@ -50,14 +71,32 @@ pub struct HeapPage {
     header: Vec<u8>,
     tuples: Vec<Tuple>,
     num_slots: usize,
+    // size of the page in bytes, taken from the data it was created with
+    page_size: usize,
     old_data: Vec<u8>,
     dirtied_by: Option<TransactionId>,
+    // the last transaction that committed changes to the page without them being written
+    // to disk yet, see BufferPool::commit_transaction_no_force
+    committed_by: Option<TransactionId>,
+    // the int column whose smallest and largest values are tracked in key_range, if any
+    key_field: Option<usize>,
+    // (min, max) of key_field over the page's tuples, None if none of them has a value
+    key_range: Option<(i32, i32)>,
 }
 
 impl HeapPage {
+    // Creates a page from freshly zeroed data, which always parses - use try_new for data
+    // read from disk or the log
     pub fn new(pid: HeapPageId, data: Vec<u8>, td: TupleDesc) -> Self {
-        let num_slots = (PAGE_SIZE * 8) / (td.get_size() * 8 + 1);
-        let old_data = vec![0; PAGE_SIZE];
+        Self::try_new(pid, data, td).unwrap()
+    }
+
+    // Parses the page from data, failing if one of its tuples can't be deserialized
+    pub fn try_new(pid: HeapPageId, data: Vec<u8>, td: TupleDesc) -> Result<Self, String> {
+        let page_size = data.len();
+        let num_slots = ((page_size - CHECKSUM_SIZE) * 8) / (td.get_size() * 8 + 1);
+        // the before image is the data the page was read with
+        let old_data = data.clone();
 
         let header_size = (num_slots as f64 / 8.0).ceil() as usize;
         let header = data[..header_size].to_vec();
@@ -69,22 +108,29 @@ impl HeapPage {
                 let start = header_size + i * td.get_size();
                 let end = start + td.get_size();
                 let tuple_data = data[start..end].to_vec();
-                tuples.push(Tuple::deserialize(&tuple_data, &td));
+                let mut tuple = Tuple::deserialize(&tuple_data, &td)
+                    .map_err(|e| format!("slot {} of page {:?}: {}", i, pid, e))?;
+                tuple.set_record_id(RecordId::new(pid, i));
+                tuples.push(tuple);
             } else {
                 tuples.push(Tuple::new(vec![], &td));
             }
         }
 
-        HeapPage {
+        Ok(HeapPage {
             pid,
             td,
             header_size,
             header,
             tuples,
             num_slots,
+            page_size,
             old_data,
             dirtied_by: None,
-        }
+            committed_by: None,
+            key_field: None,
+            key_range: None,
+        })
     }
 
     pub fn get_id(&self) -> HeapPageId {
@@ -92,14 +138,77 @@ impl HeapPage {
     }
 
     pub fn get_before_image(&self) -> HeapPage {
-        HeapPage::new(self.pid, self.old_data.clone(), self.td.clone())
+        HeapPage::try_new(self.pid, self.old_data.clone(), self.td.clone())
+            .expect("before image is data the page was parsed from or serialized to")
     }
 
     pub fn set_before_image(&mut self) {
         self.old_data = self.get_page_data();
     }
 
+    // Replaces the page's contents with data, keeping its before image and dirty state.
+    // The data is an image this page was serialized to, or zeroes to empty it.
+    pub fn restore(&mut self, data: Vec<u8>) {
+        let mut page = HeapPage::try_new(self.pid, data, self.td.clone())
+            .expect("restored image is data the page was serialized to");
+        page.old_data = std::mem::take(&mut self.old_data);
+        page.dirtied_by = self.dirtied_by;
+        page.committed_by = self.committed_by;
+        page.set_key_field(self.key_field);
+        *self = page;
+    }
+
+    // Throws away uncommitted changes, going back to the before image
+    pub fn revert(&mut self) {
+        let (committed_by, key_field) = (self.committed_by, self.key_field);
+        *self = self.get_before_image();
+        self.committed_by = committed_by;
+        self.set_key_field(key_field);
+    }
+
+    // Starts tracking the min and max of the given int column, or stops if None
+    pub fn set_key_field(&mut self, field: Option<usize>) {
+        self.key_field = field;
+        self.update_key_range();
+    }
+
+    pub fn get_key_field(&self) -> Option<usize> {
+        self.key_field
+    }
+
+    // The (min, max) of the key field over the page's tuples, None if there's no key
+    // field or none of the tuples has a value for it
+    pub fn get_key_range(&self) -> Option<(i32, i32)> {
+        self.key_range
+    }
+
+    fn update_key_range(&mut self) {
+        self.key_range = None;
+        for i in 0..self.num_slots {
+            if Self::get_slot(&self.header, i) {
+                self.widen_key_range(i);
+            }
+        }
+    }
+
+    // Extends the key range to cover the tuple in slot i
+    fn widen_key_range(&mut self, i: usize) {
+        let Some(key) = self.key_field.and_then(|f| self.tuples[i].get_int(f)) else {
+            return;
+        };
+        self.key_range = match self.key_range {
+            Some((min, max)) => Some((min.min(key), max.max(key))),
+            None => Some((key, key)),
+        };
+    }
+
     pub fn get_page_data(&self) -> Vec<u8> {
+        self.try_get_page_data().unwrap()
+    }
+
+    // Serializes the page, failing if the header and slots don't fit in the page, which
+    // means num_slots or the tuple size was miscalculated
+    pub fn try_get_page_data(&self) -> Result<Vec<u8>, String> {
         let mut data = self.header.clone();
         for i in 0..self.num_slots {
             if Self::get_slot(&self.header, i) {
@@ -108,9 +217,18 @@ impl HeapPage {
                 data.extend(vec![0; self.td.get_size()]);
             }
         }
-        // pad the rest of the page with 0s
-        data.extend(vec![0; PAGE_SIZE - data.len()]);
-        data
+        if data.len() > self.page_size - CHECKSUM_SIZE {
+            return Err(format!(
+                "page {:?} overflows: {} bytes of data but only {} of {} available",
+                self.pid,
+                data.len(),
+                self.page_size - CHECKSUM_SIZE,
+                self.page_size
+            ));
+        }
+        // pad the rest of the page with 0s, leaving the checksum bytes empty
+        data.extend(vec![0; self.page_size - data.len()]);
+        Ok(data)
     }
 
     fn get_slot(header: &[u8], i: usize) -> bool {
@@ -136,23 +254,39 @@ impl HeapPage {
         }
     }
 
-    fn create_empty_page_data(&self) -> Vec<u8> {
-        vec![0; PAGE_SIZE]
-    }
-
-    pub fn add_tuple(&mut self, t: Tuple) -> Result<(), String> {
+    // Puts the tuple in the first empty slot and returns where it was stored
+    pub fn add_tuple(&mut self, t: Tuple) -> Result<RecordId, String> {
         let mut i = 0;
         while i < self.num_slots {
             if !Self::get_slot(&self.header, i) {
-                self.tuples[i] = t;
-                Self::set_slot(&mut self.header, i, true);
-                return Ok(());
+                self.insert_tuple_at(i, t)?;
+                return Ok(RecordId::new(self.pid, i));
             }
             i += 1;
         }
         Err("No empty slots".to_string())
     }
 
+    // Places the tuple in the given slot, failing if the slot is taken or doesn't exist or
+    // the tuple's values don't fit the page's schema
+    pub fn insert_tuple_at(&mut self, slot: usize, mut t: Tuple) -> Result<(), String> {
+        if slot >= self.num_slots {
+            return Err(format!(
+                "Slot {} out of range, page has {} slots",
+                slot, self.num_slots
+            ));
+        }
+        if Self::get_slot(&self.header, slot) {
+            return Err(format!("Slot {} is already occupied", slot));
+        }
+        self.td.validate_fields(&t.get_fields())?;
+        t.set_record_id(RecordId::new(self.pid, slot));
+        self.tuples[slot] = t;
+        Self::set_slot(&mut self.header, slot, true);
+        self.widen_key_range(slot);
+        Ok(())
+    }
+
     pub fn delete_tuple(&mut self, t: Tuple) -> Result<(), String> {
         let rid = t.get_record_id();
         let tuple_no = rid.get_tuple_no();
@@ -165,9 +299,69 @@ impl HeapPage {
 
         self.tuples[tuple_no] = Tuple::new(vec![], &self.td);
         Self::set_slot(&mut self.header, tuple_no, false);
+        self.update_key_range();
+        Ok(())
+    }
+
+    // Overwrites the occupied slot referenced by the tuple's record id
+    pub fn update_tuple(&mut self, t: Tuple) -> Result<(), String> {
+        let rid = t.get_record_id();
+        let tuple_no = rid.get_tuple_no();
+        if rid.get_page_id() != self.pid {
+            return Err("Tuple not on this page".to_string());
+        }
+        if !Self::get_slot(&self.header, tuple_no) {
+            return Err("Tuple not on this page".to_string());
+        }
+        self.td.validate_fields(&t.get_fields())?;
+
+        self.tuples[tuple_no] = t;
+        self.update_key_range();
+        Ok(())
+    }
+
+    // Replaces the ith field of the tuple in the occupied slot, failing if the value
+    // doesn't fit the column
+    pub fn set_tuple_field(
+        &mut self,
+        slot: usize,
+        i: usize,
+        value: FieldVal,
+    ) -> Result<(), String> {
+        if slot >= self.num_slots || !Self::get_slot(&self.header, slot) {
+            return Err(format!("Slot {} is not occupied", slot));
+        }
+        if i >= self.td.get_num_fields() {
+            return Err(format!("Tuple has no field {}", i));
+        }
+        self.td.validate_field(i, &value)?;
+        self.tuples[slot].set_field(i, value);
+        self.update_key_range();
         Ok(())
     }
 
+    // Moves the live tuples into the lowest slots, keeping their order, and marks the page
+    // dirtied by tid if any moved. Takes the page's write lock for granted. Moved tuples get
+    // new record ids, so indexes on the table have to be rebuilt afterwards.
+    pub fn compact_slots(&mut self, tid: TransactionId) {
+        let mut next = 0;
+        for i in 0..self.num_slots {
+            if !Self::get_slot(&self.header, i) {
+                continue;
+            }
+            if i != next {
+                let mut tuple =
+                    std::mem::replace(&mut self.tuples[i], Tuple::new(vec![], &self.td));
+                tuple.set_record_id(RecordId::new(self.pid, next));
+                self.tuples[next] = tuple;
+                Self::set_slot(&mut self.header, i, false);
+                Self::set_slot(&mut self.header, next, true);
+                self.mark_dirty(true, tid);
+            }
+            next += 1;
+        }
+    }
+
     pub fn get_num_empty_slots(&self) -> usize {
         let mut count = 0;
         for i in 0..self.num_slots {
@@ -178,6 +372,12 @@ impl HeapPage {
         count
     }
 
+    // Gets the fraction of the page's slots that hold a tuple
+    pub fn fill_factor(&self) -> f64 {
+        let used = self.num_slots - self.get_num_empty_slots();
+        used as f64 / self.num_slots as f64
+    }
+
     pub fn mark_dirty(&mut self, dirty: bool, tid: TransactionId) {
         if dirty {
             self.dirtied_by = Some(tid);
@@ -190,19 +390,43 @@ impl HeapPage {
         self.dirtied_by.is_some()
     }
 
-    pub fn iter(&self) -> HeapPageIterator {
+    // Marks the page as holding committed changes that still need writing to disk, or
+    // clears the mark once they've been written
+    pub fn set_committed_by(&mut self, tid: Option<TransactionId>) {
+        self.committed_by = tid;
+    }
+
+    pub fn get_committed_by(&self) -> Option<TransactionId> {
+        self.committed_by
+    }
+
+    // Gets the transaction that last dirtied the page, if it's dirty
+    pub fn get_dirtied_by(&self) -> Option<TransactionId> {
+        self.dirtied_by
+    }
+
+    pub fn iter(&self) -> HeapPageIterator<'_> {
         HeapPageIterator {
             page: self,
             index: 0,
         }
     }
 
+    // Checks whether slot i holds a tuple
+    pub fn is_slot_used(&self, i: usize) -> bool {
+        Self::get_slot(&self.header, i)
+    }
+
     // by adam but idk if this is fine
     pub fn get_tuple(&self, i: usize) -> &Tuple {
         &self.tuples[i]
     }
 
-    pub fn num_tuples(&self) -> usize {
+    pub fn get_page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn get_num_slots(&self) -> usize {
         self.num_slots
     }
 }
@@ -230,3 +454,142 @@ impl<'a> Iterator for HeapPageIterator<'a> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_pool::PAGE_SIZE;
+    use crate::fields::IntField;
+    use crate::types::Type;
+
+    #[test]
+    fn test_compact_slots() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let pid = HeapPageId::new(0, 0);
+        let mut page = HeapPage::new(pid, vec![0; PAGE_SIZE], td.clone());
+        for i in 0..10 {
+            let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td);
+            page.add_tuple(tuple).unwrap();
+        }
+        for i in (0..10).step_by(2) {
+            page.delete_tuple(page.get_tuple(i).clone()).unwrap();
+        }
+        let tid = TransactionId::new();
+        page.compact_slots(tid);
+        assert_eq!(page.get_dirtied_by(), Some(tid));
+        for slot in 0..5 {
+            assert!(page.is_slot_used(slot));
+            let tuple = page.get_tuple(slot);
+            assert_eq!(tuple.get_int(0), Some(slot as i32 * 2 + 1));
+            assert_eq!(tuple.get_record_id(), RecordId::new(pid, slot));
+        }
+        assert!((5..page.get_num_slots()).all(|slot| !page.is_slot_used(slot)));
+        // the new layout survives a round trip through the page's bytes
+        let page = HeapPage::try_new(pid, page.get_page_data(), td).unwrap();
+        let ids: Vec<i32> = page.iter().map(|t| t.get_int(0).unwrap()).collect();
+        assert_eq!(ids, vec![1, 3, 5, 7, 9]);
+    }
+
+    #[test]
+    fn test_page_data_overflow() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let pid = HeapPageId::new(0, 3);
+        let mut page = HeapPage::new(pid, vec![0; PAGE_SIZE], td.clone());
+        assert_eq!(page.try_get_page_data().unwrap().len(), PAGE_SIZE);
+        // pretend the slot count was miscalculated so the slots no longer fit
+        page.num_slots += 100;
+        page.tuples
+            .extend((0..100).map(|_| Tuple::new(vec![], &td)));
+        let err = page.try_get_page_data().unwrap_err();
+        assert!(err.contains(&format!("{:?}", pid)));
+        assert!(err.contains(&PAGE_SIZE.to_string()));
+    }
+
+    #[test]
+    fn test_try_new_corrupt_tuple() {
+        let td = TupleDesc::new(vec![Type::StringType], vec!["name".to_string()]);
+        let pid = HeapPageId::new(0, 0);
+        let mut data = vec![0; PAGE_SIZE];
+        // a used slot whose string length is longer than the column allows
+        data[0] = 1;
+        let num_slots = ((PAGE_SIZE - CHECKSUM_SIZE) * 8) / (td.get_size() * 8 + 1);
+        let header_size = num_slots.div_ceil(8);
+        data[header_size..header_size + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let err = HeapPage::try_new(pid, data, td).unwrap_err();
+        assert!(err.contains("slot 0"));
+    }
+
+    #[test]
+    fn test_fill_factor() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let pid = HeapPageId::new(0, 0);
+        let mut page = HeapPage::new(pid, vec![0; PAGE_SIZE], td.clone());
+        assert_eq!(page.fill_factor(), 0.0);
+        // an even number of slots, so half of them can be filled exactly
+        let num_slots = page.get_num_slots() / 2 * 2;
+        for slot in 0..num_slots / 2 {
+            let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(0))], &td);
+            page.insert_tuple_at(slot, tuple).unwrap();
+        }
+        let expected = (num_slots / 2) as f64 / page.get_num_slots() as f64;
+        assert_eq!(page.fill_factor(), expected);
+    }
+
+    #[test]
+    fn test_insert_tuple_at() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let pid = HeapPageId::new(0, 0);
+        let mut page = HeapPage::new(pid, vec![0; PAGE_SIZE], td.clone());
+        let tuple = |i| Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td);
+        page.insert_tuple_at(5, tuple(5)).unwrap();
+        assert!(page.is_slot_used(5));
+        assert_eq!(page.get_tuple(5).get_int(0), Some(5));
+        assert_eq!(page.get_tuple(5).get_record_id(), RecordId::new(pid, 5));
+        // the slot survives a round trip through the page's bytes
+        let page = HeapPage::try_new(pid, page.get_page_data(), td.clone()).unwrap();
+        assert_eq!(page.iter().count(), 1);
+        assert_eq!(page.get_tuple(5).get_int(0), Some(5));
+
+        let mut page = page;
+        assert!(page.insert_tuple_at(5, tuple(6)).is_err());
+        assert!(page
+            .insert_tuple_at(page.get_num_slots(), tuple(6))
+            .is_err());
+        // add_tuple still takes the first free slot
+        assert_eq!(page.add_tuple(tuple(0)).unwrap(), RecordId::new(pid, 0));
+
+        // NULLs are rejected in the non-nullable column however they're written
+        let null = Tuple::new(vec![FieldVal::Null], &td);
+        assert!(page.insert_tuple_at(6, null.clone()).is_err());
+        assert!(page.add_tuple(null).is_err());
+        assert!(page.set_tuple_field(5, 0, FieldVal::Null).is_err());
+        let mut replaced = Tuple::new(vec![FieldVal::Null], &td);
+        replaced.set_record_id(RecordId::new(pid, 5));
+        assert!(page.update_tuple(replaced).is_err());
+        assert_eq!(page.get_tuple(5).get_int(0), Some(5));
+    }
+
+    #[test]
+    fn test_key_range() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let pid = HeapPageId::new(0, 0);
+        let mut page = HeapPage::new(pid, vec![0; PAGE_SIZE], td.clone());
+        let tuple = |i| Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td);
+        page.add_tuple(tuple(5)).unwrap();
+        assert_eq!(page.get_key_range(), None);
+        page.set_key_field(Some(0));
+        assert_eq!(page.get_key_range(), Some((5, 5)));
+        page.add_tuple(tuple(2)).unwrap();
+        let rid = page.add_tuple(tuple(9)).unwrap();
+        assert_eq!(page.get_key_range(), Some((2, 9)));
+
+        let mut max = tuple(9);
+        max.set_record_id(rid);
+        page.delete_tuple(max).unwrap();
+        assert_eq!(page.get_key_range(), Some((2, 5)));
+        // reverting keeps tracking the key
+        page.revert();
+        assert_eq!(page.get_key_range(), None);
+        assert_eq!(page.get_key_field(), Some(0));
+    }
+}
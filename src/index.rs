@@ -0,0 +1,83 @@
+use crate::fields::FieldVal;
+use crate::tuple::{RecordId, Tuple, TupleDesc};
+use std::collections::HashMap;
+
+// Hash index over a single column of a table, mapping each value to the record ids of the
+// tuples holding it. Entries can go stale if a transaction aborts, so callers should check
+// the tuple they fetch still has the value they looked up.
+#[derive(Debug, Clone)]
+pub struct HashIndex {
+    field: String,
+    field_id: usize,
+    entries: HashMap<FieldVal, Vec<RecordId>>,
+}
+
+impl HashIndex {
+    pub fn new(field: &str, td: &TupleDesc) -> Result<Self, String> {
+        let field_id = td
+            .name_to_id(field)
+            .ok_or(format!("no field named {}", field))?;
+        Ok(HashIndex {
+            field: field.to_string(),
+            field_id,
+            entries: HashMap::new(),
+        })
+    }
+
+    pub fn get_field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn get_field_id(&self) -> usize {
+        self.field_id
+    }
+
+    // Adds the tuple stored at rid to the index, once however often it's added
+    pub fn insert(&mut self, tuple: &Tuple, rid: RecordId) {
+        let value = tuple.get_field(self.field_id).unwrap().clone();
+        let rids = self.entries.entry(value).or_default();
+        if !rids.contains(&rid) {
+            rids.push(rid);
+        }
+    }
+
+    // Removes every entry pointing at rid
+    pub fn remove(&mut self, rid: RecordId) {
+        for rids in self.entries.values_mut() {
+            rids.retain(|r| *r != rid);
+        }
+        self.entries.retain(|_, rids| !rids.is_empty());
+    }
+
+    // Returns the record ids of the tuples holding value
+    pub fn get(&self, value: &FieldVal) -> Vec<RecordId> {
+        self.entries.get(value).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::IntField;
+    use crate::heap_page::HeapPageId;
+    use crate::types::Type;
+
+    #[test]
+    fn test_hash_index_insert_remove() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        assert!(HashIndex::new("missing", &td).is_err());
+        let mut index = HashIndex::new("id", &td).unwrap();
+        let value = FieldVal::IntField(IntField::new(5));
+        let tuple = Tuple::new(vec![value.clone()], &td);
+        let rid1 = RecordId::new(HeapPageId::new(1, 0), 0);
+        let rid2 = RecordId::new(HeapPageId::new(1, 0), 1);
+        index.insert(&tuple, rid1);
+        index.insert(&tuple, rid2);
+        // e.g. an update that leaves the indexed field alone
+        index.insert(&tuple, rid1);
+        assert_eq!(index.get(&value), vec![rid1, rid2]);
+        index.remove(rid1);
+        assert_eq!(index.get(&value), vec![rid2]);
+        assert!(index.get(&FieldVal::IntField(IntField::new(6))).is_empty());
+    }
+}
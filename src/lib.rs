@@ -0,0 +1,15 @@
+pub mod buffer_pool;
+pub mod catalog;
+pub mod database;
+pub mod fields;
+pub mod heap_file;
+pub mod heap_page;
+pub mod index;
+pub mod lock_manager;
+pub mod log;
+pub mod sql;
+pub mod statistics;
+pub mod table;
+pub mod transaction;
+pub mod tuple;
+pub mod types;
@@ -1,24 +1,64 @@
 use crate::database;
+use crate::fields::FieldVal;
 use crate::heap_page::HeapPageId;
 use crate::heap_page::Permission;
-use crate::transaction::TransactionId;
+use crate::transaction::{TransactionAbort, TransactionId};
 use std::borrow::BorrowMut;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::sync::RwLock;
+use std::hash::{Hash, Hasher};
 use std::sync::RwLockWriteGuard;
-use std::thread;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Copy)]
+// page number of the HeapPageId that stands for a lock on the whole table
+pub const TABLE_LOCK_PAGE: usize = usize::MAX;
+
+// number of page numbers just below TABLE_LOCK_PAGE that stand for locks on primary key
+// values, see acquire_key_lock
+const KEY_LOCK_PAGES: usize = 1 << 16;
+
+// condition variable signalled when a lock on a page is released
+type PageWaiter = Arc<(Mutex<()>, Condvar)>;
+
+// A transaction's lock on a page. Locks are keyed by (tid, pid) only, so each
+// transaction holds at most one lock per page and readers never collapse together.
+#[derive(Debug, Clone, Copy)]
 struct Lock {
     tid: TransactionId,
     pid: HeapPageId,
     exclusive: bool,
 }
 
+impl PartialEq for Lock {
+    fn eq(&self, other: &Self) -> bool {
+        self.tid == other.tid && self.pid == other.pid
+    }
+}
+
+impl Eq for Lock {}
+
+impl Hash for Lock {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tid.hash(state);
+        self.pid.hash(state);
+    }
+}
+
 pub struct LockManager {
     page_to_locks: RwLock<HashMap<HeapPageId, HashSet<Lock>>>,
     transaction_to_locks: RwLock<HashMap<TransactionId, HashSet<Lock>>>,
+    // how long a transaction may wait for a lock before it is aborted, forever if None
+    timeout: Option<Duration>,
+    // signalled whenever a lock on the page is released
+    waiters: Mutex<HashMap<HeapPageId, PageWaiter>>,
+}
+
+impl Default for LockManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LockManager {
@@ -26,11 +66,28 @@ impl LockManager {
         LockManager {
             page_to_locks: RwLock::new(HashMap::new()),
             transaction_to_locks: RwLock::new(HashMap::new()),
+            timeout: None,
+            waiters: Mutex::new(HashMap::new()),
         }
     }
 
-    // Acquires a lock on the specified page for the specified transaction
-    pub fn acquire_lock(&self, tid: TransactionId, pid: HeapPageId, exclusive: bool) {
+    pub fn new_with_timeout(timeout: Duration) -> Self {
+        LockManager {
+            timeout: Some(timeout),
+            ..LockManager::new()
+        }
+    }
+
+    // Acquires a lock on the specified page for the specified transaction.
+    // Under WAIT-DIE, a transaction that conflicts with an older lock holder is aborted
+    // and an error is returned instead of waiting. Transactions that wait longer than the
+    // timeout are aborted too.
+    pub fn acquire_lock(
+        &self,
+        tid: TransactionId,
+        pid: HeapPageId,
+        exclusive: bool,
+    ) -> Result<(), TransactionAbort> {
         // early return if the transaction already has the appropriate lock
         {
             let transaction_locks = self.transaction_to_locks.read().unwrap();
@@ -38,64 +95,211 @@ impl LockManager {
                 let locks = transaction_locks.get(&tid).unwrap();
                 for lock in locks {
                     if lock.pid == pid && (lock.exclusive == exclusive || !exclusive) {
-                        return;
+                        return Ok(());
                     }
                 }
             }
         }
         // check if there is a conflicting lock on the page
+        let start = Instant::now();
         loop {
             let mut page_to_locks = self.page_to_locks.write().unwrap();
             let mut transaction_to_locks = self.transaction_to_locks.write().unwrap();
 
-            if let Some(locks) = page_to_locks.get(&pid) {
-                // upgrade the lock if the transaction already has a lock on the page
-                if locks.len() == 1 && locks.iter().next().unwrap().tid == tid {
-                    if exclusive {
-                        self.upgrade_lock(
-                            tid,
-                            pid,
-                            page_to_locks.borrow_mut(),
-                            transaction_to_locks.borrow_mut(),
-                        );
-                    }
-                    return;
-                }
-                // conflict if there are others locks when we want an exclusive lock
-                let mut conflict = exclusive && !locks.is_empty();
-                // or if there is an exclusive lock and we want any lock
-                conflict = conflict || locks.iter().any(|lock| lock.exclusive);
-
-                if conflict {
-                    let abort = locks.iter().any(|lock| lock.tid < tid);
-                    drop(page_to_locks);
-                    drop(transaction_to_locks);
-                    if abort {
-                        // abort the transaction
-                        let db = database::get_global_db();
-                        let bp = db.get_buffer_pool();
-                        bp.abort_transaction(tid);
-                        panic!("Transaction {:?} aborted", tid);
-                    }
-                    // wait for the lock to be released
-                    thread::sleep(std::time::Duration::from_millis(500));
-                    continue;
-                }
-            }
-            // add the lock to the page and transaction
-            let page_locks = page_to_locks.entry(pid).or_insert(HashSet::new());
-            let transaction_locks = transaction_to_locks.entry(tid).or_insert(HashSet::new());
-            page_locks.insert(Lock {
+            let others = self.try_grant(
                 tid,
-                exclusive,
                 pid,
-            });
-            transaction_locks.insert(Lock {
-                tid,
                 exclusive,
-                pid,
-            });
-            return;
+                page_to_locks.borrow_mut(),
+                transaction_to_locks.borrow_mut(),
+            );
+            if others.is_empty() {
+                return Ok(());
+            }
+
+            // two readers upgrading at once can't both wait for the other, so the
+            // younger one dies and the older waits for its read lock to go
+            let abort = others.iter().any(|lock| lock.tid < tid);
+            // take the page's wait lock before letting go of the lock tables,
+            // so a release can't slip in before we start waiting
+            let waiter = self.get_waiter(pid);
+            let (wait_lock, condvar) = &*waiter;
+            let wait_guard = wait_lock.lock().unwrap();
+            drop(page_to_locks);
+            drop(transaction_to_locks);
+            let timed_out = self.timeout.is_some_and(|t| start.elapsed() >= t);
+            if abort || timed_out {
+                // abort the transaction
+                let db = database::get_global_db();
+                let bp = db.get_buffer_pool();
+                drop(wait_guard);
+                bp.abort_transaction(tid);
+                // the buffer pool only releases the locks held in its own lock manager
+                self.release_locks(tid);
+                if abort {
+                    return Err(TransactionAbort::new(tid));
+                }
+                return Err(TransactionAbort::timed_out(tid));
+            }
+            // wait for a lock on the page to be released, but not past the timeout
+            match self.timeout {
+                Some(timeout) => {
+                    let wait = timeout.saturating_sub(start.elapsed());
+                    drop(condvar.wait_timeout(wait_guard, wait).unwrap());
+                }
+                None => drop(condvar.wait(wait_guard).unwrap()),
+            }
+        }
+    }
+
+    // Gives tid the lock if no other transaction holds a conflicting one, otherwise
+    // returns the conflicting locks without changing anything
+    fn try_grant(
+        &self,
+        tid: TransactionId,
+        pid: HeapPageId,
+        exclusive: bool,
+        page_to_locks: &mut RwLockWriteGuard<HashMap<HeapPageId, HashSet<Lock>>>,
+        transaction_to_locks: &mut RwLockWriteGuard<HashMap<TransactionId, HashSet<Lock>>>,
+    ) -> Vec<Lock> {
+        let others = Self::conflicting_locks(page_to_locks, tid, pid, exclusive);
+        if !others.is_empty() {
+            return others;
+        }
+        let held = page_to_locks
+            .get(&pid)
+            .is_some_and(|locks| locks.iter().any(|lock| lock.tid == tid));
+        // upgrade the lock if the transaction already holds one on the page
+        if held {
+            if exclusive {
+                self.upgrade_lock(tid, pid, page_to_locks, transaction_to_locks);
+            }
+            return others;
+        }
+        // add the lock to the page and transaction
+        let lock = Lock {
+            tid,
+            exclusive,
+            pid,
+        };
+        page_to_locks.entry(pid).or_default().insert(lock);
+        transaction_to_locks.entry(tid).or_default().insert(lock);
+        others
+    }
+
+    // Locks the whole table exclusively if no other transaction holds a lock on it or any
+    // of its pages, returning whether it did. Never waits or aborts tid.
+    pub fn try_acquire_table_lock(&self, tid: TransactionId, table_id: usize) -> bool {
+        let pid = HeapPageId::new(table_id, TABLE_LOCK_PAGE);
+        let mut page_to_locks = self.page_to_locks.write().unwrap();
+        let mut transaction_to_locks = self.transaction_to_locks.write().unwrap();
+        self.try_grant(
+            tid,
+            pid,
+            true,
+            page_to_locks.borrow_mut(),
+            transaction_to_locks.borrow_mut(),
+        )
+        .is_empty()
+    }
+
+    // Acquires a lock on the whole table. Other transactions can't lock any of its pages
+    // while it's held exclusively, and it can't be held exclusively while they hold any.
+    // A shared table lock only keeps out writers. Conflicts are handled under WAIT-DIE
+    // like page locks, and the lock is released with the transaction's page locks.
+    pub fn acquire_table_lock(
+        &self,
+        tid: TransactionId,
+        table_id: usize,
+        exclusive: bool,
+    ) -> Result<(), TransactionAbort> {
+        self.acquire_lock(tid, HeapPageId::new(table_id, TABLE_LOCK_PAGE), exclusive)
+    }
+
+    // Acquires an exclusive lock on a primary key value of the table, so two transactions
+    // can't both find the value free and write it. Values are hashed onto a fixed set of
+    // lock pages, so unrelated values occasionally share a lock. Like a page lock it
+    // conflicts with a lock on the whole table, and it's released with the transaction's
+    // page locks.
+    pub fn acquire_key_lock(
+        &self,
+        tid: TransactionId,
+        table_id: usize,
+        key: &FieldVal,
+    ) -> Result<(), TransactionAbort> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let page_no = TABLE_LOCK_PAGE - 1 - (hasher.finish() as usize % KEY_LOCK_PAGES);
+        self.acquire_lock(tid, HeapPageId::new(table_id, page_no), true)
+    }
+
+    // The locks other transactions hold that keep tid from locking pid - those on pid
+    // itself, plus those on the pid's table for a page, or on the table's pages for a table
+    fn conflicting_locks(
+        page_to_locks: &HashMap<HeapPageId, HashSet<Lock>>,
+        tid: TransactionId,
+        pid: HeapPageId,
+        exclusive: bool,
+    ) -> Vec<Lock> {
+        let conflicts = |lock: &&Lock| lock.tid != tid && (exclusive || lock.exclusive);
+        let table_id = pid.get_table_id();
+        let related: Vec<&HashSet<Lock>> = if pid.get_page_number() == TABLE_LOCK_PAGE {
+            page_to_locks
+                .iter()
+                .filter(|(other, _)| other.get_table_id() == table_id)
+                .map(|(_, locks)| locks)
+                .collect()
+        } else {
+            [pid, HeapPageId::new(table_id, TABLE_LOCK_PAGE)]
+                .iter()
+                .filter_map(|pid| page_to_locks.get(pid))
+                .collect()
+        };
+        related
+            .into_iter()
+            .flatten()
+            .filter(conflicts)
+            .copied()
+            .collect()
+    }
+
+    // Gets the condition variable signalled when a lock on the page is released
+    fn get_waiter(&self, pid: HeapPageId) -> PageWaiter {
+        let mut waiters = self.waiters.lock().unwrap();
+        Arc::clone(waiters.entry(pid).or_default())
+    }
+
+    // Wakes up every transaction waiting on one of the pages, along with those waiting on
+    // the page's table lock, or on any page of the table for a table lock. Pages no one is
+    // waiting on any more are forgotten, so waiters only holds pages with waiters.
+    fn notify_waiters(&self, pids: impl IntoIterator<Item = HeapPageId>) {
+        let mut waiters = self.waiters.lock().unwrap();
+        let mut targets = HashSet::new();
+        for pid in pids {
+            let table_lock = HeapPageId::new(pid.get_table_id(), TABLE_LOCK_PAGE);
+            if pid == table_lock {
+                targets.extend(
+                    waiters
+                        .keys()
+                        .filter(|other| other.get_table_id() == pid.get_table_id()),
+                );
+            } else {
+                targets.insert(table_lock);
+            }
+            targets.insert(pid);
+        }
+        for pid in targets {
+            let Some(waiter) = waiters.get(&pid) else {
+                continue;
+            };
+            // every waiting transaction holds a clone, see get_waiter
+            if Arc::strong_count(waiter) == 1 {
+                waiters.remove(&pid);
+                continue;
+            }
+            let (wait_lock, condvar) = &**waiter;
+            let _guard = wait_lock.lock().unwrap();
+            condvar.notify_all();
         }
     }
 
@@ -109,27 +313,42 @@ impl LockManager {
     ) {
         let page_locks = page_to_locks.get_mut(&pid).unwrap();
         let transaction_locks = transaction_to_locks.get_mut(&tid).unwrap();
-        let old_lock = Lock {
-            tid,
-            pid,
-            exclusive: false,
-        };
         let new_lock = Lock {
             tid,
             pid,
             exclusive: true,
         };
-        page_locks.remove(&old_lock);
-        page_locks.insert(new_lock);
-        transaction_locks.remove(&old_lock);
-        transaction_locks.insert(new_lock);
+        // old and new lock are the same key, so the entry has to be replaced
+        page_locks.replace(new_lock);
+        transaction_locks.replace(new_lock);
+    }
+
+    // Releases the transaction's lock on a single page, if it holds one
+    pub fn release_lock(&self, tid: TransactionId, pid: HeapPageId) {
+        let mut page_to_locks = self.page_to_locks.write().unwrap();
+        let mut transaction_to_locks = self.transaction_to_locks.write().unwrap();
+        if let Some(transaction_locks) = transaction_to_locks.get_mut(&tid) {
+            transaction_locks.retain(|lock| lock.pid != pid);
+            if transaction_locks.is_empty() {
+                transaction_to_locks.remove(&tid);
+            }
+        }
+        if let Some(page_locks) = page_to_locks.get_mut(&pid) {
+            page_locks.retain(|lock| lock.tid != tid);
+            if page_locks.is_empty() {
+                page_to_locks.remove(&pid);
+            }
+        }
+        drop(page_to_locks);
+        drop(transaction_to_locks);
+        self.notify_waiters([pid]);
     }
 
     // Releases all locks associated with the specified transaction
     pub fn release_locks(&self, tid: TransactionId) {
         let mut page_to_locks = self.page_to_locks.write().unwrap();
         let mut transaction_locks = self.transaction_to_locks.write().unwrap();
-        let held_locks = transaction_locks.entry(tid).or_insert(HashSet::new());
+        let held_locks = transaction_locks.entry(tid).or_default();
         for lock in held_locks.iter() {
             let page_locks = page_to_locks.get_mut(&lock.pid).unwrap();
             page_locks.remove(lock);
@@ -137,8 +356,11 @@ impl LockManager {
                 page_to_locks.remove(&lock.pid);
             }
         }
-        held_locks.clear();
+        let pids: Vec<HeapPageId> = held_locks.drain().map(|lock| lock.pid).collect();
         transaction_locks.remove(&tid);
+        drop(page_to_locks);
+        drop(transaction_locks);
+        self.notify_waiters(pids);
     }
 
     // Checks if the specified transaction has a lock on the specified page
@@ -161,12 +383,179 @@ impl LockManager {
         }
     }
 
-    // gets the set of pages locked by the specified transaction
+    // Gets the number of (shared, exclusive) locks held on the page
+    pub fn get_lock_counts(&self, pid: HeapPageId) -> (usize, usize) {
+        let page_to_locks = self.page_to_locks.read().unwrap();
+        match page_to_locks.get(&pid) {
+            Some(locks) => {
+                let exclusive = locks.iter().filter(|lock| lock.exclusive).count();
+                (locks.len() - exclusive, exclusive)
+            }
+            None => (0, 0),
+        }
+    }
+
+    // Lists every lock currently held as (page, transaction, exclusive), ordered by page
+    // then transaction
+    pub fn dump_locks(&self) -> Vec<(HeapPageId, TransactionId, bool)> {
+        let page_to_locks = self.page_to_locks.read().unwrap();
+        let mut locks: Vec<(HeapPageId, TransactionId, bool)> = page_to_locks
+            .values()
+            .flatten()
+            .map(|lock| (lock.pid, lock.tid, lock.exclusive))
+            .collect();
+        locks.sort_by_key(|(pid, tid, _)| {
+            (pid.get_table_id(), pid.get_page_number(), tid.get_tid())
+        });
+        locks
+    }
+
+    // gets the set of pages locked by the specified transaction, leaving out table and
+    // key locks
     pub fn get_locked_pages(&self, tid: TransactionId) -> HashSet<HeapPageId> {
         let transaction_locks = self.transaction_to_locks.read().unwrap();
         match transaction_locks.get(&tid) {
-            Some(locks) => locks.iter().map(|lock| lock.pid).collect(),
+            Some(locks) => locks
+                .iter()
+                .map(|lock| lock.pid)
+                .filter(|pid| pid.get_page_number() < TABLE_LOCK_PAGE - KEY_LOCK_PAGES)
+                .collect(),
             None => HashSet::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_timeout() {
+        let lm = LockManager::new_with_timeout(Duration::from_millis(200));
+        let pid = HeapPageId::new(usize::MAX, 0);
+        let waiter = TransactionId::new();
+        let holder = TransactionId::new();
+        lm.acquire_lock(holder, pid, true).unwrap();
+
+        // the older waiter is allowed to wait under WAIT-DIE, but only until the timeout
+        let start = Instant::now();
+        let err = lm.acquire_lock(waiter, pid, false).unwrap_err();
+        assert!(err.is_timeout());
+        assert_eq!(err.get_tid(), waiter);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(lm.holds_lock(holder, pid), Some(Permission::Write));
+        assert_eq!(lm.holds_lock(waiter, pid), None);
+    }
+
+    #[test]
+    fn test_readers_hold_independent_locks() {
+        let lm = LockManager::new();
+        let pid = HeapPageId::new(usize::MAX, 2);
+        let first = TransactionId::new();
+        let second = TransactionId::new();
+        std::thread::scope(|s| {
+            s.spawn(|| lm.acquire_lock(first, pid, false).unwrap());
+            s.spawn(|| lm.acquire_lock(second, pid, false).unwrap());
+        });
+        assert_eq!(lm.get_lock_counts(pid), (2, 0));
+        assert_eq!(lm.holds_lock(first, pid), Some(Permission::Read));
+        assert_eq!(lm.holds_lock(second, pid), Some(Permission::Read));
+
+        // releasing one reader leaves the other's lock in place
+        lm.release_locks(first);
+        assert_eq!(lm.get_lock_counts(pid), (1, 0));
+        assert_eq!(lm.holds_lock(first, pid), None);
+        assert_eq!(lm.holds_lock(second, pid), Some(Permission::Read));
+
+        // an upgrade replaces the reader's lock rather than adding a second one
+        lm.acquire_lock(second, pid, true).unwrap();
+        assert_eq!(lm.get_lock_counts(pid), (0, 1));
+        lm.release_locks(second);
+        assert_eq!(lm.get_lock_counts(pid), (0, 0));
+    }
+
+    #[test]
+    fn test_simultaneous_upgrades() {
+        let lm = LockManager::new();
+        let pid = HeapPageId::new(usize::MAX, 3);
+        let older = TransactionId::new();
+        let younger = TransactionId::new();
+        lm.acquire_lock(older, pid, false).unwrap();
+        lm.acquire_lock(younger, pid, false).unwrap();
+
+        let barrier = std::sync::Barrier::new(2);
+        let (older_result, younger_result) = std::thread::scope(|s| {
+            let older_handle = s.spawn(|| {
+                barrier.wait();
+                lm.acquire_lock(older, pid, true)
+            });
+            let younger_handle = s.spawn(|| {
+                barrier.wait();
+                lm.acquire_lock(younger, pid, true)
+            });
+            (older_handle.join().unwrap(), younger_handle.join().unwrap())
+        });
+        // the younger upgrader dies and gives up its read lock, letting the older one in
+        assert!(older_result.is_ok());
+        assert_eq!(younger_result.unwrap_err().get_tid(), younger);
+        assert_eq!(lm.holds_lock(older, pid), Some(Permission::Write));
+        assert_eq!(lm.holds_lock(younger, pid), None);
+        assert_eq!(lm.get_lock_counts(pid), (0, 1));
+    }
+
+    #[test]
+    fn test_dump_locks() {
+        let lm = LockManager::new();
+        let pid = HeapPageId::new(usize::MAX, 4);
+        let other_pid = HeapPageId::new(usize::MAX, 5);
+        let reader = TransactionId::new();
+        let writer = TransactionId::new();
+        assert!(lm.dump_locks().is_empty());
+        lm.acquire_lock(reader, pid, false).unwrap();
+        lm.acquire_lock(writer, pid, false).unwrap();
+        lm.acquire_lock(writer, other_pid, true).unwrap();
+        assert_eq!(
+            lm.dump_locks(),
+            vec![
+                (pid, reader, false),
+                (pid, writer, false),
+                (other_pid, writer, true)
+            ]
+        );
+        lm.release_locks(reader);
+        assert_eq!(
+            lm.dump_locks(),
+            vec![(pid, writer, false), (other_pid, writer, true)]
+        );
+    }
+
+    #[test]
+    fn test_waiter_wakes_on_release() {
+        let lm = LockManager::new();
+        let pid = HeapPageId::new(usize::MAX, 1);
+        let waiter = TransactionId::new();
+        let holder = TransactionId::new();
+        lm.acquire_lock(holder, pid, true).unwrap();
+
+        let released = std::thread::scope(|s| {
+            let handle = s.spawn(|| {
+                lm.acquire_lock(waiter, pid, true).unwrap();
+                Instant::now()
+            });
+            std::thread::sleep(Duration::from_millis(100));
+            let released = Instant::now();
+            lm.release_locks(holder);
+            handle.join().unwrap() - released
+        });
+        // the waiter is woken when the lock is released rather than on its next poll
+        assert!(released < Duration::from_millis(100));
+        assert_eq!(lm.holds_lock(waiter, pid), Some(Permission::Write));
+
+        // the page is forgotten once nothing is waiting on it
+        lm.release_locks(waiter);
+        lm.acquire_lock(holder, pid, false).unwrap();
+        lm.release_lock(holder, pid);
+        assert!(lm.waiters.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,474 @@
+use crate::heap_page::HeapPageId;
+use crate::transaction::TransactionId;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+pub const LOG_FILE: &str = "data/log";
+
+const UPDATE_RECORD: u8 = 0;
+const COMMIT_RECORD: u8 = 1;
+const ABORT_RECORD: u8 = 2;
+const CHECKPOINT_RECORD: u8 = 3;
+
+// A single entry in the write-ahead log
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LogRecord {
+    // before and after images of a page dirtied by a transaction. Only the after image
+    // is used by recovery, see LogManager::recover.
+    Update {
+        tid: TransactionId,
+        pid: HeapPageId,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    },
+    Commit(TransactionId),
+    Abort(TransactionId),
+    // every change committed before it was on disk when it was written, see
+    // BufferPool::checkpoint
+    Checkpoint,
+}
+
+impl LogRecord {
+    pub fn get_tid(&self) -> Option<TransactionId> {
+        match self {
+            LogRecord::Update { tid, .. } | LogRecord::Commit(tid) | LogRecord::Abort(tid) => {
+                Some(*tid)
+            }
+            LogRecord::Checkpoint => None,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        match self {
+            LogRecord::Update {
+                tid,
+                pid,
+                before,
+                after,
+            } => {
+                bytes.push(UPDATE_RECORD);
+                bytes.extend(tid.get_tid().to_be_bytes());
+                bytes.extend((pid.get_table_id() as u64).to_be_bytes());
+                bytes.extend((pid.get_page_number() as u64).to_be_bytes());
+                for image in [before, after] {
+                    bytes.extend((image.len() as u32).to_be_bytes());
+                    bytes.extend(image);
+                }
+            }
+            LogRecord::Commit(tid) => {
+                bytes.push(COMMIT_RECORD);
+                bytes.extend(tid.get_tid().to_be_bytes());
+            }
+            LogRecord::Abort(tid) => {
+                bytes.push(ABORT_RECORD);
+                bytes.extend(tid.get_tid().to_be_bytes());
+            }
+            LogRecord::Checkpoint => bytes.push(CHECKPOINT_RECORD),
+        }
+        bytes
+    }
+
+    // Parses the record at the start of bytes, returning it with the number of bytes read.
+    // Returns None if the bytes don't hold a complete record, e.g. a torn write at the tail.
+    pub fn deserialize(bytes: &[u8]) -> Option<(LogRecord, usize)> {
+        let mut reader = LogReader { bytes, offset: 0 };
+        let record_type = reader.take(1)?[0];
+        if record_type == CHECKPOINT_RECORD {
+            return Some((LogRecord::Checkpoint, reader.offset));
+        }
+        let tid = TransactionId::from_tid(reader.take_u64()?);
+        let record = match record_type {
+            UPDATE_RECORD => {
+                let table_id = reader.take_u64()? as usize;
+                let page_number = reader.take_u64()? as usize;
+                let before_len = reader.take_u32()? as usize;
+                let before = reader.take(before_len)?.to_vec();
+                let after_len = reader.take_u32()? as usize;
+                let after = reader.take(after_len)?.to_vec();
+                LogRecord::Update {
+                    tid,
+                    pid: HeapPageId::new(table_id, page_number),
+                    before,
+                    after,
+                }
+            }
+            COMMIT_RECORD => LogRecord::Commit(tid),
+            ABORT_RECORD => LogRecord::Abort(tid),
+            _ => return None,
+        };
+        Some((record, reader.offset))
+    }
+}
+
+// Cursor over the raw bytes of the log
+struct LogReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> LogReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(len)?;
+        let slice = self.bytes.get(self.offset..end)?;
+        self.offset = end;
+        Some(slice)
+    }
+
+    fn take_u32(&mut self) -> Option<u32> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Some(u32::from_be_bytes(buf))
+    }
+
+    fn take_u64(&mut self) -> Option<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Some(u64::from_be_bytes(buf))
+    }
+}
+
+// Appends records to the write-ahead log and replays them on startup
+pub struct LogManager {
+    file: Mutex<File>,
+    path: String,
+}
+
+impl LogManager {
+    pub fn new(log_path: &str) -> Self {
+        if let Some(parent) = Path::new(log_path).parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        LogManager {
+            file: Mutex::new(Self::open(log_path)),
+            path: log_path.to_string(),
+        }
+    }
+
+    fn open(log_path: &str) -> File {
+        OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(log_path)
+            .unwrap()
+    }
+
+    fn append(&self, record: &LogRecord) {
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&record.serialize()).unwrap();
+    }
+
+    // Records the before and after images of a page modified by the transaction
+    pub fn log_update(&self, tid: TransactionId, pid: HeapPageId, before: Vec<u8>, after: Vec<u8>) {
+        self.append(&LogRecord::Update {
+            tid,
+            pid,
+            before,
+            after,
+        });
+    }
+
+    // Records the commit and forces the log to disk, so it must happen before any page writes
+    pub fn log_commit(&self, tid: TransactionId) {
+        self.append(&LogRecord::Commit(tid));
+        self.file.lock().unwrap().sync_data().unwrap();
+    }
+
+    pub fn log_abort(&self, tid: TransactionId) {
+        self.append(&LogRecord::Abort(tid));
+    }
+
+    // Records a checkpoint and forces the log to disk, so it must only happen once every
+    // committed change is on disk
+    pub fn log_checkpoint(&self) {
+        self.append(&LogRecord::Checkpoint);
+        self.file.lock().unwrap().sync_data().unwrap();
+    }
+
+    // Drops the records recovery no longer needs, everything before the last checkpoint.
+    // A transaction's updates are logged together with its commit, so none of them are
+    // split by a checkpoint. The kept records are written to a new file that replaces the
+    // log in one rename, so a crash part way through leaves the old log whole.
+    pub fn truncate(&self) {
+        let mut file = self.file.lock().unwrap();
+        let records = Self::read_all(&mut file);
+        let Some(checkpoint) = records.iter().rposition(|r| *r == LogRecord::Checkpoint) else {
+            return;
+        };
+        let mut bytes = vec![];
+        for record in records[checkpoint..].iter() {
+            bytes.extend(record.serialize());
+        }
+        let tmp_path = format!("{}.tmp", self.path);
+        let mut tmp_file = File::create(&tmp_path).unwrap();
+        tmp_file.write_all(&bytes).unwrap();
+        tmp_file.sync_all().unwrap();
+        std::fs::rename(&tmp_path, &self.path).unwrap();
+        // make the rename itself durable
+        let parent = Path::new(&self.path)
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        File::open(parent).unwrap().sync_all().unwrap();
+        *file = Self::open(&self.path);
+    }
+
+    // Reads every complete record in the log, in the order they were written
+    pub fn read_records(&self) -> Vec<LogRecord> {
+        Self::read_all(&mut self.file.lock().unwrap())
+    }
+
+    fn read_all(file: &mut File) -> Vec<LogRecord> {
+        let mut bytes = vec![];
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut records = vec![];
+        let mut offset = 0;
+        while let Some((record, len)) = LogRecord::deserialize(&bytes[offset..]) {
+            records.push(record);
+            offset += len;
+        }
+        records
+    }
+
+    // Runs a redo pass over the log and returns the image each logged page should have on
+    // disk: the last after image written by a committed transaction. Redo starts at the
+    // last checkpoint, since earlier committed changes are already on disk.
+    // Nothing is undone: the buffer pool never writes a page with uncommitted changes, and
+    // a transaction's updates are only logged when it commits, so updates without a commit
+    // record after them come from a crash part way through committing and are ignored.
+    pub fn recover(&self) -> HashMap<HeapPageId, Vec<u8>> {
+        let records = self.read_records();
+        let committed = committed_transactions(&records);
+        let redo_start = records
+            .iter()
+            .rposition(|r| *r == LogRecord::Checkpoint)
+            .map_or(0, |i| i + 1);
+
+        let mut pages = HashMap::new();
+        // redo committed transactions from oldest to newest
+        for record in records[redo_start..].iter() {
+            if let LogRecord::Update {
+                tid, pid, after, ..
+            } = record
+            {
+                if committed.contains(tid) {
+                    pages.insert(*pid, after.clone());
+                }
+            }
+        }
+        pages
+    }
+}
+
+// Gets the transactions that committed in the records
+fn committed_transactions(records: &[LogRecord]) -> HashSet<TransactionId> {
+    records
+        .iter()
+        .filter_map(|record| match record {
+            LogRecord::Commit(tid) => Some(*tid),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_pool::PAGE_SIZE;
+    use crate::database::Database;
+    use crate::fields::{FieldVal, IntField};
+    use crate::heap_file::HeapFile;
+    use crate::heap_page::HeapPage;
+    use crate::tuple::{RecordId, Tuple, TupleDesc};
+    use crate::types::Type;
+    use uuid::Uuid;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}_{}", name, Uuid::new_v4()))
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_log_record_round_trip() {
+        let records = vec![
+            LogRecord::Update {
+                tid: TransactionId::from_tid(3),
+                pid: HeapPageId::new(7, 2),
+                before: vec![0; 16],
+                after: vec![1; 16],
+            },
+            LogRecord::Commit(TransactionId::from_tid(3)),
+            LogRecord::Abort(TransactionId::from_tid(4)),
+            LogRecord::Checkpoint,
+        ];
+        let log = LogManager::new(&temp_path("log"));
+        for record in records.iter() {
+            log.append(record);
+        }
+        assert_eq!(log.read_records(), records);
+
+        // a torn record at the tail is ignored
+        let bytes = records[0].serialize();
+        assert_eq!(LogRecord::deserialize(&bytes[..bytes.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_recovery_after_restart() {
+        let log_path = temp_path("log");
+        let table_path = temp_path("table");
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let table_id = Uuid::new_v4().as_u128() as usize;
+        let open_table = || {
+            let file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(&table_path)
+                .unwrap();
+            HeapFile::new_with_id(file, td.clone(), table_id)
+        };
+
+        // committed transaction whose pages never made it to disk before the crash
+        let committed = TransactionId::from_tid(1);
+        let pid = HeapPageId::new(table_id, 0);
+        let mut page = HeapPage::new(pid, vec![0; PAGE_SIZE], td.clone());
+        let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(42))], &td);
+        page.add_tuple(tuple.clone()).unwrap();
+        // transaction that crashed part way through committing
+        let in_flight = TransactionId::from_tid(2);
+        let pid2 = HeapPageId::new(table_id, 1);
+        let mut page2 = HeapPage::new(pid2, vec![0; PAGE_SIZE], td.clone());
+        page2.add_tuple(tuple.clone()).unwrap();
+        {
+            let log = LogManager::new(&log_path);
+            log.log_update(committed, pid, vec![0; PAGE_SIZE], page.get_page_data());
+            log.log_commit(committed);
+            log.log_update(in_flight, pid2, vec![0; PAGE_SIZE], page2.get_page_data());
+        }
+
+        // restart: a new database recovers from the log once the table is registered
+        let db = Database::new_with_log(&log_path);
+        db.get_catalog()
+            .add_table(open_table(), "recovered".to_string());
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+        let recovered = table.read_page(&pid).unwrap();
+        let mut tuple = tuple;
+        tuple.set_record_id(RecordId::new(pid, 0));
+        assert_eq!(recovered.iter().cloned().collect::<Vec<_>>(), vec![tuple]);
+        assert_eq!(table.read_page(&pid2).unwrap().iter().count(), 0);
+    }
+
+    #[test]
+    fn test_truncate_replaces_log() {
+        let log_path = temp_path("log");
+        let tmp_path = format!("{}.tmp", log_path);
+        // left behind by a crash during an earlier truncate
+        std::fs::write(&tmp_path, b"partial").unwrap();
+        let (done, running) = (TransactionId::from_tid(1), TransactionId::from_tid(2));
+        let pid = HeapPageId::new(1, 0);
+        let log = LogManager::new(&log_path);
+        log.log_update(done, pid, vec![1], vec![2]);
+        log.log_commit(done);
+        log.log_checkpoint();
+        log.truncate();
+        assert!(!Path::new(&tmp_path).exists());
+
+        // records appended after truncating go to the new log
+        log.log_update(running, pid, vec![2], vec![3]);
+        log.log_commit(running);
+        let expected = vec![
+            LogRecord::Checkpoint,
+            LogRecord::Update {
+                tid: running,
+                pid,
+                before: vec![2],
+                after: vec![3],
+            },
+            LogRecord::Commit(running),
+        ];
+        assert_eq!(log.read_records(), expected);
+        assert_eq!(LogManager::new(&log_path).read_records(), expected);
+        std::fs::remove_file(&log_path).unwrap();
+    }
+
+    #[test]
+    fn test_recovery_from_checkpoint() {
+        let log_path = temp_path("log");
+        let table_path = temp_path("table");
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let table_id = Uuid::new_v4().as_u128() as usize;
+        let open_table = || {
+            let file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(&table_path)
+                .unwrap();
+            HeapFile::new_with_id(file, td.clone(), table_id)
+        };
+        let page_with = |page_no: usize, value: i32| {
+            let pid = HeapPageId::new(table_id, page_no);
+            let mut page = HeapPage::new(pid, vec![0; PAGE_SIZE], td.clone());
+            let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(value))], &td);
+            page.add_tuple(tuple).unwrap();
+            page
+        };
+        let empty = vec![0; PAGE_SIZE];
+        let (before, in_flight, after) = (
+            TransactionId::from_tid(1),
+            TransactionId::from_tid(2),
+            TransactionId::from_tid(3),
+        );
+        let (page0, page1, page2) = (page_with(0, 10), page_with(1, 20), page_with(2, 30));
+        {
+            let log = LogManager::new(&log_path);
+            // committed and written out by the checkpoint
+            log.log_update(before, page0.get_id(), empty.clone(), page0.get_page_data());
+            log.log_commit(before);
+            open_table().write_page(&page0);
+            // crashed part way through committing, before the checkpoint
+            log.log_update(
+                in_flight,
+                page1.get_id(),
+                empty.clone(),
+                page1.get_page_data(),
+            );
+            log.log_checkpoint();
+            // committed after the checkpoint, but the crash came before its page was written
+            log.log_update(after, page2.get_id(), empty.clone(), page2.get_page_data());
+            log.log_commit(after);
+
+            log.truncate();
+            let tids: Vec<Option<TransactionId>> =
+                log.read_records().iter().map(|r| r.get_tid()).collect();
+            assert_eq!(tids, vec![None, Some(after), Some(after)]);
+            // redo starts at the checkpoint
+            assert!(!log.recover().contains_key(&page0.get_id()));
+        }
+
+        let db = Database::new_with_log(&log_path);
+        db.get_catalog()
+            .add_table(open_table(), "checkpointed".to_string());
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+        let values = |page_no| -> Vec<i32> {
+            let page = table
+                .read_page(&HeapPageId::new(table_id, page_no))
+                .unwrap();
+            page.iter().map(|t| t.get_int(0).unwrap()).collect()
+        };
+        assert_eq!(values(0), vec![10]);
+        assert_eq!(values(1), Vec::<i32>::new());
+        assert_eq!(values(2), vec![30]);
+    }
+}
@@ -1,17 +1,6 @@
-mod buffer_pool;
-mod catalog;
-mod database;
-mod fields;
-mod heap_file;
-mod heap_page;
-mod lock_manager;
-mod table;
-mod transaction;
-mod tuple;
-mod types;
-mod view;
-
+use rustic_db::{database, fields, table, transaction, tuple};
 use std::thread;
+
 fn main() {
     let db = database::get_global_db();
 
@@ -19,20 +8,20 @@ fn main() {
     let mut schema_file_path = std::env::current_dir().unwrap();
     schema_file_path.push("schemas.txt");
     db.get_catalog()
-        .load_schema(schema_file_path.to_str().unwrap());
+        .load_schema(schema_file_path.to_str().unwrap())
+        .unwrap();
 
     // 2. Retrieve the list of catalogs
     let catalog = db.get_catalog();
 
     // 3. Retrieve the table id for the employee table
-    let table = catalog.get_table_from_name("employees").unwrap();
-    let table_id = table.get_id();
+    let table_id = catalog.get_table_id_from_name("employees").unwrap();
 
     // 4. Retrieve the tuple descriptor for the employee table
-    let td = table.get_tuple_desc().clone();
+    let td = catalog.get_tuple_desc(table_id).unwrap();
 
     // 5. Insert 3 tuples into the employee table in 3 separate threads
-    // threads panic if aborted by WAIT-DIE protocol
+    // transactions aborted by the WAIT-DIE protocol are retried by run_transaction
     println!("table id: {}", table_id);
     println!("table name: {:?}", td.get_field_name(0));
     let handles: Vec<_> = (0..3)
@@ -40,12 +29,11 @@ fn main() {
             let db = database::get_global_db();
             let table = db.get_catalog().get_table_from_id(table_id).unwrap();
             let td = table.get_tuple_desc().clone();
-            thread::spawn(move || loop {
-                let res = std::panic::catch_unwind(|| {
-                    let tid = transaction::TransactionId::new();
+            thread::spawn(move || {
+                db.run_transaction(|tid| {
                     let bp = db.get_buffer_pool();
                     let name = format!("Alice_{}", tid.get_tid());
-                    for i in 0..3 {
+                    (0..3).try_for_each(|i| {
                         bp.insert_tuple(
                             tid,
                             table_id,
@@ -59,17 +47,11 @@ fn main() {
                                 ],
                                 &td,
                             ),
-                        );
-                    }
-                    bp.commit_transaction(tid);
-                });
-                if res.is_err() {
-                    println!("thread {:?} aborted", thread::current().id());
-                    thread::sleep(std::time::Duration::from_millis(500));
-                } else {
-                    println!("thread {:?} committed", thread::current().id());
-                    break;
-                }
+                        )
+                    })
+                })
+                .unwrap();
+                println!("thread {:?} committed", thread::current().id());
             })
         })
         .collect();
@@ -84,6 +66,7 @@ fn main() {
     let tid = transaction::TransactionId::new();
     let table = catalog.get_table_from_id(table_id).unwrap();
     for page in table.iter(tid) {
+        let page = page.unwrap();
         let page = page.read().unwrap();
         page_count += 1;
         for tuple in page.iter() {
@@ -94,45 +77,62 @@ fn main() {
     let bp = db.get_buffer_pool();
     bp.commit_transaction(tid);
 
-    print!("page count: {}\n", page_count);
-    print!("tuple count: {}\n", tuple_count);
+    println!("page count: {}", page_count);
+    println!("tuple count: {}", tuple_count);
 
     // my stuff trying to create user friendly tables
     print!("my stuff\n\n\n");
 
-    let my_table = table::Table::new("employess".to_string(), "schema.txt".to_string());
-
-    my_table.insert_tuple(
-        tuple::Tuple::new(
-            vec![
-                fields::FieldVal::IntField(fields::IntField::new(1)),
-                fields::FieldVal::StringField(fields::StringField::new("Alice".to_string(), 7)),
-            ],
-            &td,
-        ),
-        transaction::TransactionId::new(),
-    );
+    let my_table = table::Table::new("employees".to_string(), "schema.txt".to_string()).unwrap();
+    let tid = transaction::TransactionId::new();
 
-    my_table.print();
+    my_table
+        .insert_tuple(
+            tuple::Tuple::new(
+                vec![
+                    fields::FieldVal::IntField(fields::IntField::new(1)),
+                    fields::FieldVal::StringField(fields::StringField::new("Alice".to_string(), 7)),
+                ],
+                &td,
+            ),
+            tid,
+        )
+        .unwrap();
+    db.get_buffer_pool().commit_transaction(tid);
+
+    my_table.print().unwrap();
+
+    db.shutdown();
 }
 
 #[cfg(test)]
 mod test {
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex, MutexGuard};
 
     use super::*;
+    use rustic_db::transaction::TransactionAbort;
+
+    // tests using the tables in schemas.txt run one at a time so that their
+    // transactions only conflict with each other
+    static SHARED_TABLES_LOCK: Mutex<()> = Mutex::new(());
+
+    fn lock_shared_tables() -> MutexGuard<'static, ()> {
+        SHARED_TABLES_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
 
     #[test]
     fn test_table() {
+        let _guard = lock_shared_tables();
         let db = database::get_global_db();
 
         // 1. Load the schemas and tables from the schemas.txt file
         let mut schema_file_path = std::env::current_dir().unwrap();
         schema_file_path.push("schemas.txt");
         db.get_catalog()
-            .load_schema(schema_file_path.to_str().unwrap());
+            .load_schema(schema_file_path.to_str().unwrap())
+            .unwrap();
 
-        let my_table = table::Table::new("products".to_string(), "schema.txt".to_string());
+        let my_table = table::Table::new("products".to_string(), "schema.txt".to_string()).unwrap();
 
         // We can inserting tuples one at a time
         let tuple_to_insert = tuple::Tuple::new(
@@ -143,7 +143,7 @@ mod test {
             &my_table.get_tuple_desc().clone(),
         );
         let tid = transaction::TransactionId::new();
-        my_table.insert_tuple(tuple_to_insert.clone(), tid);
+        my_table.insert_tuple(tuple_to_insert.clone(), tid).unwrap();
 
         // Insert multiple tuples into the table
         let tuple_collection = (1..20)
@@ -162,18 +162,18 @@ mod test {
                 )
             })
             .collect();
-        my_table.insert_many_tuples(tuple_collection, tid);
+        my_table.insert_many_tuples(tuple_collection, tid).unwrap();
 
         // We can then scan the table to see all of our results
         println!("-------------");
         println!("----SCAN-----");
         println!("-------------");
-        let scan = my_table.scan(20, tid);
+        let scan = my_table.scan(20, tid).unwrap();
         for tuple in scan.into_iter() {
             println!("{}", tuple);
         }
 
-        let mut scan2 = my_table.scan(5, tid);
+        let mut scan2 = my_table.scan(5, tid).unwrap();
 
         // simple filtering, using a predicate
         println!("---------------");
@@ -181,7 +181,7 @@ mod test {
         println!("---------------");
         let pred = table::Predicate::GreaterThan(1);
         scan2.table_filter("id", pred);
-        for tuple in scan2.into_iter() {
+        for tuple in scan2 {
             println!("{}", tuple);
         }
         // performing a filter on the scan, on the field "id" with the predicate "GreaterThan(1)"
@@ -190,7 +190,7 @@ mod test {
         println!("----JOINS----");
         println!("-------------");
         // load up second table
-        let my_table2 = table::Table::new("test2".to_string(), "schema.txt".to_string());
+        let my_table2 = table::Table::new("test2".to_string(), "schema.txt".to_string()).unwrap();
         let tuple_collection2 = (5..10)
             .map(|i| {
                 let name = format!("Alice_{}", i);
@@ -207,12 +207,14 @@ mod test {
                 )
             })
             .collect();
-        my_table2.insert_many_tuples(tuple_collection2, tid);
+        my_table2
+            .insert_many_tuples(tuple_collection2, tid)
+            .unwrap();
 
         // grab two scans, combine both scans into a join
-        let scan3 = my_table2.scan(5, tid);
-        let scan4 = my_table.scan(20, tid);
-        let join = scan3.join(&scan4, "title", "id");
+        let scan3 = my_table2.scan(5, tid).unwrap();
+        let scan4 = my_table.scan(20, tid).unwrap();
+        let join = scan3.join(&scan4, "title", "id").unwrap();
 
         for tuple in join {
             println!("{}", tuple);
@@ -221,34 +223,37 @@ mod test {
         println!("--------------");
         println!("--PROJECTION--");
         println!("--------------");
-        let scan5 = my_table.scan(2, tid);
-        let proj = scan5.project(vec!["id".to_string()]);
+        let scan5 = my_table.scan(2, tid).unwrap();
+        let proj = scan5.project(vec!["id".to_string()]).unwrap();
         for tuple in proj {
             println!("{}", tuple);
         }
+        db.get_buffer_pool().commit_transaction(tid);
     }
 
     #[test]
     fn test_asynchronous_scan() {
+        let _guard = lock_shared_tables();
         let db = database::get_global_db();
         let mut schema_file_path = std::env::current_dir().unwrap();
         schema_file_path.push("schemas.txt");
         db.get_catalog()
-            .load_schema(schema_file_path.to_str().unwrap());
+            .load_schema(schema_file_path.to_str().unwrap())
+            .unwrap();
 
-        let table = Arc::new(table::Table::new(
-            "products".to_string(),
-            "schema.txt".to_string(),
-        ));
+        let table =
+            Arc::new(table::Table::new("products".to_string(), "schema.txt".to_string()).unwrap());
         let handles: Vec<_> = (0..10)
             .map(|i| {
                 let table = table.clone();
                 thread::spawn(move || {
                     let tid = transaction::TransactionId::new();
-                    let scan = table.scan(2, tid);
+                    let scan = table.scan(2, tid).unwrap();
                     for tuple in scan.into_iter() {
                         println!("{} - Thread {}", tuple, i);
                     }
+                    let db = database::get_global_db();
+                    db.get_buffer_pool().commit_transaction(tid);
                 })
             })
             .collect();
@@ -257,31 +262,53 @@ mod test {
         }
     }
 
+    fn print_committed(handles: Vec<thread::JoinHandle<Result<(), String>>>) {
+        for handle in handles {
+            match handle.join().unwrap() {
+                Ok(_) => println!("Transaction committed"),
+                Err(abort) => println!("{}", abort),
+            }
+        }
+    }
+
+    fn print_table(table: &table::Table) {
+        let tid = transaction::TransactionId::new();
+        for tuple in table.scan(20, tid).unwrap() {
+            println!("{}", tuple);
+        }
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
     #[test]
     fn test_younger_transaction_aborts() {
+        let _guard = lock_shared_tables();
         let db = database::get_global_db();
         let mut schema_file_path = std::env::current_dir().unwrap();
         schema_file_path.push("schemas.txt");
         db.get_catalog()
-            .load_schema(schema_file_path.to_str().unwrap());
+            .load_schema(schema_file_path.to_str().unwrap())
+            .unwrap();
 
-        let table = Arc::new(table::Table::new(
-            "testwrites".to_string(),
-            "schema.txt".to_string(),
-        ));
+        let table = Arc::new(
+            table::Table::new("testwrites".to_string(), "schema.txt".to_string()).unwrap(),
+        );
+        // the first transaction is older than the second
+        let tids: Vec<_> = (0..2).map(|_| transaction::TransactionId::new()).collect();
         let handles: Vec<_> = (0..2)
-            .map(|_| {
+            .map(|i| {
                 let table = table.clone();
                 let db = database::get_global_db();
+                let tid = tids[i];
                 thread::spawn(move || {
                     // second transaction waits for 500 ms for first transaction to insert
                     // their first tuple
-                    let tid = transaction::TransactionId::new();
-                    if tid.get_tid() == 1 {
+                    if i == 1 {
                         thread::sleep(std::time::Duration::from_millis(500));
                     }
                     // inserted i should be 0 from first transaction and 1 for second transaction
-                    let i = tid.get_tid() as i32;
+                    let i = i as i32;
                     let mut tuple = tuple::Tuple::new(
                         vec![
                             fields::FieldVal::IntField(fields::IntField::new(i)),
@@ -292,7 +319,7 @@ mod test {
                         ],
                         &table.get_tuple_desc().clone(),
                     );
-                    table.insert_tuple(tuple.clone(), tid);
+                    table.insert_tuple(tuple.clone(), tid)?;
                     // first transaction sleeps and allows second thread to attempt insertion
                     // second transaction should abort since first transaction has write lock
                     thread::sleep(std::time::Duration::from_millis(2000 * (-i + 1) as u64));
@@ -303,52 +330,49 @@ mod test {
                             7,
                         )),
                     );
-                    table.insert_tuple(tuple, tid);
+                    table.insert_tuple(tuple, tid)?;
                     let bp = db.get_buffer_pool();
                     bp.commit_transaction(tid);
+                    Ok(())
                 })
             })
             .collect();
-        for handle in handles {
-            match handle.join() {
-                Ok(_) => println!("Transaction committed"),
-                Err(_) => println!("Transaction aborted"),
-            }
-        }
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results[0], Ok(()));
+        assert_eq!(results[1], Err(TransactionAbort::new(tids[1]).to_string()));
 
         // table should only have the tuples inserted by the first transaction
-        for tuple in table.scan(10, transaction::TransactionId::new()) {
-            println!("{}", tuple);
-        }
+        print_table(&table);
     }
 
     #[test]
     fn test_older_transaction_waits() {
+        let _guard = lock_shared_tables();
         let db = database::get_global_db();
         let mut schema_file_path = std::env::current_dir().unwrap();
         schema_file_path.push("schemas.txt");
         db.get_catalog()
-            .load_schema(schema_file_path.to_str().unwrap());
+            .load_schema(schema_file_path.to_str().unwrap())
+            .unwrap();
 
-        let table = Arc::new(table::Table::new(
-            "testwrites".to_string(),
-            "schema.txt".to_string(),
-        ));
+        let table = Arc::new(
+            table::Table::new("testwrites".to_string(), "schema.txt".to_string()).unwrap(),
+        );
+        let tids: Vec<_> = (0..2).map(|_| transaction::TransactionId::new()).collect();
         let handles: Vec<_> = (0..2)
-            .map(|_| {
+            .map(|i| {
                 let table = table.clone();
                 let db = database::get_global_db();
+                let tid = tids[i];
                 thread::spawn(move || {
                     // first transaction waits for 500 ms for second transaction to start insert
-                    let tid = transaction::TransactionId::new();
-                    let i = tid.get_tid() as i32;
                     if i == 0 {
                         thread::sleep(std::time::Duration::from_millis(500));
                     }
                     // second transaction should insert first and have write lock
                     let mut tuple = tuple::Tuple::new(
                         vec![
-                            fields::FieldVal::IntField(fields::IntField::new(tid.get_tid() as i32)),
+                            fields::FieldVal::IntField(fields::IntField::new(i as i32)),
                             fields::FieldVal::StringField(fields::StringField::new(
                                 format!("Alice_{}", i),
                                 7,
@@ -356,7 +380,7 @@ mod test {
                         ],
                         &table.get_tuple_desc().clone(),
                     );
-                    table.insert_tuple(tuple.clone(), tid);
+                    table.insert_tuple(tuple.clone(), tid)?;
                     // second transaction sleeps and first transaction will try to insert
                     // first transaction should wait since second transaction has write lock
                     if i == 1 {
@@ -365,45 +389,41 @@ mod test {
                     tuple.set_field(
                         1,
                         fields::FieldVal::StringField(fields::StringField::new(
-                            format!("Bob_{}", tid.get_tid()),
+                            format!("Bob_{}", i),
                             5,
                         )),
                     );
-                    table.insert_tuple(tuple, tid);
+                    table.insert_tuple(tuple, tid)?;
                     let bp = db.get_buffer_pool();
                     bp.commit_transaction(tid);
+                    Ok(())
                 })
             })
             .collect();
-        for handle in handles {
-            match handle.join() {
-                Ok(_) => println!("Transaction committed"),
-                Err(_) => println!("Transaction aborted"),
-            }
-        }
+        let results: Vec<Result<(), String>> =
+            handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, vec![Ok(()), Ok(())]);
 
         // we should see all 4 tuples inserted with transaction 1's tuples first
-        for tuple in table.scan(10, transaction::TransactionId::new()) {
-            println!("{}", tuple);
-        }
+        print_table(&table);
     }
 
     #[test]
     fn test_inserting_different_tables() {
+        let _guard = lock_shared_tables();
         let db = database::get_global_db();
         let mut schema_file_path = std::env::current_dir().unwrap();
         schema_file_path.push("schemas.txt");
         db.get_catalog()
-            .load_schema(schema_file_path.to_str().unwrap());
-
-        let table1 = Arc::new(table::Table::new(
-            "testwrites".to_string(),
-            "schema.txt".to_string(),
-        ));
-        let table2 = Arc::new(table::Table::new(
-            "testwrites2".to_string(),
-            "schema.txt".to_string(),
-        ));
+            .load_schema(schema_file_path.to_str().unwrap())
+            .unwrap();
+
+        let table1 = Arc::new(
+            table::Table::new("testwrites".to_string(), "schema.txt".to_string()).unwrap(),
+        );
+        let table2 = Arc::new(
+            table::Table::new("testwrites2".to_string(), "schema.txt".to_string()).unwrap(),
+        );
         let handles: Vec<_> = (0..2)
             .map(|t| {
                 let table = if t == 0 {
@@ -434,52 +454,44 @@ mod test {
                         })
                         .collect();
 
-                    table.insert_many_tuples(tuple_collection, tid);
+                    table.insert_many_tuples(tuple_collection, tid)?;
                     let bp = db.get_buffer_pool();
                     bp.commit_transaction(tid);
+                    Ok(())
                 })
             })
             .collect();
-        for handle in handles {
-            match handle.join() {
-                Ok(_) => println!("Transaction committed"),
-                Err(_) => println!("Transaction aborted"),
-            }
-        }
+        print_committed(handles);
 
         // we should see all the tuples inserted
-        for tuple in table1.scan(20, transaction::TransactionId::new()) {
-            println!("{}", tuple);
-        }
-        for tuple in table2.scan(20, transaction::TransactionId::new()) {
-            println!("{}", tuple);
-        }
+        print_table(&table1);
+        print_table(&table2);
     }
 
     #[test]
     fn test_recovery_from_abort() {
+        let _guard = lock_shared_tables();
         let db = database::get_global_db();
         let mut schema_file_path = std::env::current_dir().unwrap();
         schema_file_path.push("schemas.txt");
         db.get_catalog()
-            .load_schema(schema_file_path.to_str().unwrap());
-
-        let table1 = Arc::new(table::Table::new(
-            "testwrites".to_string(),
-            "schema.txt".to_string(),
-        ));
-        let table2 = Arc::new(table::Table::new(
-            "testwrites2".to_string(),
-            "schema.txt".to_string(),
-        ));
+            .load_schema(schema_file_path.to_str().unwrap())
+            .unwrap();
+
+        let table1 = Arc::new(
+            table::Table::new("testwrites".to_string(), "schema.txt".to_string()).unwrap(),
+        );
+        let table2 = Arc::new(
+            table::Table::new("testwrites2".to_string(), "schema.txt".to_string()).unwrap(),
+        );
         let tables = vec![table1.clone(), table2.clone()];
+        let tids: Vec<_> = (0..2).map(|_| transaction::TransactionId::new()).collect();
         let handles: Vec<_> = (0..2)
-            .map(|_| {
+            .map(|i| {
                 let db = database::get_global_db();
                 let tables = tables.clone();
+                let tid = tids[i];
                 thread::spawn(move || {
-                    let tid = transaction::TransactionId::new();
-                    let i = tid.get_tid() as usize;
                     let tuple = tuple::Tuple::new(
                         vec![
                             fields::FieldVal::IntField(fields::IntField::new(tid.get_tid() as i32)),
@@ -490,7 +502,7 @@ mod test {
                         ],
                         &tables[0].get_tuple_desc().clone(),
                     );
-                    tables[i].insert_tuple(tuple, tid);
+                    tables[i].insert_tuple(tuple, tid)?;
                     // second transaction waits to make sure first transaction has write
                     // lock on the first table
                     if i == 1 {
@@ -507,27 +519,19 @@ mod test {
                         &tables[1].get_tuple_desc().clone(),
                     );
                     // second transaction should abort since first transaction has write lock
-                    tables[(i + 1) % 2].insert_tuple(tuple, tid);
+                    tables[(i + 1) % 2].insert_tuple(tuple, tid)?;
                     let bp = db.get_buffer_pool();
                     bp.commit_transaction(tid);
+                    Ok(())
                 })
             })
             .collect();
-        for handle in handles {
-            match handle.join() {
-                Ok(_) => println!("Transaction committed"),
-                Err(_) => println!("Transaction aborted"),
-            }
-        }
+        print_committed(handles);
 
         // we should only see the tuples inserted by the first transaction
         println!("table 1");
-        for tuple in table1.scan(20, transaction::TransactionId::new()) {
-            println!("{}", tuple);
-        }
+        print_table(&table1);
         println!("table 2");
-        for tuple in table2.scan(20, transaction::TransactionId::new()) {
-            println!("{}", tuple);
-        }
+        print_table(&table2);
     }
 }
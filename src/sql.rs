@@ -0,0 +1,298 @@
+use crate::table::{Predicate, Table};
+use crate::transaction::TransactionId;
+use crate::tuple::Tuple;
+use crate::types::Type;
+
+// A parsed SELECT cols FROM table [WHERE col op value] [LIMIT n] query
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    // the selected columns, None for SELECT *
+    pub columns: Option<Vec<String>>,
+    pub table: String,
+    pub filter: Option<(String, Predicate)>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    // a quoted string literal
+    Text(String),
+    Symbol(char),
+}
+
+// Splits a query into words, 'quoted' strings and the symbols , * = < >
+fn tokenize(query: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if matches!(c, ',' | '*' | '=' | '<' | '>') {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else if c == '\'' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    // '' stands for a quote inside the string
+                    Some('\'') if chars.peek() == Some(&'\'') => {
+                        text.push('\'');
+                        chars.next();
+                    }
+                    Some('\'') => break,
+                    Some(c) => text.push(c),
+                    None => return Err("unterminated string literal".to_string()),
+                }
+            }
+            tokens.push(Token::Text(text));
+        } else if c.is_alphanumeric() || c == '_' || c == '-' {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_' || c == '-') {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        } else {
+            return Err(format!("unexpected character {:?}", c));
+        }
+    }
+    Ok(tokens)
+}
+
+// Cursor over the tokens of a query
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if !self.peek_keyword(keyword) {
+            return Err(format!("expected {}", keyword));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expect_word(&mut self, what: &str) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            _ => Err(format!("expected {}", what)),
+        }
+    }
+}
+
+// Parses a SELECT query. Only =, < and > on ints and = on 'quoted' strings are supported
+// in the WHERE clause, and keywords are case-insensitive.
+pub fn parse_select(query: &str) -> Result<Query, String> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        pos: 0,
+    };
+    parser.expect_keyword("SELECT")?;
+    let columns = if parser.tokens.get(parser.pos) == Some(&Token::Symbol('*')) {
+        parser.pos += 1;
+        None
+    } else {
+        let mut columns = vec![parser.expect_word("column name")?];
+        while parser.tokens.get(parser.pos) == Some(&Token::Symbol(',')) {
+            parser.pos += 1;
+            columns.push(parser.expect_word("column name")?);
+        }
+        Some(columns)
+    };
+    parser.expect_keyword("FROM")?;
+    let table = parser.expect_word("table name")?;
+
+    let mut filter = None;
+    if parser.peek_keyword("WHERE") {
+        parser.pos += 1;
+        let column = parser.expect_word("column name")?;
+        let op = match parser.next() {
+            Some(Token::Symbol(op @ ('=' | '<' | '>'))) => op,
+            _ => return Err("expected one of =, < or >".to_string()),
+        };
+        let predicate = match (op, parser.next()) {
+            ('=', Some(Token::Text(text))) => Predicate::Equals(text),
+            (_, Some(Token::Text(_))) => {
+                return Err(format!("{} is not supported on strings", op));
+            }
+            (op, Some(Token::Word(word))) => {
+                let value = word
+                    .parse()
+                    .map_err(|_| format!("expected an int or a 'string', found {}", word))?;
+                match op {
+                    '=' => Predicate::EqualsInt(value),
+                    '<' => Predicate::LessThan(value),
+                    _ => Predicate::GreaterThan(value),
+                }
+            }
+            _ => return Err(format!("expected a value after {}", op)),
+        };
+        filter = Some((column, predicate));
+    }
+
+    let mut limit = None;
+    if parser.peek_keyword("LIMIT") {
+        parser.pos += 1;
+        let n = parser.expect_word("row count")?;
+        limit = Some(n.parse().map_err(|_| format!("invalid limit {}", n))?);
+    }
+    if let Some(token) = parser.next() {
+        return Err(format!("unexpected {:?} at end of query", token));
+    }
+    Ok(Query {
+        columns,
+        table,
+        filter,
+        limit,
+    })
+}
+
+impl Query {
+    // Runs the query against the catalog's tables, failing if the table or a column
+    // doesn't exist, the WHERE value doesn't match the column's type, or the transaction
+    // is aborted while scanning
+    pub fn execute(&self, tid: TransactionId) -> Result<Vec<Tuple>, String> {
+        let table = Table::new(self.table.clone(), "schema.txt".to_string())?;
+        let td = table.get_tuple_desc();
+        let columns = self.columns.iter().flatten();
+        for column in columns.chain(self.filter.iter().map(|(column, _)| column)) {
+            if td.name_to_id(column).is_none() {
+                return Err(format!("unknown column {}", column));
+            }
+        }
+
+        let mut scan = table.scan_all(tid)?;
+        if let Some((column, predicate)) = &self.filter {
+            let field_type = td.get_field_type(td.name_to_id(column).unwrap()).unwrap();
+            let expected_type = match predicate {
+                Predicate::Equals(_)
+                | Predicate::NotEquals(_)
+                | Predicate::Contains(_)
+                | Predicate::StartsWith(_)
+                | Predicate::StringGreaterThan(_)
+                | Predicate::StringLessThan(_) => Type::StringType,
+                _ => Type::IntType,
+            };
+            if *field_type != expected_type {
+                return Err(format!("column {} is a {:?}", column, field_type));
+            }
+            scan.table_filter(column, predicate.clone());
+        }
+        let rows = scan.limit(self.limit.unwrap_or(usize::MAX));
+        match &self.columns {
+            Some(columns) => Ok(rows.project(columns.clone())?.collect()),
+            None => Ok(rows.collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::fields::{FieldVal, IntField, StringField};
+    use crate::heap_file::create_test_heap_file;
+    use crate::tuple::TupleDesc;
+
+    #[test]
+    fn test_parse_select() {
+        let query = parse_select("select id, name FROM users where id > 3 LIMIT 2").unwrap();
+        assert_eq!(
+            query,
+            Query {
+                columns: Some(vec!["id".to_string(), "name".to_string()]),
+                table: "users".to_string(),
+                filter: Some(("id".to_string(), Predicate::GreaterThan(3))),
+                limit: Some(2),
+            }
+        );
+        let query = parse_select("SELECT * FROM users WHERE name = 'O''Brien'").unwrap();
+        assert_eq!(query.columns, None);
+        assert_eq!(
+            query.filter,
+            Some(("name".to_string(), Predicate::Equals("O'Brien".to_string())))
+        );
+
+        assert!(parse_select("SELECT FROM users").is_err());
+        assert!(parse_select("SELECT * FROM users WHERE name < 'a'").is_err());
+        assert!(parse_select("SELECT * FROM users LIMIT 1 extra").is_err());
+    }
+
+    #[test]
+    fn test_execute_select() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::StringType],
+            vec!["id".to_string(), "name".to_string()],
+        );
+        let name = create_test_heap_file("sql", td.clone());
+        let db = database::get_global_db();
+        let table = Table::new(name.clone(), "schema.txt".to_string()).unwrap();
+        let tid = TransactionId::new();
+        for i in 0..10 {
+            let value = format!("name_{}", i);
+            let len = value.len() as u32;
+            let fields = vec![
+                FieldVal::IntField(IntField::new(i)),
+                FieldVal::StringField(StringField::new(value, len)),
+            ];
+            table.insert_tuple(Tuple::new(fields, &td), tid).unwrap();
+        }
+
+        let query = format!("SELECT id FROM {} WHERE id > 5 LIMIT 3", name);
+        let rows = parse_select(&query).unwrap().execute(tid).unwrap();
+        let ids: Vec<i32> = rows.iter().map(|t| t.get_int(0).unwrap()).collect();
+        assert_eq!(ids, vec![6, 7, 8]);
+        assert_eq!(rows[0].get_tuple_desc().get_num_fields(), 1);
+
+        let query = format!("SELECT * FROM {} WHERE name = 'name_2'", name);
+        let rows = parse_select(&query).unwrap().execute(tid).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get_int_by_name("id"), Some(2));
+
+        let query = format!("SELECT * FROM {} WHERE name = 3", name);
+        assert!(parse_select(&query).unwrap().execute(tid).is_err());
+        // string comparisons on an int column are rejected rather than matching nothing
+        let mut query = parse_select(&format!("SELECT * FROM {}", name)).unwrap();
+        query.filter = Some((
+            "id".to_string(),
+            Predicate::StringGreaterThan("a".to_string()),
+        ));
+        assert!(query.execute(tid).is_err());
+        query.filter = Some((
+            "name".to_string(),
+            Predicate::StringGreaterThan("name_7".to_string()),
+        ));
+        assert_eq!(query.execute(tid).unwrap().len(), 2);
+        let query = format!("SELECT age FROM {}", name);
+        assert!(parse_select(&query).unwrap().execute(tid).is_err());
+        assert!(parse_select("SELECT * FROM missing_table")
+            .unwrap()
+            .execute(tid)
+            .is_err());
+        // a younger reader of the pages tid is writing is aborted rather than panicking
+        let younger = TransactionId::new();
+        let err = parse_select(&format!("SELECT * FROM {}", name))
+            .unwrap()
+            .execute(younger)
+            .unwrap_err();
+        assert!(err.contains("aborted"), "{}", err);
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+}
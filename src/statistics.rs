@@ -0,0 +1,50 @@
+use crate::fields::FieldVal;
+use crate::heap_page::HeapPage;
+use std::collections::HashMap;
+
+// Summary of a table's contents gathered by Table::analyze
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Statistics {
+    num_tuples: usize,
+    // fraction of each page's slots in use, in page order
+    page_fill_factors: Vec<f64>,
+    // smallest and largest non-NULL value of each int column
+    int_ranges: HashMap<String, (i32, i32)>,
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Statistics::default()
+    }
+
+    // Adds the page's tuples to the statistics, pages should be added in order
+    pub fn add_page(&mut self, page: &HeapPage) {
+        self.page_fill_factors.push(page.fill_factor());
+        for tuple in page.iter() {
+            self.num_tuples += 1;
+            let td = tuple.get_tuple_desc();
+            for (i, field) in tuple.get_fields().iter().enumerate() {
+                if let FieldVal::IntField(int_field) = field {
+                    let value = int_field.get_value();
+                    let name = td.get_field_name(i).unwrap().clone();
+                    let range = self.int_ranges.entry(name).or_insert((value, value));
+                    range.0 = range.0.min(value);
+                    range.1 = range.1.max(value);
+                }
+            }
+        }
+    }
+
+    pub fn get_num_tuples(&self) -> usize {
+        self.num_tuples
+    }
+
+    pub fn get_page_fill_factors(&self) -> &[f64] {
+        &self.page_fill_factors
+    }
+
+    // Gets the (min, max) of the named int column, None if it has no non-NULL ints
+    pub fn get_int_range(&self, field: &str) -> Option<(i32, i32)> {
+        self.int_ranges.get(field).copied()
+    }
+}
@@ -1,21 +1,32 @@
 use crate::database; // Import the `database` module or crate
-use crate::fields::FieldVal;
-use crate::heap_file::HeapFile;
-use crate::transaction::TransactionId; // Import the `transaction` module or crate
+use crate::fields::{FieldVal, FloatField, IntField};
+use crate::heap_file::{HeapFile, HeapFileIterator};
+use crate::heap_page::{HeapPageId, Permission};
+use crate::index::HashIndex;
+use crate::lock_manager::TABLE_LOCK_PAGE;
+use crate::statistics::Statistics;
+use crate::transaction::{DbError, IsolationLevel, TransactionAbort, TransactionId}; // Import the `transaction` module or crate
 use crate::tuple; // Import the `tuple` module or crate
+use crate::tuple::format_columns;
+use crate::tuple::RecordId;
 use crate::tuple::Tuple;
 use crate::tuple::TupleDesc;
-use std::sync::Arc;
+use crate::types::Type;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
 pub struct Table {
     name: String,
     heap_file: Arc<HeapFile>,
     table_id: usize,
     tuple_desc: TupleDesc,
+    // hash indexes on this table, keyed by field name
+    indexes: RwLock<HashMap<String, HashIndex>>,
 }
 
 impl Table {
-    pub fn new(name: String, schema: String) -> Self {
+    // Wraps the named table of the catalog, failing if it hasn't been loaded
+    pub fn new(name: String, schema: String) -> Result<Self, String> {
         let db = database::get_global_db();
         let catalog = db.get_catalog();
 
@@ -23,51 +34,549 @@ impl Table {
         let mut schema_file_path = std::env::current_dir().unwrap();
         schema_file_path.push(schema);
 
-        let heap_file = catalog.get_table_from_name(&name).unwrap();
+        let heap_file = catalog
+            .get_table_from_name(&name)
+            .ok_or(format!("table not found: {}", name))?;
         let table_id = heap_file.get_id();
 
-        Table {
+        Ok(Table {
             name,
             tuple_desc: heap_file.get_tuple_desc().clone(),
             heap_file,
             table_id,
+            indexes: RwLock::new(HashMap::new()),
+        })
+    }
+
+    // Builds a tuple for this table from the values, checking their count and types
+    // against the table's TupleDesc
+    pub fn make_tuple(&self, values: Vec<FieldVal>) -> Result<Tuple, String> {
+        self.tuple_desc.validate_fields(&values)?;
+        Ok(Tuple::new(values, &self.tuple_desc))
+    }
+
+    pub fn insert_tuple(&self, tuple: Tuple, tid: TransactionId) -> Result<(), DbError> {
+        // don't hold the index lock while waiting on page locks
+        if self.indexes.read().unwrap().is_empty() {
+            self.heap_file.add_tuple(tid, tuple)?;
+        } else {
+            let rid = self.heap_file.add_tuple(tid, tuple.clone())?;
+            for index in self.indexes.write().unwrap().values_mut() {
+                index.insert(&tuple, rid);
+            }
         }
+        Ok(())
     }
 
-    pub fn insert_tuple(&self, tuple: Tuple, tid: TransactionId) {
-        self.heap_file.add_tuple(tid, tuple);
+    // Inserts the tuples a page at a time, see HeapFile::add_tuples
+    pub fn insert_many_tuples(
+        &self,
+        tuples: Vec<Tuple>,
+        tid: TransactionId,
+    ) -> Result<(), DbError> {
+        if self.indexes.read().unwrap().is_empty() {
+            self.heap_file.add_tuples(tid, tuples)?;
+        } else {
+            let rids = self.heap_file.add_tuples(tid, tuples.clone())?;
+            for index in self.indexes.write().unwrap().values_mut() {
+                for (tuple, rid) in tuples.iter().zip(rids.iter()) {
+                    index.insert(tuple, *rid);
+                }
+            }
+        }
+        Ok(())
     }
 
-    pub fn insert_many_tuples(&self, tuples: Vec<Tuple>, tid: TransactionId) {
-        for tuple in tuples {
-            self.heap_file.add_tuple(tid, tuple);
+    // Inserts every tuple of the view, like INSERT INTO ... SELECT, returning how many were
+    // inserted. The view's field types must match the table's.
+    pub fn insert_from(&self, source: TableIterator, tid: TransactionId) -> Result<usize, String> {
+        let source_td = source.get_tuple_desc();
+        if !self.tuple_desc.matches_types(&source_td) {
+            return Err(format!(
+                "cannot insert {:?} into {}, the field types differ from {:?}",
+                source_td, self.name, self.tuple_desc
+            ));
         }
+        let tuples: Vec<Tuple> = source
+            .filtered_data()
+            .map(|t| Tuple::new(t.get_fields(), &self.tuple_desc))
+            .collect();
+        let count = tuples.len();
+        self.insert_many_tuples(tuples, tid)?;
+        Ok(count)
     }
 
-    pub fn get_tuple_desc(&self) -> &TupleDesc {
-        &self.tuple_desc
+    // Inserts every row of the CSV file at path, returning the number of rows inserted.
+    // A first line matching the field names is treated as a header and skipped, and empty
+    // values are NULL. Nothing is inserted if any row doesn't fit the schema.
+    pub fn import_csv(&self, path: &str, tid: TransactionId) -> Result<usize, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        let td = &self.tuple_desc;
+        let field_names: Vec<String> = (0..td.get_num_fields())
+            .map(|i| td.get_field_name(i).unwrap().clone())
+            .collect();
+
+        let mut tuples = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            let line_no = i + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let values = parse_csv_line(line);
+            if i == 0 && values.iter().map(|v| v.trim()).eq(field_names.iter()) {
+                continue;
+            }
+            if values.len() != field_names.len() {
+                return Err(format!(
+                    "line {}: expected {} fields, found {}",
+                    line_no,
+                    field_names.len(),
+                    values.len()
+                ));
+            }
+            let fields = td
+                .coerce(&values)
+                .map_err(|e| format!("line {}: {}", line_no, e))?;
+            td.validate_fields(&fields)
+                .map_err(|e| format!("line {}: {}", line_no, e))?;
+            tuples.push(Tuple::new(fields, td));
+        }
+        let count = tuples.len();
+        self.insert_many_tuples(tuples, tid)?;
+        Ok(count)
     }
 
-    pub fn get_id(&self) -> usize {
-        self.table_id
+    // Deletes the tuple stored at the tuple's record id
+    pub fn delete_tuple(&self, tuple: Tuple, tid: TransactionId) -> Result<(), DbError> {
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let rid = tuple.get_record_id();
+        bp.delete_tuple(tid, self.table_id, tuple)?;
+        for index in self.indexes.write().unwrap().values_mut() {
+            index.remove(rid);
+        }
+        Ok(())
+    }
+
+    // Replaces the fields of the tuple stored at rid with new_fields
+    pub fn update_tuple(
+        &self,
+        rid: RecordId,
+        new_fields: Vec<FieldVal>,
+        tid: TransactionId,
+    ) -> Result<(), DbError> {
+        self.tuple_desc.validate_fields(&new_fields)?;
+        let mut tuple = Tuple::new(new_fields, &self.tuple_desc);
+        tuple.set_record_id(rid);
+        self.heap_file.update_tuple(tid, tuple.clone())?;
+        for index in self.indexes.write().unwrap().values_mut() {
+            index.remove(rid);
+            index.insert(&tuple, rid);
+        }
+        Ok(())
+    }
+
+    // Sets the named field to new_val in every tuple where it matches the predicate,
+    // returning how many were updated. Every page is locked for writing. Fails if the
+    // field doesn't exist or new_val doesn't fit it, or if it's the primary key and
+    // new_val is taken or would be given to more than one tuple.
+    pub fn update_where(
+        &self,
+        field: &str,
+        predicate: Predicate,
+        new_val: FieldVal,
+        tid: TransactionId,
+    ) -> Result<usize, DbError> {
+        let field_id = self
+            .tuple_desc
+            .name_to_id(field)
+            .ok_or(format!("no field named {}", field))?;
+        self.tuple_desc.validate_field(field_id, &new_val)?;
+        if self.heap_file.get_primary_key() == Some(field_id) {
+            // every updated tuple would get the same key, so there can be at most one
+            let mut matching = vec![];
+            for page in self.heap_file.iter_mut(tid) {
+                let page = page?;
+                let page = page.read().unwrap();
+                let matches = page.iter().filter(|t| t.filter(field, &predicate));
+                matching.extend(matches.map(|t| t.get_record_id()));
+            }
+            match matching[..] {
+                [] => {}
+                [rid] => self.heap_file.check_primary_key(tid, &new_val, Some(rid))?,
+                _ => {
+                    return Err(DbError::Other(format!(
+                        "can't set primary key {} of {} tuples to the same value",
+                        field,
+                        matching.len()
+                    )))
+                }
+            }
+        }
+        let mut updated = vec![];
+        for page in self.heap_file.iter_mut(tid) {
+            let page = page?;
+            let mut page = page.write().unwrap();
+            for slot in 0..page.get_num_slots() {
+                if !page.is_slot_used(slot) || !page.get_tuple(slot).filter(field, &predicate) {
+                    continue;
+                }
+                page.set_tuple_field(slot, field_id, new_val.clone())?;
+                page.mark_dirty(true, tid);
+                updated.push(page.get_tuple(slot).clone());
+            }
+            if page.is_dirty() {
+                self.heap_file.record_page_metadata(&page);
+            }
+        }
+        self.heap_file.record_primary_keys(&updated);
+        for index in self.indexes.write().unwrap().values_mut() {
+            for tuple in updated.iter() {
+                index.remove(tuple.get_record_id());
+                index.insert(tuple, tuple.get_record_id());
+            }
+        }
+        Ok(updated.len())
+    }
+
+    // Deletes every tuple whose named field matches the predicate, returning how many were
+    // deleted. Every page is locked for writing. Fails if the field doesn't exist.
+    pub fn delete_where(
+        &self,
+        pred_field: &str,
+        predicate: Predicate,
+        tid: TransactionId,
+    ) -> Result<usize, DbError> {
+        if self.tuple_desc.name_to_id(pred_field).is_none() {
+            return Err(DbError::Other(format!("no field named {}", pred_field)));
+        }
+        let mut deleted = vec![];
+        for page in self.heap_file.iter_mut(tid) {
+            let page = page?;
+            let mut page = page.write().unwrap();
+            let matches: Vec<Tuple> = page
+                .iter()
+                .filter(|t| t.filter(pred_field, &predicate))
+                .cloned()
+                .collect();
+            if matches.is_empty() {
+                continue;
+            }
+            for tuple in matches {
+                deleted.push(tuple.get_record_id());
+                page.delete_tuple(tuple)?;
+            }
+            page.mark_dirty(true, tid);
+            self.heap_file.record_page_metadata(&page);
+        }
+        for index in self.indexes.write().unwrap().values_mut() {
+            for rid in deleted.iter() {
+                index.remove(*rid);
+            }
+        }
+        Ok(deleted.len())
+    }
+
+    // Deletes every tuple in the table under an exclusive table lock. The pages are
+    // emptied in the buffer pool and rolled back if tid aborts; the file keeps its pages
+    // until it's compacted. The indexes keep their entries so they're still right if tid
+    // aborts - lookup already skips entries whose slot no longer holds the value.
+    pub fn truncate(&self, tid: TransactionId) -> Result<(), DbError> {
+        let db = database::get_global_db();
+        db.get_buffer_pool()
+            .get_lock_manager()
+            .acquire_table_lock(tid, self.table_id, true)?;
+        for page in self.heap_file.iter_mut(tid) {
+            let page = page?;
+            let mut page = page.write().unwrap();
+            if page.iter().next().is_none() {
+                continue;
+            }
+            let empty = vec![0; page.get_page_size()];
+            page.restore(empty);
+            page.mark_dirty(true, tid);
+            self.heap_file.record_page_metadata(&page);
+        }
+        Ok(())
+    }
+
+    // Packs the table's tuples into as few pages as possible, see HeapFile::compact, and
+    // rebuilds its indexes for the moved tuples' new record ids
+    pub fn compact(&self, tid: TransactionId) -> Result<(), DbError> {
+        self.heap_file.compact(tid)?;
+        let fields: Vec<String> = self.indexes.read().unwrap().keys().cloned().collect();
+        for field in fields {
+            let index = self.build_index(&field, tid)?;
+            self.indexes.write().unwrap().insert(field, index);
+        }
+        Ok(())
     }
 
-    pub fn print(&self) {
+    // Builds a hash index on the named field by scanning the table once.
+    // Later inserts, updates and deletes through this Table keep it up to date. The index
+    // belongs to this Table alone: changes made through another Table on the same file, or
+    // straight through its HeapFile, aren't added to it.
+    pub fn create_index(&self, field: &str) -> Result<(), String> {
         let db = database::get_global_db();
         let tid = TransactionId::new();
+        let index = self.build_index(field, tid)?;
+        db.get_buffer_pool().commit_transaction(tid);
+        self.indexes
+            .write()
+            .unwrap()
+            .insert(field.to_string(), index);
+        Ok(())
+    }
+
+    fn build_index(&self, field: &str, tid: TransactionId) -> Result<HashIndex, DbError> {
+        let mut index = HashIndex::new(field, &self.tuple_desc)?;
         for page in self.heap_file.iter(tid) {
+            let page = page?;
+            let page = page.read().unwrap();
+            for slot in 0..page.get_num_slots() {
+                if page.is_slot_used(slot) {
+                    index.insert(page.get_tuple(slot), RecordId::new(page.get_id(), slot));
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    // Checks whether create_index has built an index on the named field
+    pub fn has_index(&self, field: &str) -> bool {
+        self.indexes.read().unwrap().contains_key(field)
+    }
+
+    // Returns the tuples whose field equals value, reading only the pages the index points
+    // at. Falls back to a full scan if the field isn't indexed. Fails if the transaction
+    // is aborted while acquiring read locks.
+    pub fn lookup(
+        &self,
+        field: &str,
+        value: FieldVal,
+        tid: TransactionId,
+    ) -> Result<Vec<Tuple>, TransactionAbort> {
+        // copy what we need out of the index so its lock isn't held while waiting on pages
+        let entry = self
+            .indexes
+            .read()
+            .unwrap()
+            .get(field)
+            .map(|index| (index.get_field_id(), index.get(&value)));
+        let Some((field_id, rids)) = entry else {
+            return Ok(self
+                .scan_all(tid)?
+                .filter(|tuple| {
+                    let i = tuple.get_tuple_desc().name_to_id(field);
+                    i.and_then(|i| tuple.get_field(i)) == Some(&value)
+                })
+                .collect());
+        };
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let mut tuples = vec![];
+        for rid in rids {
+            let page = bp.get_page(tid, rid.get_page_id(), Permission::Read)?;
             let page = page.read().unwrap();
-            for (i, tuple) in page.iter().enumerate() {
-                println!("{}: {}", i, tuple);
+            // skip entries left behind by aborted transactions
+            let slot = rid.get_tuple_no();
+            if page.is_slot_used(slot) && page.get_tuple(slot).get_field(field_id) == Some(&value) {
+                tuples.push(page.get_tuple(slot).clone());
+            }
+        }
+        Ok(tuples)
+    }
+
+    // Returns a view of the tuples whose int field is between low and high inclusive,
+    // skipping pages whose key range can't overlap when field is the file's key field.
+    // The key ranges include uncommitted changes, so pruning first takes a shared table
+    // lock to wait out other writers - fails if the transaction is aborted while locking.
+    pub fn scan_range(
+        &self,
+        field: &str,
+        low: i32,
+        high: i32,
+        tid: TransactionId,
+    ) -> Result<TableIterator<'_>, TransactionAbort> {
+        let predicate = Predicate::Between(low, high);
+        let is_key = self.heap_file.get_key_field().is_some()
+            && self.heap_file.get_key_field() == self.tuple_desc.name_to_id(field);
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let lm = bp.get_lock_manager();
+        let table_lock = HeapPageId::new(self.table_id, TABLE_LOCK_PAGE);
+        let mut release_table_lock = false;
+        let prune = is_key
+            && match tid.get_isolation() {
+                // reads the same uncommitted changes the key ranges reflect
+                IsolationLevel::ReadUncommitted => true,
+                // reads pages older than the key ranges
+                IsolationLevel::Snapshot => false,
+                isolation => {
+                    let held = lm.holds_lock(tid, table_lock).is_some();
+                    lm.acquire_table_lock(tid, self.table_id, false)?;
+                    release_table_lock = isolation == IsolationLevel::ReadCommitted && !held;
+                    true
+                }
+            };
+        let mut data = vec![];
+        for page_no in 0..self.heap_file.num_pages() {
+            if prune && !self.heap_file.page_may_contain_keys(page_no, low, high) {
+                continue;
             }
+            let pid = HeapPageId::new(self.table_id, page_no);
+            let page = bp.get_page(tid, pid, Permission::Read)?;
+            let page = page.read().unwrap();
+            data.extend(page.iter().filter(|t| t.filter(field, &predicate)).cloned());
+        }
+        // read committed doesn't keep its read locks past the read
+        if release_table_lock {
+            lm.release_lock(tid, table_lock);
         }
+        Ok(TableIterator {
+            table: self,
+            current_page_index: 0,
+            tid,
+            data,
+            td: self.tuple_desc.clone(),
+            filters: Vec::new(),
+            plan: vec![format!(
+                "ScanRange({}, {})",
+                self.name,
+                describe_filter(field, &predicate)
+            )],
+        })
+    }
+
+    // Scans the table to gather its statistics and stores them in the catalog
+    pub fn analyze(&self, tid: TransactionId) -> Result<Statistics, String> {
+        let mut statistics = Statistics::new();
+        for page in self.heap_file.iter(tid) {
+            let page = page?;
+            statistics.add_page(&page.read().unwrap());
+        }
+        let db = database::get_global_db();
+        db.get_catalog()
+            .set_statistics(self.table_id, statistics.clone());
+        Ok(statistics)
+    }
+
+    pub fn get_tuple_desc(&self) -> &TupleDesc {
+        &self.tuple_desc
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_id(&self) -> usize {
+        self.table_id
+    }
+
+    // Prints every tuple in the table as an aligned table, see TableIterator::print_table.
+    // Fails if the scan is aborted while acquiring read locks.
+    pub fn print(&self) -> Result<(), TransactionAbort> {
+        let db = database::get_global_db();
+        let tid = TransactionId::new();
+        self.scan_all(tid)?.print_table();
         let bp = db.get_buffer_pool();
         bp.commit_transaction(tid);
+        Ok(())
+    }
+
+    // Counts the tuples in the table from each page's slot usage, without copying them,
+    // failing if the transaction is aborted while acquiring read locks
+    pub fn count(&self, tid: TransactionId) -> Result<usize, TransactionAbort> {
+        self.heap_file.num_tuples(tid)
     }
 
-    pub fn scan(&self, count: usize, tid: TransactionId) -> TableIterator {
+    // Reads up to count tuples, failing if the transaction is aborted while acquiring
+    // read locks
+    pub fn scan(
+        &self,
+        count: usize,
+        tid: TransactionId,
+    ) -> Result<TableIterator<'_>, TransactionAbort> {
         TableIterator::new(self, tid, count)
     }
+
+    // Materializes every live tuple in the table, use scan for a bounded read
+    pub fn scan_all(&self, tid: TransactionId) -> Result<TableIterator<'_>, TransactionAbort> {
+        self.scan(usize::MAX, tid)
+    }
+
+    // Yields every tuple in the table with its record id set, a page at a time - panics if
+    // the transaction is aborted while acquiring read locks. Under Serializable isolation
+    // the read locks are held until tid commits or aborts, so commit it when done.
+    pub fn iter_tuples(&self, tid: TransactionId) -> impl Iterator<Item = Tuple> + '_ {
+        self.stream(tid)
+    }
+
+    // Lazily scans the table a page at a time instead of materializing it like scan
+    pub fn stream(&self, tid: TransactionId) -> TableStream<'_> {
+        TableStream {
+            table: self,
+            pages: self.heap_file.iter(tid),
+            page_tuples: Vec::new().into_iter(),
+            filters: Vec::new(),
+            fields: None,
+        }
+    }
+}
+
+// Streaming scan that only holds the tuples of the page it is currently on -
+// panics if the scan's transaction is aborted while acquiring read locks
+pub struct TableStream<'a> {
+    table: &'a Table,
+    pages: HeapFileIterator<'a>,
+    page_tuples: std::vec::IntoIter<Tuple>,
+    filters: Vec<(String, Predicate)>,
+    // fields to project the tuples down to, if any
+    fields: Option<Vec<String>>,
+}
+
+impl<'a> TableStream<'a> {
+    pub fn table_filter(&mut self, field_name: &str, predicate: Predicate) {
+        self.filters.push((field_name.to_string(), predicate));
+    }
+
+    // Projects every streamed tuple down to the given fields in the given order, after
+    // filtering. Fails if the table has no such field.
+    pub fn project(mut self, fields: Vec<String>) -> Result<Self, String> {
+        let td = self.table.get_tuple_desc();
+        if let Some(field) = fields.iter().find(|f| td.name_to_id(f).is_none()) {
+            return Err(format!("unknown field {}", field));
+        }
+        self.fields = Some(fields);
+        Ok(self)
+    }
+}
+
+impl<'a> Iterator for TableStream<'a> {
+    type Item = Tuple;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            for tuple in self.page_tuples.by_ref() {
+                let keep = self
+                    .filters
+                    .iter()
+                    .all(|(field_name, predicate)| tuple.filter(field_name, predicate));
+                if keep {
+                    return Some(match &self.fields {
+                        // the fields were checked against the table by project
+                        Some(fields) => tuple.project(fields).unwrap(),
+                        None => tuple,
+                    });
+                }
+            }
+            // move on to the next page
+            let page = self.pages.next()?.unwrap();
+            let tuples: Vec<Tuple> = page.read().unwrap().iter().cloned().collect();
+            self.page_tuples = tuples.into_iter();
+        }
+    }
 }
 
 // iterator iterates on a view generated from the heapfile -> quick fix to get the view working
@@ -77,15 +586,21 @@ pub struct TableIterator<'a> {
     current_page_index: usize,
     tid: TransactionId,
     data: Vec<tuple::Tuple>, // like a view
+    // the schema of the view's tuples, which is known even when there are none
+    td: TupleDesc,
     filters: Vec<(String, Predicate)>,
+    // the operations that produced the view, in order, see explain
+    plan: Vec<String>,
 }
 
 impl<'a> TableIterator<'a> {
-    // make a new table iterator and fill its vector with count tuples -
-    fn new(table: &'a Table, tid: TransactionId, count: usize) -> Self {
+    // make a new table iterator and fill its vector with count tuples, failing if the
+    // scan's transaction is aborted while acquiring read locks
+    fn new(table: &'a Table, tid: TransactionId, count: usize) -> Result<Self, TransactionAbort> {
         let mut data = Vec::new();
         let mut count = count;
         for page in table.heap_file.iter(tid) {
+            let page = page?;
             let page = page.read().unwrap();
             for tuple in page.iter() {
                 if count == 0 {
@@ -95,140 +610,749 @@ impl<'a> TableIterator<'a> {
                 data.push(tuple.clone());
             }
         }
-        TableIterator {
+        Ok(TableIterator {
             table,
             current_page_index: 0,
             tid,
             data,
+            td: table.tuple_desc.clone(),
             filters: Vec::new(),
-        }
+            plan: vec![format!("Scan({})", table.name)],
+        })
     }
 
-    pub fn project(&self, fields: Vec<String>) -> TableIterator {
-        let mut data = Vec::new();
-
-        // take the Tuple and make a new TupleDesc for it as well as a new Fields for it
-        for tuple in self.data.iter() {
-            let mut new_field_types = Vec::new();
-            let mut new_field_vals = Vec::new();
-
-            // go through each of the fields for this tuple
-            for i in 0..tuple.get_tuple_desc().get_num_fields() {
-                let field_name = tuple.get_tuple_desc().get_field_name(i).unwrap().clone();
+    // Describes the operations that produced the view, e.g.
+    // Scan(employees) -> Filter(id>1) -> Project(name). Views combined with this one are
+    // described inside the step that combined them.
+    pub fn explain(&self) -> String {
+        self.plan.join(" -> ")
+    }
 
-                // Check if the field is in the list of fields to keep and has the desired type
-                if fields.contains(&field_name) {
-                    // we want to keep this field - so adding it to the new field types
+    // Adds a step to the view's plan
+    fn then(mut self, step: String) -> Self {
+        self.plan.push(step);
+        self
+    }
 
-                    let field_type = tuple.get_tuple_desc().get_field_type(i).unwrap().clone();
-                    new_field_types.push(field_type);
+    // Keeps only the given fields of each tuple, in the given order.
+    // Fails if a tuple has no such field.
+    pub fn project(&self, fields: Vec<String>) -> Result<TableIterator<'_>, String> {
+        let specs: Vec<(String, String)> = fields.iter().map(|f| (f.clone(), f.clone())).collect();
+        let (td, _) = self.td.project_as(&specs)?;
+        let data = self
+            .data
+            .iter()
+            .map(|tuple| tuple.project(&fields))
+            .collect::<Result<Vec<_>, String>>()?;
+        // make a new iterator with the new data
+        Ok(self
+            .with_data(data)
+            .with_tuple_desc(td)
+            .then(format!("Project({})", fields.join(", "))))
+    }
 
-                    let field = tuple.get_field(i).unwrap().clone();
-                    new_field_vals.push(field);
-                }
+    // Like project, but each spec is a (source field, output name) pair so columns can be
+    // renamed, e.g. to tell apart columns with the same name after a join
+    pub fn project_as(&self, specs: Vec<(String, String)>) -> Result<TableIterator<'_>, String> {
+        for (i, (_, name)) in specs.iter().enumerate() {
+            if specs[..i].iter().any(|(_, other)| other == name) {
+                return Err(format!("duplicate output field {}", name));
             }
+        }
+        let (td, _) = self.td.project_as(&specs)?;
+        let data = self
+            .data
+            .iter()
+            .map(|tuple| tuple.project_as(&specs))
+            .collect::<Result<Vec<_>, String>>()?;
+        let steps: Vec<String> = specs
+            .iter()
+            .map(|(field, name)| format!("{} AS {}", field, name))
+            .collect();
+        Ok(self
+            .with_data(data)
+            .with_tuple_desc(td)
+            .then(format!("Project({})", steps.join(", "))))
+    }
 
-            // Create a new tuple descriptor with only the selected fields
-            let new_tuple_desc = TupleDesc::new(new_field_types, fields.clone());
+    pub fn table_filter(&mut self, field_name: &str, predicate: Predicate) {
+        self.plan.push(format!(
+            "Filter({})",
+            describe_filter(field_name, &predicate)
+        ));
+        self.filters.push((field_name.to_string(), predicate));
+    }
 
-            // Create a new tuple with the selected fields
-            let new_tuple = Tuple::new(new_field_vals, &new_tuple_desc);
+    // the tuples in the view that pass all of the filters
+    fn filtered_data(&self) -> impl Iterator<Item = &Tuple> {
+        self.data.iter().filter(|tuple| {
+            self.filters
+                .iter()
+                .all(|(field_name, predicate)| tuple.filter(field_name, predicate))
+        })
+    }
 
-            data.push(new_tuple);
-        }
-        // make a new iterator with the new data
+    // make a new iterator over the same table with the given data, continuing this
+    // view's plan and keeping its schema
+    fn with_data(&self, data: Vec<Tuple>) -> TableIterator<'a> {
         TableIterator {
             table: self.table,
             current_page_index: 0,
             tid: self.tid,
             data,
+            td: self.td.clone(),
             filters: Vec::new(),
+            plan: self.plan.clone(),
         }
     }
 
-    pub fn table_filter(&mut self, field_name: &str, predicate: Predicate) {
-        self.filters.push((field_name.to_string(), predicate));
+    // Replaces the schema of the view, for steps that change the shape of its tuples
+    fn with_tuple_desc(mut self, td: TupleDesc) -> Self {
+        self.td = td;
+        self
+    }
+
+    // the schema of the view
+    fn get_tuple_desc(&self) -> TupleDesc {
+        self.td.clone()
+    }
+
+    // the schema of a join of this view with other, each side's fields prefixed with the
+    // name of the table it came from so they stay distinguishable. A table joined with
+    // itself has its sides prefixed with "left" and "right" instead. Fails if a field name
+    // would still appear on both sides, e.g. joining a joined view with one of its tables.
+    fn join_desc(&self, other: &TableIterator) -> Result<TupleDesc, String> {
+        let (left_name, right_name) = (self.table.get_name(), other.table.get_name());
+        let (left_name, right_name) = match left_name == right_name {
+            true => ("left", "right"),
+            false => (left_name, right_name),
+        };
+        let ctd = TupleDesc::combine(
+            &self.td.with_prefix(left_name),
+            &other.td.with_prefix(right_name),
+        );
+        for i in 0..ctd.get_num_fields() {
+            let name = ctd.get_field_name(i).unwrap();
+            if (0..i).any(|j| ctd.get_field_name(j) == Some(name)) {
+                return Err(format!("ambiguous field name {} in join", name));
+            }
+        }
+        Ok(ctd)
+    }
+
+    // Renders the filtered tuples as a table with a header of their field names, each column
+    // as wide as its longest value or name
+    pub fn format_table(&self) -> String {
+        let td = self.get_tuple_desc();
+        let header: Vec<String> = (0..td.get_num_fields())
+            .map(|i| td.get_field_name(i).unwrap().clone())
+            .collect();
+        let values: Vec<Vec<String>> = self.filtered_data().map(|t| t.format_values()).collect();
+        let widths: Vec<usize> = (0..header.len())
+            .map(|i| {
+                values
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([header[i].chars().count()])
+                    .max()
+                    .unwrap()
+            })
+            .collect();
+        let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        let mut table = format_columns(&header, &widths) + "\n";
+        table.push_str(&separator.join("-+-"));
+        table.push('\n');
+        for tuple in self.filtered_data() {
+            table.push_str(&tuple.format_row(&widths));
+            table.push('\n');
+        }
+        table
+    }
+
+    pub fn print_table(&self) {
+        print!("{}", self.format_table());
+    }
+
+    // Renders the filtered tuples as CSV with a header of their field names.
+    // Strings are quoted and NULLs are left empty.
+    pub fn to_csv(&self) -> String {
+        let td = self.get_tuple_desc();
+        let header: Vec<String> = (0..td.get_num_fields())
+            .map(|i| td.get_field_name(i).unwrap().clone())
+            .collect();
+        let mut csv = header.join(",") + "\n";
+        for tuple in self.filtered_data() {
+            let values: Vec<String> = tuple
+                .get_fields()
+                .iter()
+                .map(|field| match field {
+                    FieldVal::IntField(int_field) => int_field.get_value().to_string(),
+                    FieldVal::FloatField(float_field) => float_field.get_value().to_string(),
+                    FieldVal::BoolField(bool_field) => bool_field.get_value().to_string(),
+                    FieldVal::LongField(long_field) => long_field.get_value().to_string(),
+                    FieldVal::StringField(string_field) => {
+                        format!("\"{}\"", string_field.get_value().replace('"', "\"\""))
+                    }
+                    FieldVal::Null => String::new(),
+                })
+                .collect();
+            csv.push_str(&values.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    // Renders the filtered tuples as a JSON array of objects keyed by field name
+    pub fn to_json(&self) -> String {
+        let td = self.get_tuple_desc();
+        let rows: Vec<String> = self
+            .filtered_data()
+            .map(|tuple| {
+                let members: Vec<String> = tuple
+                    .get_fields()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| {
+                        let value = match field {
+                            FieldVal::IntField(int_field) => int_field.get_value().to_string(),
+                            FieldVal::FloatField(float_field) => {
+                                float_field.get_value().to_string()
+                            }
+                            FieldVal::BoolField(bool_field) => bool_field.get_value().to_string(),
+                            FieldVal::LongField(long_field) => long_field.get_value().to_string(),
+                            FieldVal::StringField(string_field) => {
+                                json_string(&string_field.get_value())
+                            }
+                            FieldVal::Null => "null".to_string(),
+                        };
+                        format!("{}:{}", json_string(td.get_field_name(i).unwrap()), value)
+                    })
+                    .collect();
+                format!("{{{}}}", members.join(","))
+            })
+            .collect();
+        format!("[{}]", rows.join(","))
+    }
+
+    // Computes the aggregate of the named column over the filtered tuples, skipping NULLs.
+    // Returns None if the column doesn't exist or op isn't supported for its type.
+    pub fn aggregate(&self, field: &str, op: AggOp) -> Option<FieldVal> {
+        let mut values = vec![];
+        for tuple in self.filtered_data() {
+            let i = tuple.get_tuple_desc().name_to_id(field)?;
+            values.push(tuple.get_field(i)?.clone());
+        }
+        aggregate_values(&values, op)
+    }
+
+    // Sorts the filtered tuples by the named column, with NULLs first and values of
+    // different types ordered as FieldVal's Ord does - panics if the column is missing
+    pub fn order_by(&self, field: &str, descending: bool) -> TableIterator<'a> {
+        let mut data: Vec<Tuple> = self.filtered_data().cloned().collect();
+        let field_id = |tuple: &Tuple| {
+            tuple
+                .get_tuple_desc()
+                .name_to_id(field)
+                .unwrap_or_else(|| panic!("cannot order by missing field {}", field))
+        };
+        data.sort_by(|a, b| {
+            let a = a.get_field(field_id(a)).unwrap();
+            let b = b.get_field(field_id(b)).unwrap();
+            let ordering = a.cmp(b);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        let order = if descending { " DESC" } else { "" };
+        self.with_data(data)
+            .then(format!("OrderBy({}{})", field, order))
+    }
+
+    // Keeps at most the first n of the filtered tuples
+    pub fn limit(&self, n: usize) -> TableIterator<'a> {
+        self.with_data(self.filtered_data().take(n).cloned().collect())
+            .then(format!("Limit({})", n))
+    }
+
+    // Skips the first n of the filtered tuples, leaving nothing if there are fewer than n
+    pub fn offset(&self, n: usize) -> TableIterator<'a> {
+        self.with_data(self.filtered_data().skip(n).cloned().collect())
+            .then(format!("Offset({})", n))
+    }
+
+    // Drops duplicate tuples from the filtered data, keeping the first of each. Tuples are
+    // compared by value, so copies stored in different slots are duplicates.
+    pub fn distinct(&self) -> TableIterator<'a> {
+        self.with_data(dedup(self.filtered_data().cloned()))
+            .then("Distinct".to_string())
+    }
+
+    // The filtered tuples of both views, with other's given this view's schema so they
+    // compare by value. Fails unless the views have the same number and types of fields.
+    fn set_operands(&self, other: &TableIterator) -> Result<(Vec<Tuple>, Vec<Tuple>), String> {
+        let (td, other_td) = (self.get_tuple_desc(), other.get_tuple_desc());
+        if !td.matches_types(&other_td) {
+            return Err(format!(
+                "cannot combine {:?} with {:?}, the field types differ",
+                td, other_td
+            ));
+        }
+        let left = self.filtered_data().cloned().collect();
+        let right = other
+            .filtered_data()
+            .map(|t| Tuple::new(t.get_fields(), &td))
+            .collect();
+        Ok((left, right))
+    }
+
+    // Every filtered tuple of this view followed by every one of other's, duplicates kept
+    pub fn union_all(&self, other: &TableIterator) -> Result<TableIterator<'a>, String> {
+        let (mut left, right) = self.set_operands(other)?;
+        left.extend(right);
+        Ok(self
+            .with_data(left)
+            .then(format!("UnionAll({})", other.explain())))
+    }
+
+    // The distinct tuples found in either view, like SQL UNION
+    pub fn union(&self, other: &TableIterator) -> Result<TableIterator<'a>, String> {
+        let (left, right) = self.set_operands(other)?;
+        Ok(self
+            .with_data(dedup(left.into_iter().chain(right)))
+            .then(format!("Union({})", other.explain())))
+    }
+
+    // The distinct tuples of this view that other also has, like SQL INTERSECT
+    pub fn intersect(&self, other: &TableIterator) -> Result<TableIterator<'a>, String> {
+        let (left, right) = self.set_operands(other)?;
+        let common = left
+            .into_iter()
+            .filter(|t| right.iter().any(|r| r.value_eq(t)));
+        Ok(self
+            .with_data(dedup(common))
+            .then(format!("Intersect({})", other.explain())))
+    }
+
+    // The distinct tuples of this view that other doesn't have, like SQL EXCEPT
+    pub fn except(&self, other: &TableIterator) -> Result<TableIterator<'a>, String> {
+        let (left, right) = self.set_operands(other)?;
+        let remaining = left
+            .into_iter()
+            .filter(|t| !right.iter().any(|r| r.value_eq(t)));
+        Ok(self
+            .with_data(dedup(remaining))
+            .then(format!("Except({})", other.explain())))
+    }
+
+    // Groups the filtered tuples by the value of group_field and aggregates agg_field within
+    // each group. The resulting tuples hold the group key and the aggregate, in the order each
+    // group was first seen; groups where the aggregate isn't supported get a NULL.
+    pub fn group_by(&self, group_field: &str, agg_field: &str, op: AggOp) -> TableIterator<'a> {
+        let step = format!("GroupBy({}, {:?}({}))", group_field, op, agg_field);
+        let (Some(group_id), Some(agg_id)) = (
+            self.td.name_to_id(group_field),
+            self.td.name_to_id(agg_field),
+        ) else {
+            return self.with_data(vec![]).then(step);
+        };
+        let mut keys: Vec<FieldVal> = vec![];
+        let mut groups: HashMap<FieldVal, Vec<FieldVal>> = HashMap::new();
+        for tuple in self.filtered_data() {
+            let key = tuple.get_field(group_id).unwrap().clone();
+            let value = tuple.get_field(agg_id).unwrap().clone();
+            groups
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    keys.push(key);
+                    vec![]
+                })
+                .push(value);
+        }
+        let agg_type = match op {
+            AggOp::Avg => Type::FloatType,
+            _ => Type::IntType,
+        };
+        let td = TupleDesc::new_with_nullable(
+            vec![self.td.get_field_type(group_id).unwrap().clone(), agg_type],
+            vec![
+                group_field.to_string(),
+                format!("{:?}({})", op, agg_field).to_lowercase(),
+            ],
+            vec![true, true],
+        );
+        let data = keys
+            .into_iter()
+            .map(|key| {
+                let agg = aggregate_values(&groups[&key], op).unwrap_or(FieldVal::Null);
+                Tuple::new(vec![key, agg], &td)
+            })
+            .collect();
+        self.with_data(data).with_tuple_desc(td).then(step)
     }
 
+    // Joins this view with other on field_name_left = field_name_right, see join_desc for
+    // the names of the result's fields. Fails if either field is missing or ambiguous.
     pub fn join(
         &self,
         other: &TableIterator,
         field_name_left: &str,
         field_name_right: &str,
-    ) -> TableIterator {
+    ) -> Result<TableIterator<'_>, String> {
         // making a new 'view'/ TableIterator using nxn from both tables
         // field_name is the field/col that we are joining on
         // similar to JOIN t1 ON t1.id = t2.id where id is field_name
         let mut data = Vec::new();
+        let ctd = self.join_desc(other)?;
+        let target_col_left = self.td.resolve_name(field_name_left)?;
+        let target_col_right = other.td.resolve_name(field_name_right)?;
 
         for tuple in self.data.iter() {
-            println!("{}", tuple);
-            let target_col_left = tuple.get_tuple_desc().name_to_id(field_name_left).unwrap();
             for other_tuple in other.data.iter() {
-                let target_col_right = other_tuple
-                    .get_tuple_desc()
-                    .name_to_id(field_name_right)
-                    .unwrap();
                 // check if the tuples match
                 // if they do, add them to the new view
                 if tuple.get_field(target_col_left).unwrap()
                     == other_tuple.get_field(target_col_right).unwrap()
                 {
-                    // add the tuple to the new view
-
-                    // need to combine the two tuples
+                    // add the combined tuple to the new view
+                    data.push(combine_tuples(tuple, other_tuple, &ctd));
+                }
+            }
+        }
+        Ok(self.with_data(data).with_tuple_desc(ctd).then(format!(
+            "Join({}, {}={})",
+            other.explain(),
+            field_name_left,
+            field_name_right
+        )))
+    }
 
-                    // making a new TupleDesc
-                    let ctd: TupleDesc =
-                        TupleDesc::combine(tuple.get_tuple_desc(), other_tuple.get_tuple_desc());
-                    let combined_fields = tuple
-                        .get_fields()
-                        .iter()
-                        .chain(other_tuple.get_fields().iter())
-                        .cloned()
-                        .collect::<Vec<_>>();
-                    let new_tuple = Tuple::new(combined_fields, &ctd);
-                    data.push(new_tuple);
+    // Like join, but every left tuple appears at least once - left tuples without a match
+    // are padded with NULLs for the right side's columns
+    pub fn left_join(
+        &self,
+        other: &TableIterator,
+        left_field: &str,
+        right_field: &str,
+    ) -> Result<TableIterator<'a>, String> {
+        let mut data = Vec::new();
+        let ctd = self.join_desc(other)?;
+        let target_col_left = self.td.resolve_name(left_field)?;
+        let target_col_right = other.td.resolve_name(right_field)?;
+        for tuple in self.data.iter() {
+            let mut matched = false;
+            for other_tuple in other.data.iter() {
+                if tuple.get_field(target_col_left) == other_tuple.get_field(target_col_right) {
+                    data.push(combine_tuples(tuple, other_tuple, &ctd));
+                    matched = true;
                 }
             }
+            if !matched {
+                let nulls = vec![FieldVal::Null; other.td.get_num_fields()];
+                data.push(combine_tuples(tuple, &Tuple::new(nulls, &other.td), &ctd));
+            }
         }
-        TableIterator {
-            table: self.table,
-            current_page_index: 0,
-            tid: self.tid,
-            data,
-            filters: Vec::new(),
+        Ok(self.with_data(data).with_tuple_desc(ctd).then(format!(
+            "LeftJoin({}, {}={})",
+            other.explain(),
+            left_field,
+            right_field
+        )))
+    }
+
+    // Joins on several column pairs at once, keeping only combinations where every
+    // (left, right) pair of columns is equal. Fails if a field is missing or ambiguous on
+    // either side.
+    pub fn join_on(
+        &self,
+        other: &TableIterator,
+        pairs: &[(&str, &str)],
+    ) -> Result<TableIterator<'a>, String> {
+        let ctd = self.join_desc(other)?;
+        let columns = |td: &TupleDesc, side: &str, names: Vec<&str>| {
+            names
+                .into_iter()
+                .map(|name| {
+                    td.resolve_name(name)
+                        .map_err(|e| format!("{} on the {} side", e, side))
+                })
+                .collect::<Result<Vec<usize>, String>>()
+        };
+        let left_cols = columns(&self.td, "left", pairs.iter().map(|(l, _)| *l).collect())?;
+        let right_cols = columns(&other.td, "right", pairs.iter().map(|(_, r)| *r).collect())?;
+
+        let mut data = Vec::new();
+        for tuple in self.data.iter() {
+            for other_tuple in other.data.iter() {
+                let matches = left_cols
+                    .iter()
+                    .zip(right_cols.iter())
+                    .all(|(l, r)| tuple.get_field(*l) == other_tuple.get_field(*r));
+                if matches {
+                    data.push(combine_tuples(tuple, other_tuple, &ctd));
+                }
+            }
         }
+        let on: Vec<String> = pairs.iter().map(|(l, r)| format!("{}={}", l, r)).collect();
+        Ok(self.with_data(data).with_tuple_desc(ctd).then(format!(
+            "JoinOn({}, {})",
+            other.explain(),
+            on.join(", ")
+        )))
     }
-}
 
-impl<'a> Iterator for TableIterator<'a> {
-    type Item = tuple::Tuple;
+    // Equi-join that probes the index on other's table once per left tuple instead of
+    // looping over other's tuples, falling back to join if right_field isn't indexed.
+    // Probing reads the table rather than other's view, so other's filters are re-applied
+    // to the matches but a limit or projection on other is not.
+    pub fn index_join(
+        &self,
+        other: &TableIterator,
+        left_field: &str,
+        right_field: &str,
+    ) -> Result<TableIterator<'a>, String> {
+        if !other.table.has_index(right_field) {
+            let joined = self.join(other, left_field, right_field)?;
+            return Ok(self
+                .with_data(joined.data)
+                .with_tuple_desc(joined.td)
+                .then(format!(
+                    "Join({}, {}={})",
+                    other.explain(),
+                    left_field,
+                    right_field
+                )));
+        }
+        let ctd = self.join_desc(other)?;
+        let col = self.td.resolve_name(left_field)?;
+        let mut data = Vec::new();
+        for tuple in self.data.iter() {
+            let value = tuple.get_field(col).unwrap().clone();
+            let mut matches: Vec<Tuple> = other
+                .table
+                .lookup(right_field, value, self.tid)?
+                .into_iter()
+                .filter(|other_tuple| {
+                    other
+                        .filters
+                        .iter()
+                        .all(|(field_name, predicate)| other_tuple.filter(field_name, predicate))
+                })
+                .collect();
+            // emit matches in scan order, as join does
+            matches.sort_by_key(|t| {
+                let rid = t.get_record_id();
+                (rid.get_page_id().get_page_number(), rid.get_tuple_no())
+            });
+            for other_tuple in matches.iter() {
+                data.push(combine_tuples(tuple, other_tuple, &ctd));
+            }
+        }
+        Ok(self.with_data(data).with_tuple_desc(ctd).then(format!(
+            "IndexJoin({}, {}={})",
+            other.explain(),
+            left_field,
+            right_field
+        )))
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.current_page_index < self.data.len() {
-            let tuple = self.data[self.current_page_index].clone();
-            self.current_page_index += 1;
+    // Equi-join that builds a hash table on the right side's join column and probes it once
+    // per left tuple - produces the same tuples as join in the same order
+    pub fn hash_join(
+        &self,
+        other: &TableIterator,
+        left_field: &str,
+        right_field: &str,
+    ) -> Result<TableIterator<'a>, String> {
+        let ctd = self.join_desc(other)?;
+        let right_col = other.td.resolve_name(right_field)?;
+        let left_col = self.td.resolve_name(left_field)?;
+        let mut right: HashMap<&FieldVal, Vec<&Tuple>> = HashMap::new();
+        for other_tuple in other.data.iter() {
+            right
+                .entry(other_tuple.get_field(right_col).unwrap())
+                .or_default()
+                .push(other_tuple);
+        }
 
-            // also apply any filters here - dumb but i think it would work
-            for filter in self.filters.iter() {
-                if !tuple.filter(&filter.0, &filter.1) {
-                    return self.next();
+        let mut data = Vec::new();
+        for tuple in self.data.iter() {
+            if let Some(matches) = right.get(tuple.get_field(left_col).unwrap()) {
+                for other_tuple in matches {
+                    data.push(combine_tuples(tuple, other_tuple, &ctd));
                 }
             }
+        }
+        Ok(self.with_data(data).with_tuple_desc(ctd).then(format!(
+            "HashJoin({}, {}={})",
+            other.explain(),
+            left_field,
+            right_field
+        )))
+    }
+}
 
-            Some(tuple)
-        } else {
-            None
+// Renders a filter for explain, e.g. id>1 or name='bob'
+fn describe_filter(field_name: &str, predicate: &Predicate) -> String {
+    match predicate {
+        Predicate::Equals(value) => format!("{}='{}'", field_name, value),
+        Predicate::EqualsInt(value) => format!("{}={}", field_name, value),
+        Predicate::GreaterThan(value) => format!("{}>{}", field_name, value),
+        Predicate::LessThan(value) => format!("{}<{}", field_name, value),
+        Predicate::GreaterThanOrEqual(value) => format!("{}>={}", field_name, value),
+        Predicate::LessThanOrEqual(value) => format!("{}<={}", field_name, value),
+        Predicate::NotEqualsInt(value) => format!("{}!={}", field_name, value),
+        Predicate::NotEquals(value) => format!("{}!='{}'", field_name, value),
+        Predicate::Contains(value) => format!("{} CONTAINS '{}'", field_name, value),
+        Predicate::StartsWith(value) => format!("{} STARTS WITH '{}'", field_name, value),
+        Predicate::Between(low, high) => {
+            format!("{} BETWEEN {} AND {}", field_name, low, high)
         }
+        Predicate::StringGreaterThan(value) => format!("{}>'{}'", field_name, value),
+        Predicate::StringLessThan(value) => format!("{}<'{}'", field_name, value),
     }
 }
 
-pub enum Predicate {
-    Equals(String),
-    EqualsInt(i32),
-    GreaterThan(i32),
-    LessThan(i32),
+// Keeps the first of each group of tuples that are equal by value
+fn dedup(tuples: impl Iterator<Item = Tuple>) -> Vec<Tuple> {
+    let mut data: Vec<Tuple> = Vec::new();
+    for tuple in tuples {
+        if !data.iter().any(|t| t.value_eq(&tuple)) {
+            data.push(tuple);
+        }
+    }
+    data
+}
+
+// Concatenates two tuples into one with the combined TupleDesc, see join_desc
+fn combine_tuples(left: &Tuple, right: &Tuple, ctd: &TupleDesc) -> Tuple {
+    let combined_fields = left
+        .get_fields()
+        .into_iter()
+        .chain(right.get_fields())
+        .collect::<Vec<_>>();
+    Tuple::new(combined_fields, ctd)
+}
+
+impl<'a> Iterator for TableIterator<'a> {
+    type Item = tuple::Tuple;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // skip tuples that fail any of the filters, looping so long runs of them can't
+        // overflow the stack
+        while self.current_page_index < self.data.len() {
+            let tuple = &self.data[self.current_page_index];
+            self.current_page_index += 1;
+            let keep = self
+                .filters
+                .iter()
+                .all(|(field_name, predicate)| tuple.filter(field_name, predicate));
+            if keep {
+                return Some(tuple.clone());
+            }
+        }
+        None
+    }
+}
+
+// Aggregate operations supported by TableIterator::aggregate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggOp {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+// Aggregates a column of values. Count works on any column and counts non-NULL values;
+// Sum, Avg, Min and Max only take ints and are NULL when there are no values.
+// Avg is returned as a float.
+pub fn aggregate_values(values: &[FieldVal], op: AggOp) -> Option<FieldVal> {
+    let values: Vec<&FieldVal> = values.iter().filter(|v| !v.is_null()).collect();
+    if op == AggOp::Count {
+        return Some(FieldVal::IntField(IntField::new(values.len() as i32)));
+    }
+    let mut ints = vec![];
+    for value in values {
+        match value {
+            FieldVal::IntField(int_field) => ints.push(int_field.get_value()),
+            _ => return None,
+        }
+    }
+    if ints.is_empty() {
+        return Some(FieldVal::Null);
+    }
+    let sum: i64 = ints.iter().map(|v| *v as i64).sum();
+    let result = match op {
+        AggOp::Sum => FieldVal::IntField(IntField::new(i32::try_from(sum).ok()?)),
+        AggOp::Avg => FieldVal::FloatField(FloatField::new(sum as f64 / ints.len() as f64)),
+        AggOp::Min => FieldVal::IntField(IntField::new(*ints.iter().min().unwrap())),
+        AggOp::Max => FieldVal::IntField(IntField::new(*ints.iter().max().unwrap())),
+        AggOp::Count => unreachable!(),
+    };
+    Some(result)
+}
+
+// Splits a line of CSV into its values. Values may be wrapped in double quotes to hold
+// commas, with "" standing for a quote inside them.
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => values.push(std::mem::take(&mut value)),
+            _ => value.push(c),
+        }
+    }
+    values.push(value);
+    values
+}
+
+// Quotes s as a JSON string
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Equals(String),
+    EqualsInt(i32),
+    GreaterThan(i32),
+    LessThan(i32),
+    GreaterThanOrEqual(i32),
+    LessThanOrEqual(i32),
+    NotEqualsInt(i32),
+    NotEquals(String),
+    // substring matches on string columns, both case-sensitive
+    Contains(String),
+    StartsWith(String),
+    // ints from the first bound to the second, inclusive, nothing if the first is larger
+    Between(i32, i32),
+    // lexicographic comparisons on string columns
+    StringGreaterThan(String),
+    StringLessThan(String),
 }
 
 // trait to do filtering for filter()
@@ -236,6 +1360,15 @@ pub trait Filterable {
     fn filter(&self, field_name: &str, predicate: &Predicate) -> bool;
 }
 
+// Applies an int predicate's comparison to the field, which never matches NULLs or fields
+// of other types
+fn int_matches(field: &FieldVal, matches: impl Fn(i32) -> bool) -> bool {
+    match field {
+        FieldVal::IntField(int_field) => matches(int_field.get_value()),
+        _ => false,
+    }
+}
+
 // quick implementation of filter
 impl Filterable for Tuple {
     fn filter(&self, field_name: &str, predicate: &Predicate) -> bool {
@@ -253,28 +1386,50 @@ impl Filterable for Tuple {
                             return false;
                         }
                     }
-                    Predicate::GreaterThan(value) => {
-                        print!(
-                            "field: {:?}\n",
-                            field.clone().into_int().unwrap().get_value()
-                        );
-                        print!("value: {:?}\n", value);
-                        if let FieldVal::IntField(int_field) = &field {
-                            return int_field.get_value() > *value;
+                    Predicate::GreaterThan(value) => return int_matches(field, |v| v > *value),
+                    Predicate::LessThan(value) => return int_matches(field, |v| v < *value),
+                    Predicate::EqualsInt(value) => return int_matches(field, |v| v == *value),
+                    Predicate::GreaterThanOrEqual(value) => {
+                        return int_matches(field, |v| v >= *value)
+                    }
+                    Predicate::LessThanOrEqual(value) => {
+                        return int_matches(field, |v| v <= *value)
+                    }
+                    Predicate::NotEqualsInt(value) => return int_matches(field, |v| v != *value),
+                    Predicate::NotEquals(value) => {
+                        if let FieldVal::StringField(string_field) = &field {
+                            return string_field.get_value().as_str() != value;
+                        } else {
+                            return false;
+                        }
+                    }
+                    Predicate::Contains(value) => {
+                        if let FieldVal::StringField(string_field) = &field {
+                            return string_field.get_value().contains(value.as_str());
                         } else {
                             return false;
                         }
                     }
-                    Predicate::LessThan(value) => {
-                        if let FieldVal::IntField(int_field) = &field {
-                            return int_field.get_value() < *value;
+                    Predicate::StartsWith(value) => {
+                        if let FieldVal::StringField(string_field) = &field {
+                            return string_field.get_value().starts_with(value.as_str());
                         } else {
                             return false;
                         }
                     }
-                    Predicate::EqualsInt(value) => {
-                        if let FieldVal::IntField(int_field) = &field {
-                            return int_field.get_value() == *value;
+                    Predicate::Between(low, high) => {
+                        return int_matches(field, |v| (*low..=*high).contains(&v));
+                    }
+                    Predicate::StringGreaterThan(value) => {
+                        if let FieldVal::StringField(string_field) = &field {
+                            return string_field.get_value().as_str() > value.as_str();
+                        } else {
+                            return false;
+                        }
+                    }
+                    Predicate::StringLessThan(value) => {
+                        if let FieldVal::StringField(string_field) = &field {
+                            return string_field.get_value().as_str() < value.as_str();
                         } else {
                             return false;
                         }
@@ -285,3 +1440,1858 @@ impl Filterable for Tuple {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_pool::PAGE_SIZE;
+    use crate::catalog::Catalog;
+    use crate::fields::{BoolField, LongField, StringField};
+    use crate::heap_file::create_test_heap_file;
+    use crate::heap_page::CHECKSUM_SIZE;
+    use crate::types::Type;
+    use std::collections::HashSet;
+    use uuid::Uuid;
+
+    // Registers a fresh table backed by a temporary file with the global catalog
+    fn create_test_table(td: TupleDesc) -> Table {
+        let name = create_test_heap_file("test", td);
+        Table::new(name, "schema.txt".to_string()).unwrap()
+    }
+
+    // Parses a schema line the way load_schema does, in a catalog of its own
+    fn schema_desc(line: &str) -> TupleDesc {
+        let dir = std::env::temp_dir().join(format!("schema_test_{}", Uuid::new_v4().simple()));
+        let mut catalog = Catalog::new_with_meta(dir.join("catalog.meta").to_str().unwrap());
+        catalog.set_data_dir(dir.join("data").to_str().unwrap());
+        catalog.load_schema_from_str(line).unwrap();
+        let name = line.split_whitespace().next().unwrap();
+        let td = catalog
+            .get_table_from_name(name)
+            .unwrap()
+            .get_tuple_desc()
+            .clone();
+        std::fs::remove_dir_all(&dir).unwrap();
+        td
+    }
+
+    #[test]
+    fn test_insert_and_scan_null() {
+        let td = TupleDesc::new_with_nullable(
+            vec![Type::IntType, Type::StringType],
+            vec!["id".to_string(), "nickname".to_string()],
+            vec![false, true],
+        );
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples = vec![
+            Tuple::new(
+                vec![FieldVal::IntField(IntField::new(0)), FieldVal::Null],
+                &td,
+            ),
+            Tuple::new(
+                vec![
+                    FieldVal::IntField(IntField::new(1)),
+                    FieldVal::StringField(StringField::new("Al".to_string(), 2)),
+                ],
+                &td,
+            ),
+        ];
+        table.insert_many_tuples(tuples.clone(), tid).unwrap();
+        let db = database::get_global_db();
+        db.get_buffer_pool().commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        let scanned: Vec<Vec<FieldVal>> = table
+            .scan(10, tid)
+            .unwrap()
+            .map(|t| t.get_fields())
+            .collect();
+        let expected: Vec<Vec<FieldVal>> = tuples.iter().map(|t| t.get_fields()).collect();
+        assert_eq!(scanned, expected);
+
+        // NULL is never equal to anything
+        let mut scan = table.scan(10, tid).unwrap();
+        scan.table_filter("nickname", Predicate::Equals("".to_string()));
+        assert_eq!(scan.count(), 0);
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_scan_bool_column() {
+        let table = create_test_table(schema_desc("bools (id: Int, active: Bool)"));
+        let td = table.get_tuple_desc().clone();
+        assert_eq!(td.get_field_type(1), Some(&Type::BoolType));
+        assert_eq!(td.get_size(), 5);
+
+        let tid = TransactionId::new();
+        for i in 0..2 {
+            let fields = vec![
+                FieldVal::IntField(IntField::new(i)),
+                FieldVal::BoolField(BoolField::new(i == 1)),
+            ];
+            table.insert_tuple(Tuple::new(fields, &td), tid).unwrap();
+        }
+        let rows: Vec<String> = table
+            .scan(10, tid)
+            .unwrap()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(
+            rows,
+            vec!["{id: 0, active: false}", "{id: 1, active: true}"]
+        );
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_scan_long_column() {
+        let table = create_test_table(schema_desc("longs (id: Int, views: Long)"));
+        assert_eq!(
+            table.get_tuple_desc().get_field_type(1),
+            Some(&Type::LongType)
+        );
+
+        let tid = TransactionId::new();
+        let views = i32::MAX as i64 + 1;
+        let tuple = table
+            .make_tuple(vec![
+                FieldVal::IntField(IntField::new(0)),
+                FieldVal::LongField(LongField::new(views)),
+            ])
+            .unwrap();
+        table.insert_tuple(tuple, tid).unwrap();
+        let rows: Vec<String> = table
+            .scan_all(tid)
+            .unwrap()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(rows, vec!["{id: 0, views: 2147483648}"]);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    fn id_name_desc() -> TupleDesc {
+        TupleDesc::new(
+            vec![Type::IntType, Type::StringType],
+            vec!["id".to_string(), "name".to_string()],
+        )
+    }
+
+    fn id_name_tuple(id: i32, td: &TupleDesc) -> Tuple {
+        let name = format!("name_{}", id);
+        let len = name.len() as u32;
+        Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(id)),
+                FieldVal::StringField(StringField::new(name, len)),
+            ],
+            td,
+        )
+    }
+
+    #[test]
+    fn test_delete_tuple() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = (0..5).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples.clone(), tid).unwrap();
+
+        // tuples fill the first page in order, so the middle one is in slot 2
+        let mut middle = tuples[2].clone();
+        middle.set_record_id(RecordId::new(HeapPageId::new(table.get_id(), 0), 2));
+        table.delete_tuple(middle.clone(), tid).unwrap();
+        assert!(table.delete_tuple(middle, tid).is_err());
+
+        let scanned: Vec<Tuple> = table.scan(10, tid).unwrap().collect();
+        assert_eq!(scanned.len(), 4);
+        assert!(!scanned.contains(&tuples[2]));
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_count() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        assert_eq!(table.count(tid), Ok(0));
+        let tuples = (0..40).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        assert_eq!(table.count(tid), Ok(40));
+
+        let scanned: Vec<Tuple> = table.scan(40, tid).unwrap().collect();
+        for tuple in scanned.into_iter().take(15) {
+            table.delete_tuple(tuple, tid).unwrap();
+        }
+        assert_eq!(table.count(tid), Ok(25));
+        // a younger reader of the pages tid is writing dies instead of panicking
+        let younger = TransactionId::new();
+        assert_eq!(table.count(younger), Err(TransactionAbort::new(younger)));
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_make_tuple() {
+        let table = create_test_table(id_name_desc());
+        let tid = TransactionId::new();
+        let tuple = table
+            .make_tuple(vec![
+                FieldVal::IntField(IntField::new(7)),
+                FieldVal::StringField(StringField::new("seven".to_string(), 5)),
+            ])
+            .unwrap();
+        table.insert_tuple(tuple, tid).unwrap();
+        let rows: Vec<String> = table
+            .scan_all(tid)
+            .unwrap()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(rows, vec!["{id: 7, name: seven}"]);
+
+        assert!(table
+            .make_tuple(vec![FieldVal::IntField(IntField::new(7))])
+            .is_err());
+        assert!(table
+            .make_tuple(vec![
+                FieldVal::StringField(StringField::new("seven".to_string(), 5)),
+                FieldVal::IntField(IntField::new(7)),
+            ])
+            .is_err());
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_explain() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples = (0..5).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let mut scan = table.scan_all(tid).unwrap();
+        scan.table_filter("id", Predicate::GreaterThan(1));
+        let top = scan.order_by("id", true).limit(2);
+        let view = top.project(vec!["name".to_string()]).unwrap();
+        assert_eq!(
+            view.explain(),
+            format!(
+                "Scan({}) -> Filter(id>1) -> OrderBy(id DESC) -> Limit(2) -> Project(name)",
+                table.get_name()
+            )
+        );
+        // explaining doesn't change what the view holds
+        let names: Vec<String> = view.map(|t| t.to_string()).collect();
+        assert_eq!(names, vec!["{name: name_4}", "{name: name_3}"]);
+
+        let (left, right) = (
+            table.scan_all(tid).unwrap(),
+            table.scan_all(tid).unwrap().limit(1),
+        );
+        let joined = left.join(&right, "id", "id").unwrap();
+        assert_eq!(
+            joined.explain(),
+            format!(
+                "Scan({0}) -> Join(Scan({0}) -> Limit(1), id=id)",
+                table.get_name()
+            )
+        );
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_scan_all() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples = (0..30).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        assert_eq!(table.scan(20, tid).unwrap().count(), 20);
+        let ids: Vec<i32> = table
+            .scan_all(tid)
+            .unwrap()
+            .map(|t| t.get_int(0).unwrap())
+            .collect();
+        assert_eq!(ids, (0..30).collect::<Vec<_>>());
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_iter_tuples() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples = (0..20).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        let mut rids = HashSet::new();
+        for (i, tuple) in table.iter_tuples(tid).enumerate() {
+            assert_eq!(tuple.get_int(0), Some(i as i32));
+            let unset = RecordId::new(HeapPageId::new(0, 0), 0);
+            assert_ne!(tuple.get_record_id(), unset);
+            assert_eq!(
+                tuple.get_record_id().get_page_id().get_table_id(),
+                table.get_id()
+            );
+            rids.insert(tuple.get_record_id());
+        }
+        assert_eq!(rids.len(), 20);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_update_tuple() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = (0..3).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples.clone(), tid).unwrap();
+
+        let rid = RecordId::new(HeapPageId::new(table.get_id(), 1), 1);
+        let new_fields = vec![
+            FieldVal::IntField(IntField::new(1)),
+            FieldVal::StringField(StringField::new("Bob".to_string(), 3)),
+        ];
+        table
+            .update_tuple(rid, new_fields.clone(), tid)
+            .unwrap_err();
+        let rid = RecordId::new(HeapPageId::new(table.get_id(), 0), 1);
+        table.update_tuple(rid, new_fields.clone(), tid).unwrap();
+        // mismatched types are rejected
+        assert!(table
+            .update_tuple(rid, vec![FieldVal::IntField(IntField::new(1))], tid)
+            .is_err());
+        assert!(table
+            .update_tuple(rid, vec![new_fields[1].clone(), new_fields[0].clone()], tid)
+            .is_err());
+
+        let scanned: Vec<Tuple> = table.scan(10, tid).unwrap().collect();
+        assert_eq!(scanned.len(), 3);
+        assert_eq!(scanned[1].get_fields(), new_fields);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_update_where() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType],
+            vec!["id".to_string(), "level".to_string()],
+        );
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples = (0..40)
+            .map(|i| {
+                let fields = vec![
+                    FieldVal::IntField(IntField::new(i)),
+                    FieldVal::IntField(IntField::new(i % 4)),
+                ];
+                Tuple::new(fields, &td)
+            })
+            .collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        let db = database::get_global_db();
+        db.get_buffer_pool().commit_transaction(tid);
+        table.create_index("level").unwrap();
+
+        let tid = TransactionId::new();
+        let new_level = FieldVal::IntField(IntField::new(9));
+        let updated = table
+            .update_where("level", Predicate::GreaterThan(1), new_level.clone(), tid)
+            .unwrap();
+        assert_eq!(updated, 20);
+        let levels: Vec<i32> = table
+            .scan(usize::MAX, tid)
+            .unwrap()
+            .map(|t| t.get_int(1).unwrap())
+            .collect();
+        let expected: Vec<i32> = (0..40).map(|i| if i % 4 > 1 { 9 } else { i % 4 }).collect();
+        assert_eq!(levels, expected);
+        assert_eq!(table.lookup("level", new_level, tid).unwrap().len(), 20);
+
+        // the new value is checked even when no tuple matches
+        let name = FieldVal::StringField(StringField::new("x".to_string(), 1));
+        assert!(table
+            .update_where("level", Predicate::EqualsInt(100), name, tid)
+            .is_err());
+        assert!(table
+            .update_where("level", Predicate::EqualsInt(100), FieldVal::Null, tid)
+            .is_err());
+        assert!(table
+            .update_where("rank", Predicate::EqualsInt(0), FieldVal::Null, tid)
+            .is_err());
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_delete_where() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = (0..40).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        let deleted = table
+            .delete_where("id", Predicate::GreaterThan(10), tid)
+            .unwrap();
+        assert_eq!(deleted, 29);
+        let ids: Vec<i32> = table
+            .scan(usize::MAX, tid)
+            .unwrap()
+            .map(|t| t.get_int(0).unwrap())
+            .collect();
+        assert_eq!(ids, (0..=10).collect::<Vec<_>>());
+        assert_eq!(table.count(tid), Ok(11));
+
+        // the freed slots are reused, starting with the first page that has room
+        table.insert_tuple(id_name_tuple(100, &td), tid).unwrap();
+        let mut scan = table.scan(usize::MAX, tid).unwrap();
+        scan.table_filter("id", Predicate::EqualsInt(100));
+        let rid = scan.next().unwrap().get_record_id();
+        assert_eq!(rid.get_page_id().get_page_number(), 0);
+        assert_eq!(rid.get_tuple_no(), 11);
+        assert!(table
+            .delete_where("age", Predicate::EqualsInt(1), tid)
+            .is_err());
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = [4, 8, 15, 16, 23, 42]
+            .iter()
+            .map(|i| id_name_tuple(*i, &td))
+            .collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let int = |v| Some(FieldVal::IntField(IntField::new(v)));
+        let scan = table.scan(10, tid).unwrap();
+        assert_eq!(scan.aggregate("id", AggOp::Sum), int(108));
+        assert_eq!(scan.aggregate("id", AggOp::Max), int(42));
+        assert_eq!(scan.aggregate("id", AggOp::Min), int(4));
+        assert_eq!(scan.aggregate("name", AggOp::Count), int(6));
+        assert_eq!(
+            scan.aggregate("id", AggOp::Avg),
+            Some(FieldVal::FloatField(FloatField::new(18.0)))
+        );
+        assert_eq!(scan.aggregate("name", AggOp::Sum), None);
+        assert_eq!(scan.aggregate("missing", AggOp::Count), None);
+
+        // aggregates respect filters
+        let mut scan = table.scan(10, tid).unwrap();
+        scan.table_filter("id", Predicate::GreaterThan(15));
+        assert_eq!(scan.aggregate("id", AggOp::Sum), int(81));
+        scan.table_filter("id", Predicate::GreaterThan(100));
+        assert_eq!(scan.aggregate("id", AggOp::Max), Some(FieldVal::Null));
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_group_by() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType],
+            vec!["id".to_string(), "dept".to_string()],
+        );
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let depts = [1, 2, 1, 3, 1, 2];
+        let tuples: Vec<Tuple> = depts
+            .iter()
+            .enumerate()
+            .map(|(id, dept)| {
+                Tuple::new(
+                    vec![
+                        FieldVal::IntField(IntField::new(id as i32)),
+                        FieldVal::IntField(IntField::new(*dept)),
+                    ],
+                    &td,
+                )
+            })
+            .collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let groups: Vec<Tuple> = table
+            .scan(10, tid)
+            .unwrap()
+            .group_by("dept", "id", AggOp::Count)
+            .collect();
+        let counts: Vec<(i32, i32)> = groups
+            .iter()
+            .map(|t| {
+                let dept = t.get_field(0).unwrap().clone().into_int().unwrap();
+                let count = t.get_field(1).unwrap().clone().into_int().unwrap();
+                (dept.get_value(), count.get_value())
+            })
+            .collect();
+        assert_eq!(counts, vec![(1, 3), (2, 2), (3, 1)]);
+        assert_eq!(
+            groups[0].get_tuple_desc().get_field_name(1),
+            Some(&"count(id)".to_string())
+        );
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_order_by() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = [5, 2, 9, 1, 7]
+            .iter()
+            .map(|i| id_name_tuple(*i, &td))
+            .collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let ids = |scan: TableIterator| -> Vec<i32> {
+            scan.map(|t| {
+                t.get_field(0)
+                    .unwrap()
+                    .clone()
+                    .into_int()
+                    .unwrap()
+                    .get_value()
+            })
+            .collect()
+        };
+        let scan = table.scan(10, tid).unwrap();
+        assert_eq!(ids(scan.order_by("id", false)), vec![1, 2, 5, 7, 9]);
+        assert_eq!(ids(scan.order_by("id", true)), vec![9, 7, 5, 2, 1]);
+        // strings compare lexicographically
+        assert_eq!(ids(scan.order_by("name", true)), vec![9, 7, 5, 2, 1]);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot order by missing field")]
+    fn test_order_by_missing_field() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        table
+            .insert_many_tuples(vec![id_name_tuple(1, &td), id_name_tuple(2, &td)], tid)
+            .unwrap();
+        table.scan(10, tid).unwrap().order_by("missing", false);
+    }
+
+    #[test]
+    fn test_distinct() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType],
+            vec!["id".to_string(), "dept".to_string()],
+        );
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = [1, 2, 1, 3, 1, 2]
+            .iter()
+            .enumerate()
+            .map(|(id, dept)| {
+                Tuple::new(
+                    vec![
+                        FieldVal::IntField(IntField::new(id as i32)),
+                        FieldVal::IntField(IntField::new(*dept)),
+                    ],
+                    &td,
+                )
+            })
+            .collect();
+        table.insert_many_tuples(tuples.clone(), tid).unwrap();
+
+        let scan = table.scan(10, tid).unwrap();
+        let depts: Vec<Tuple> = scan
+            .project(vec!["dept".to_string()])
+            .unwrap()
+            .distinct()
+            .collect();
+        let depts: Vec<i32> = depts
+            .iter()
+            .map(|t| {
+                t.get_field(0)
+                    .unwrap()
+                    .clone()
+                    .into_int()
+                    .unwrap()
+                    .get_value()
+            })
+            .collect();
+        assert_eq!(depts, vec![1, 2, 3]);
+        // every full row is already unique
+        assert_eq!(scan.distinct().count(), 6);
+        // until a copy of one is stored in another slot
+        table.insert_tuple(tuples[0].clone(), tid).unwrap();
+        let scan = table.scan(10, tid).unwrap();
+        assert_eq!(scan.distinct().count(), 6);
+        assert_eq!(scan.count(), 7);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    // Inserts the ints into a single-column table and scans all of it
+    fn ints_view<'t>(table: &'t Table, values: &[i32], tid: TransactionId) -> TableIterator<'t> {
+        let td = table.get_tuple_desc().clone();
+        let tuples = values
+            .iter()
+            .map(|v| Tuple::new(vec![FieldVal::IntField(IntField::new(*v))], &td))
+            .collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        table.scan(usize::MAX, tid).unwrap()
+    }
+
+    fn ints(view: TableIterator) -> Vec<i32> {
+        view.map(|t| t.get_int(0).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_union_and_intersect() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let (a, b) = (create_test_table(td.clone()), create_test_table(td.clone()));
+        let tid = TransactionId::new();
+        let left = ints_view(&a, &[1, 2, 2], tid);
+        let right = ints_view(&b, &[3, 4], tid);
+        assert_eq!(ints(left.union(&right).unwrap()), vec![1, 2, 3, 4]);
+        assert_eq!(ints(left.union_all(&right).unwrap()), vec![1, 2, 2, 3, 4]);
+        assert_eq!(ints(left.intersect(&right).unwrap()), Vec::<i32>::new());
+
+        // b now holds 3, 4, 2, 1, 5
+        let right = ints_view(&b, &[2, 1, 5], tid);
+        assert_eq!(ints(left.intersect(&right).unwrap()), vec![1, 2]);
+        // the result has this view's schema
+        let unioned = left.union(&right).unwrap();
+        assert!(unioned.data.iter().all(|t| t.get_tuple_desc() == &td));
+
+        let strings = create_test_table(id_name_desc());
+        let other = strings.scan(usize::MAX, tid).unwrap();
+        assert!(left.union(&other).is_err());
+        assert!(left.intersect(&other).is_err());
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_insert_from() {
+        let td = id_name_desc();
+        let (source, target) = (create_test_table(td.clone()), create_test_table(td.clone()));
+        let tid = TransactionId::new();
+        for i in 0..5 {
+            source.insert_tuple(id_name_tuple(i, &td), tid).unwrap();
+        }
+        let mut scan = source.scan(usize::MAX, tid).unwrap();
+        scan.table_filter("id", Predicate::GreaterThan(2));
+        assert_eq!(target.insert_from(scan, tid).unwrap(), 2);
+        let copied: Vec<Tuple> = target.scan_all(tid).unwrap().collect();
+        let ids: Vec<i32> = copied.iter().map(|t| t.get_int(0).unwrap()).collect();
+        assert_eq!(ids, vec![3, 4]);
+        assert_eq!(copied[0].get_string(1), Some("name_3".to_string()));
+
+        let ints = create_test_table(TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]));
+        assert!(ints
+            .insert_from(source.scan(usize::MAX, tid).unwrap(), tid)
+            .is_err());
+        assert_eq!(ints.scan_all(tid).unwrap().count(), 0);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_except() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let (a, b) = (create_test_table(td.clone()), create_test_table(td));
+        let tid = TransactionId::new();
+        let left = ints_view(&a, &[1, 2, 3, 3, 4, 4], tid);
+        let right = ints_view(&b, &[2, 4, 6], tid);
+        // duplicates left over are collapsed, as in SQL
+        assert_eq!(ints(left.except(&right).unwrap()), vec![1, 3]);
+        assert_eq!(ints(right.except(&left).unwrap()), vec![6]);
+        assert_eq!(ints(left.except(&left).unwrap()), Vec::<i32>::new());
+
+        let strings = create_test_table(id_name_desc());
+        assert!(left
+            .except(&strings.scan(usize::MAX, tid).unwrap())
+            .is_err());
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_limit_offset() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = (0..20).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let scan = table.scan(20, tid).unwrap();
+        let ids: Vec<i32> = scan
+            .offset(5)
+            .limit(3)
+            .map(|t| {
+                t.get_field(0)
+                    .unwrap()
+                    .clone()
+                    .into_int()
+                    .unwrap()
+                    .get_value()
+            })
+            .collect();
+        assert_eq!(ids, vec![5, 6, 7]);
+        assert_eq!(scan.offset(50).count(), 0);
+        assert_eq!(scan.limit(50).count(), 20);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_hash_join_matches_join() {
+        let td = id_name_desc();
+        let left = create_test_table(td.clone());
+        let right_td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType],
+            vec!["owner".to_string(), "amount".to_string()],
+        );
+        let right = create_test_table(right_td.clone());
+        let tid = TransactionId::new();
+        let people: Vec<Tuple> = (0..5).map(|i| id_name_tuple(i, &td)).collect();
+        left.insert_many_tuples(people, tid).unwrap();
+        let orders: Vec<Tuple> = [(1, 10), (3, 20), (1, 30), (7, 40)]
+            .iter()
+            .map(|(owner, amount)| {
+                Tuple::new(
+                    vec![
+                        FieldVal::IntField(IntField::new(*owner)),
+                        FieldVal::IntField(IntField::new(*amount)),
+                    ],
+                    &right_td,
+                )
+            })
+            .collect();
+        right.insert_many_tuples(orders, tid).unwrap();
+
+        let left_scan = left.scan(10, tid).unwrap();
+        let right_scan = right.scan(10, tid).unwrap();
+        let nested: Vec<Tuple> = left_scan
+            .join(&right_scan, "id", "owner")
+            .unwrap()
+            .collect();
+        let hashed: Vec<Tuple> = left_scan
+            .hash_join(&right_scan, "id", "owner")
+            .unwrap()
+            .collect();
+        assert_eq!(hashed.len(), 3);
+        assert_eq!(nested, hashed);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_inclusive_and_negated_predicates() {
+        let td = id_name_desc();
+        let a = id_name_tuple(3, &td);
+        let b = id_name_tuple(4, &td);
+        assert!(a.filter("id", &Predicate::GreaterThanOrEqual(3)));
+        assert!(!a.filter("id", &Predicate::GreaterThanOrEqual(4)));
+        assert!(a.filter("id", &Predicate::LessThanOrEqual(3)));
+        assert!(!b.filter("id", &Predicate::LessThanOrEqual(3)));
+        assert!(b.filter("id", &Predicate::NotEqualsInt(3)));
+        assert!(!a.filter("id", &Predicate::NotEqualsInt(3)));
+        assert!(b.filter("name", &Predicate::NotEquals("name_3".to_string())));
+        assert!(!a.filter("name", &Predicate::NotEquals("name_3".to_string())));
+
+        // mismatched column types never match
+        assert!(!a.filter("name", &Predicate::GreaterThanOrEqual(0)));
+        assert!(!a.filter("name", &Predicate::LessThanOrEqual(0)));
+        assert!(!a.filter("name", &Predicate::NotEqualsInt(0)));
+        assert!(!a.filter("id", &Predicate::NotEquals("3".to_string())));
+    }
+
+    #[test]
+    fn test_between() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples = (0..15).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let mut scan = table.scan(20, tid).unwrap();
+        scan.table_filter("id", Predicate::Between(5, 10));
+        let ids: Vec<i32> = scan.map(|t| t.get_int(0).unwrap()).collect();
+        assert_eq!(ids, vec![5, 6, 7, 8, 9, 10]);
+
+        let mut scan = table.scan(20, tid).unwrap();
+        scan.table_filter("id", Predicate::Between(10, 5));
+        assert_eq!(scan.count(), 0);
+        // like the other int predicates it never matches a string column
+        let mut scan = table.scan(20, tid).unwrap();
+        scan.table_filter("name", Predicate::Between(0, 100));
+        assert_eq!(scan.count(), 0);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_int_predicates_on_large_scan() {
+        let td =
+            TupleDesc::new_with_nullable(vec![Type::IntType], vec!["id".to_string()], vec![true]);
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        // every tenth row is NULL, which no int predicate matches
+        let tuples = (0..5000)
+            .map(|i| {
+                let field = match i % 10 {
+                    0 => FieldVal::Null,
+                    _ => FieldVal::IntField(IntField::new(i)),
+                };
+                Tuple::new(vec![field], &td)
+            })
+            .collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let expected =
+            |keep: fn(i32) -> bool| (0..5000).filter(|i| i % 10 != 0 && keep(*i)).count();
+        let cases = [
+            (Predicate::GreaterThan(4000), expected(|v| v > 4000)),
+            (Predicate::LessThan(100), expected(|v| v < 100)),
+            (Predicate::EqualsInt(4321), expected(|v| v == 4321)),
+            (Predicate::GreaterThanOrEqual(4990), expected(|v| v >= 4990)),
+            (Predicate::LessThanOrEqual(15), expected(|v| v <= 15)),
+            (Predicate::NotEqualsInt(7), expected(|v| v != 7)),
+            (
+                Predicate::Between(10, 30),
+                expected(|v| (10..=30).contains(&v)),
+            ),
+        ];
+        for (predicate, count) in cases {
+            let mut scan = table.scan_all(tid).unwrap();
+            scan.table_filter("id", predicate.clone());
+            assert_eq!(scan.count(), count, "{:?}", predicate);
+        }
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_filter_skips_long_runs() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples = (0..20_000)
+            .map(|i| Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td))
+            .collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        // every tuple before the last few fails the first filter
+        let mut scan = table.scan_all(tid).unwrap();
+        scan.table_filter("id", Predicate::GreaterThan(19_990));
+        scan.table_filter("id", Predicate::NotEqualsInt(19_995));
+        let ids: Vec<i32> = scan.map(|t| t.get_int(0).unwrap()).collect();
+        assert_eq!(
+            ids,
+            vec![19_991, 19_992, 19_993, 19_994, 19_996, 19_997, 19_998, 19_999]
+        );
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_contains_and_starts_with() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let names = ["Alice", "Malik", "Bob", "alina"];
+        let tuples: Vec<Tuple> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                Tuple::new(
+                    vec![
+                        FieldVal::IntField(IntField::new(i as i32)),
+                        FieldVal::StringField(StringField::new(
+                            name.to_string(),
+                            name.len() as u32,
+                        )),
+                    ],
+                    &td,
+                )
+            })
+            .collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let matching = |predicate: Predicate| -> Vec<String> {
+            let mut scan = table.scan(10, tid).unwrap();
+            scan.table_filter("name", predicate);
+            scan.map(|t| {
+                t.get_field(1)
+                    .unwrap()
+                    .clone()
+                    .into_string()
+                    .unwrap()
+                    .get_value()
+            })
+            .collect()
+        };
+        assert_eq!(
+            matching(Predicate::Contains("li".to_string())),
+            vec!["Alice", "Malik", "alina"]
+        );
+        assert_eq!(
+            matching(Predicate::StartsWith("Al".to_string())),
+            vec!["Alice"]
+        );
+        // non-string columns never match
+        assert!(!id_name_tuple(1, &td).filter("id", &Predicate::Contains("1".to_string())));
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let tid = TransactionId::new();
+        let tuples = (0..100).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        bp.commit_transaction(tid);
+        assert!(table.heap_file.num_pages() > 1);
+        table.create_index("id").unwrap();
+        let lookup = |id, tid| {
+            table
+                .lookup("id", FieldVal::IntField(IntField::new(id)), tid)
+                .unwrap()
+        };
+
+        let tid = TransactionId::new();
+        table.truncate(tid).unwrap();
+        assert_eq!(table.count(tid), Ok(0));
+        assert!(lookup(42, tid).is_empty());
+        bp.abort_transaction(tid);
+        let tid = TransactionId::new();
+        assert_eq!(table.count(tid), Ok(100));
+        // the index still finds the restored tuples
+        assert_eq!(lookup(42, tid).len(), 1);
+        bp.commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        table.truncate(tid).unwrap();
+        bp.commit_transaction(tid);
+        let tid = TransactionId::new();
+        assert_eq!(table.scan_all(tid).unwrap().count(), 0);
+        assert!(lookup(42, tid).is_empty());
+        // the emptied pages are reused by later inserts
+        table.insert_tuple(id_name_tuple(7, &td), tid).unwrap();
+        let rid = table.scan_all(tid).unwrap().next().unwrap().get_record_id();
+        assert_eq!(rid.get_page_id().get_page_number(), 0);
+        // the slot's old entry doesn't match the value now in it
+        assert!(lookup(0, tid).is_empty());
+        assert_eq!(lookup(7, tid).len(), 1);
+        bp.commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_table_lock_blocks_insert() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        // the inserter is older, so under WAIT-DIE it waits instead of dying
+        let inserter = TransactionId::new();
+        let holder = TransactionId::new();
+        bp.get_lock_manager()
+            .acquire_table_lock(holder, table.table_id, true)
+            .unwrap();
+
+        let waited = std::thread::scope(|s| {
+            let handle = s.spawn(|| {
+                let start = std::time::Instant::now();
+                table.insert_tuple(id_name_tuple(1, &td), inserter).unwrap();
+                start.elapsed()
+            });
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            assert!(!handle.is_finished());
+            bp.commit_transaction(holder);
+            handle.join().unwrap()
+        });
+        assert!(waited >= std::time::Duration::from_millis(200));
+        assert_eq!(table.count(inserter), Ok(1));
+        // the table can't be locked exclusively while another transaction holds its pages
+        let younger = TransactionId::new();
+        assert!(bp
+            .get_lock_manager()
+            .acquire_table_lock(younger, table.table_id, true)
+            .is_err());
+        bp.commit_transaction(inserter);
+    }
+
+    #[test]
+    fn test_string_comparisons() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        for (i, name) in ["Zoe", "Alice", "Mia", "Bob", "Noah"].iter().enumerate() {
+            let fields = vec![
+                FieldVal::IntField(IntField::new(i as i32)),
+                FieldVal::StringField(StringField::new(name.to_string(), name.len() as u32)),
+            ];
+            table.insert_tuple(Tuple::new(fields, &td), tid).unwrap();
+        }
+        let names = |predicate: Predicate| -> Vec<String> {
+            let mut scan = table.scan_all(tid).unwrap();
+            scan.table_filter("name", predicate);
+            scan.map(|t| {
+                t.get_field(1)
+                    .unwrap()
+                    .clone()
+                    .into_string()
+                    .unwrap()
+                    .get_value()
+            })
+            .collect()
+        };
+        // names after "M", which "M" itself would sort before
+        assert_eq!(
+            names(Predicate::StringGreaterThan("M".to_string())),
+            vec!["Zoe", "Mia", "Noah"]
+        );
+        assert_eq!(
+            names(Predicate::StringLessThan("Mia".to_string())),
+            vec!["Alice", "Bob"]
+        );
+        // int columns never match
+        let predicate = Predicate::StringLessThan("9".to_string());
+        assert!(!id_name_tuple(1, &td).filter("id", &predicate));
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_scan_range_skips_pages() {
+        let td = id_name_desc();
+        let name = format!("range_{}", Uuid::new_v4());
+        let mut heap_file = HeapFile::new_in_memory(td.clone());
+        heap_file.set_key_field(0).unwrap();
+        assert!(heap_file.set_key_field(1).is_err());
+        let db = database::get_global_db();
+        db.get_catalog().add_table(heap_file, name.clone());
+        let table = Table::new(name, "schema.txt".to_string()).unwrap();
+        let tid = TransactionId::new();
+        // ids are inserted in order, so each page holds a narrow range of them
+        let tuples: Vec<Tuple> = (0..60).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        db.get_buffer_pool().commit_transaction(tid);
+        assert!(table.heap_file.num_pages() > 2);
+
+        let lm = db.get_buffer_pool().get_lock_manager();
+        let tid = TransactionId::new();
+        let ids: Vec<i32> = table
+            .scan_range("id", 20, 25, tid)
+            .unwrap()
+            .map(|t| t.get_int(0).unwrap())
+            .collect();
+        assert_eq!(ids, (20..=25).collect::<Vec<_>>());
+        let range_pages = lm.get_locked_pages(tid).len();
+        db.get_buffer_pool().commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        assert_eq!(table.scan(usize::MAX, tid).unwrap().count(), 60);
+        let full_pages = lm.get_locked_pages(tid).len();
+        assert!(
+            range_pages < full_pages,
+            "{} vs {}",
+            range_pages,
+            full_pages
+        );
+        assert_eq!(table.scan_range("id", 100, 200, tid).unwrap().count(), 0);
+        db.get_buffer_pool().commit_transaction(tid);
+
+        // a writer's uncommitted delete narrows its page's key range, so a reader that
+        // pruned on it would miss the committed tuple instead of waiting it out
+        let writer = TransactionId::new();
+        let reader = TransactionId::new();
+        let tuple = table
+            .scan_all(writer)
+            .unwrap()
+            .find(|t| t.get_int(0) == Some(22))
+            .unwrap();
+        table.delete_tuple(tuple, writer).unwrap();
+        assert!(table.scan_range("id", 22, 22, reader).is_err());
+        db.get_buffer_pool().abort_transaction(writer);
+        let reader = TransactionId::new();
+        assert_eq!(table.scan_range("id", 22, 22, reader).unwrap().count(), 1);
+        db.get_buffer_pool().commit_transaction(reader);
+    }
+
+    #[test]
+    fn test_index_lookup() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        // enough tuples to fill several pages
+        let tuples: Vec<Tuple> = (0..40).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        let db = database::get_global_db();
+        db.get_buffer_pool().commit_transaction(tid);
+
+        table.create_index("id").unwrap();
+        let tid = TransactionId::new();
+        table.insert_tuple(id_name_tuple(100, &td), tid).unwrap();
+        db.get_buffer_pool().commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        let found = table
+            .lookup("id", FieldVal::IntField(IntField::new(33)), tid)
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_fields(), id_name_tuple(33, &td).get_fields());
+        // only the page holding the match was read, not the whole table
+        let lm = db.get_buffer_pool().get_lock_manager();
+        assert_eq!(lm.get_locked_pages(tid).len(), 1);
+        assert!(lm
+            .holds_lock(tid, HeapPageId::new(table.get_id(), 0))
+            .is_none());
+
+        // inserts after the index was built are picked up
+        let found = table
+            .lookup("id", FieldVal::IntField(IntField::new(100)), tid)
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(table
+            .lookup("id", FieldVal::IntField(IntField::new(-1)), tid)
+            .unwrap()
+            .is_empty());
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_index_belongs_to_its_table() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let db = database::get_global_db();
+        let tid = TransactionId::new();
+        table.insert_tuple(id_name_tuple(1, &td), tid).unwrap();
+        db.get_buffer_pool().commit_transaction(tid);
+        table.create_index("id").unwrap();
+
+        // another Table on the same file doesn't maintain the first one's index
+        let other = Table::new(table.get_name().to_string(), "schema.txt".to_string()).unwrap();
+        assert!(!other.has_index("id"));
+        let tid = TransactionId::new();
+        other.insert_tuple(id_name_tuple(2, &td), tid).unwrap();
+        db.get_buffer_pool().commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        let lookup = |table: &Table, id| {
+            table
+                .lookup("id", FieldVal::IntField(IntField::new(id)), tid)
+                .unwrap()
+        };
+        assert_eq!(lookup(&table, 1).len(), 1);
+        assert!(lookup(&table, 2).is_empty());
+        // without an index of its own the other Table scans and finds both
+        assert_eq!(lookup(&other, 2).len(), 1);
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_compact_rebuilds_indexes() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = (0..40).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        let db = database::get_global_db();
+        db.get_buffer_pool().commit_transaction(tid);
+        table.create_index("id").unwrap();
+
+        let tid = TransactionId::new();
+        let evens: Vec<Tuple> = table
+            .scan_all(tid)
+            .unwrap()
+            .filter(|t| t.get_int(0).unwrap() % 2 == 0)
+            .collect();
+        for tuple in evens {
+            table.delete_tuple(tuple, tid).unwrap();
+        }
+        db.get_buffer_pool().commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        table.compact(tid).unwrap();
+        db.get_buffer_pool().commit_transaction(tid);
+
+        // the last tuples moved to earlier pages, and the index followed them
+        let tid = TransactionId::new();
+        let found = table
+            .lookup("id", FieldVal::IntField(IntField::new(39)), tid)
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_fields(), id_name_tuple(39, &td).get_fields());
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_index_join_matches_join() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType],
+            vec!["id".to_string(), "dept".to_string()],
+        );
+        let ints = |a: i32, b: i32| {
+            Tuple::new(
+                vec![
+                    FieldVal::IntField(IntField::new(a)),
+                    FieldVal::IntField(IntField::new(b)),
+                ],
+                &td,
+            )
+        };
+        let left = create_test_table(td.clone());
+        let right = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        left.insert_many_tuples((0..10).map(|i| ints(i, i % 3)).collect(), tid)
+            .unwrap();
+        // several right tuples per dept, spread over a few pages
+        right
+            .insert_many_tuples((0..40).map(|i| ints(i % 4, i)).collect(), tid)
+            .unwrap();
+        let db = database::get_global_db();
+        db.get_buffer_pool().commit_transaction(tid);
+
+        let tid = TransactionId::new();
+        let (l, r) = (
+            left.scan(usize::MAX, tid).unwrap(),
+            right.scan(usize::MAX, tid).unwrap(),
+        );
+        // without an index it's the same as the nested loop
+        let fallback: Vec<Tuple> = l.index_join(&r, "dept", "id").unwrap().collect();
+        let expected: Vec<Tuple> = l.join(&r, "dept", "id").unwrap().collect();
+        assert_eq!(fallback, expected);
+
+        right.create_index("id").unwrap();
+        assert!(right.has_index("id"));
+        let indexed: Vec<Tuple> = l.index_join(&r, "dept", "id").unwrap().collect();
+        assert_eq!(indexed.len(), 100);
+        assert_eq!(indexed, expected);
+
+        // filters on the right side still apply
+        let mut r = right.scan(usize::MAX, tid).unwrap();
+        r.table_filter("dept", Predicate::LessThan(10));
+        let indexed: Vec<Tuple> = l.index_join(&r, "dept", "id").unwrap().collect();
+        assert_eq!(indexed.len(), 27);
+        assert!(indexed.iter().all(|t| t.get_int(3).unwrap() < 10));
+        db.get_buffer_pool().commit_transaction(tid);
+
+        // probing a page an older transaction is writing aborts the join instead of panicking
+        let writer = TransactionId::new();
+        let tid = TransactionId::new();
+        let l = left.scan(usize::MAX, tid).unwrap();
+        let r = right.scan(usize::MAX, writer).unwrap();
+        let first = right.scan(1, writer).unwrap().next().unwrap();
+        right.delete_tuple(first, writer).unwrap();
+        let err = l.index_join(&r, "dept", "id").err().unwrap();
+        assert!(err.contains("aborted"), "{}", err);
+        db.get_buffer_pool().commit_transaction(writer);
+    }
+
+    #[test]
+    fn test_join_on_multiple_columns() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType, Type::IntType],
+            vec!["id".to_string(), "dept".to_string(), "region".to_string()],
+        );
+        let ints = |values: &[i32], td: &TupleDesc| {
+            Tuple::new(
+                values
+                    .iter()
+                    .map(|v| FieldVal::IntField(IntField::new(*v)))
+                    .collect(),
+                td,
+            )
+        };
+        let employees = create_test_table(td.clone());
+        let offices_td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType, Type::IntType],
+            vec!["office".to_string(), "d".to_string(), "r".to_string()],
+        );
+        let offices = create_test_table(offices_td.clone());
+        let tid = TransactionId::new();
+        let people = [[1, 10, 1], [2, 10, 2], [3, 20, 1]];
+        for p in people.iter() {
+            employees.insert_tuple(ints(p, &td), tid).unwrap();
+        }
+        for o in [[100, 10, 1], [200, 20, 2]].iter() {
+            offices.insert_tuple(ints(o, &offices_td), tid).unwrap();
+        }
+
+        let left = employees.scan(10, tid).unwrap();
+        let right = offices.scan(10, tid).unwrap();
+        let joined: Vec<Tuple> = left
+            .join_on(&right, &[("dept", "d"), ("region", "r")])
+            .unwrap()
+            .collect();
+        // only employee 1 matches an office on both dept and region
+        assert_eq!(joined.len(), 1);
+        assert_eq!(
+            joined[0].get_fields(),
+            ints(&[1, 10, 1, 100, 10, 1], &td).get_fields()
+        );
+        assert_eq!(joined[0].get_tuple_desc().get_num_fields(), 6);
+        let err = left.join_on(&right, &[("dept", "missing")]).err().unwrap();
+        assert!(err.contains("missing"));
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_left_join() {
+        let td = id_name_desc();
+        let people = create_test_table(td.clone());
+        let orders_td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType],
+            vec!["owner".to_string(), "amount".to_string()],
+        );
+        let orders = create_test_table(orders_td.clone());
+        let tid = TransactionId::new();
+        people
+            .insert_many_tuples(vec![id_name_tuple(1, &td), id_name_tuple(2, &td)], tid)
+            .unwrap();
+        let order = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::IntField(IntField::new(50)),
+            ],
+            &orders_td,
+        );
+        orders.insert_tuple(order, tid).unwrap();
+
+        let joined: Vec<Tuple> = people
+            .scan(10, tid)
+            .unwrap()
+            .left_join(&orders.scan(10, tid).unwrap(), "id", "owner")
+            .unwrap()
+            .collect();
+        assert_eq!(joined.len(), 2);
+        assert_eq!(
+            joined[0].get_tuple_desc(),
+            &TupleDesc::combine(
+                &td.with_prefix(people.get_name()),
+                &orders_td.with_prefix(orders.get_name())
+            )
+        );
+        assert_eq!(
+            joined[0].get_field(3),
+            Some(&FieldVal::IntField(IntField::new(50)))
+        );
+        // person 2 has no orders
+        assert_eq!(
+            joined[1].get_field(0),
+            Some(&FieldVal::IntField(IntField::new(2)))
+        );
+        assert_eq!(joined[1].get_field(2), Some(&FieldVal::Null));
+        assert_eq!(joined[1].get_field(3), Some(&FieldVal::Null));
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_scan_record_ids() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        // spans two pages
+        let tuples: Vec<Tuple> = (0..20).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let scanned: Vec<Tuple> = table.scan(20, tid).unwrap().collect();
+        let victim = scanned
+            .iter()
+            .find(|t| t.get_fields() == id_name_tuple(17, &td).get_fields())
+            .unwrap()
+            .clone();
+        assert_eq!(victim.get_record_id().get_page_id().get_page_number(), 1);
+        table.delete_tuple(victim, tid).unwrap();
+
+        let remaining: Vec<Tuple> = table.scan(20, tid).unwrap().collect();
+        assert_eq!(remaining.len(), 19);
+        assert!(remaining
+            .iter()
+            .all(|t| t.get_fields() != id_name_tuple(17, &td).get_fields()));
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_stream() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let per_page = (PAGE_SIZE - CHECKSUM_SIZE) * 8 / (td.get_size() * 8 + 1);
+        // the pool caches every page it reads, so this only checks what the stream buffers
+        let num_tuples = per_page * 5;
+        let tuples: Vec<Tuple> = (0..num_tuples as i32)
+            .map(|i| id_name_tuple(i, &td))
+            .collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+
+        let mut stream = table.stream(tid);
+        let mut count = 0;
+        while stream.next().is_some() {
+            // never more than a page of tuples buffered
+            assert!(stream.page_tuples.len() < per_page);
+            count += 1;
+        }
+        assert_eq!(count, num_tuples);
+
+        let mut stream = table.stream(tid);
+        stream.table_filter("id", Predicate::LessThan(3));
+        let names: Vec<Tuple> = stream.project(vec!["name".to_string()]).unwrap().collect();
+        assert_eq!(names.len(), 3);
+        assert_eq!(
+            names[2].get_fields(),
+            vec![FieldVal::StringField(StringField::new(
+                "name_2".to_string(),
+                6
+            ))]
+        );
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_scan_sees_own_writes() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let setup = TransactionId::new();
+        table.insert_tuple(id_name_tuple(0, &td), setup).unwrap();
+        bp.commit_transaction(setup);
+
+        for isolation in [
+            IsolationLevel::Serializable,
+            IsolationLevel::ReadCommitted,
+            IsolationLevel::ReadUncommitted,
+        ] {
+            let tid = TransactionId::with_isolation(isolation);
+            table.insert_tuple(id_name_tuple(1, &td), tid).unwrap();
+            let pid = HeapPageId::new(table.table_id, 0);
+            let page = bp.get_page(tid, pid, Permission::Read).unwrap();
+            assert_eq!(page.read().unwrap().get_dirtied_by(), Some(tid));
+
+            // both scans read the dirty page's current image rather than its before image
+            let ids: Vec<i32> = table
+                .scan_all(tid)
+                .unwrap()
+                .map(|t| t.get_int(0).unwrap())
+                .collect();
+            assert_eq!(ids, vec![0, 1], "{:?}", isolation);
+            let ids: Vec<i32> = table.stream(tid).map(|t| t.get_int(0).unwrap()).collect();
+            assert_eq!(ids, vec![0, 1], "{:?}", isolation);
+            bp.abort_transaction(tid);
+        }
+
+        let tid = TransactionId::new();
+        assert_eq!(table.scan_all(tid).unwrap().count(), 1);
+        bp.commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_snapshot_scan_ignores_concurrent_commits() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let db = database::get_global_db();
+        let bp = db.get_buffer_pool();
+        let tid = TransactionId::new();
+        let tuples: Vec<Tuple> = (0..40).map(|i| id_name_tuple(i, &td)).collect();
+        table.insert_many_tuples(tuples, tid).unwrap();
+        bp.commit_transaction(tid);
+        assert!(table.heap_file.num_pages() > 2);
+
+        let snapshot = TransactionId::with_isolation(IsolationLevel::Snapshot);
+        let mut stream = table.stream(snapshot);
+        assert_eq!(stream.next().unwrap().get_int(0), Some(0));
+        assert!(bp.get_lock_manager().get_locked_pages(snapshot).is_empty());
+
+        // a writer changes a page the scan hasn't reached yet and commits without waiting
+        let writer = TransactionId::new();
+        let mut scan = table.scan(usize::MAX, writer).unwrap();
+        scan.table_filter("id", Predicate::EqualsInt(30));
+        let rid = scan.next().unwrap().get_record_id();
+        assert!(rid.get_page_id().get_page_number() > 0);
+        table
+            .update_tuple(rid, id_name_tuple(1000, &td).get_fields(), writer)
+            .unwrap();
+        table.insert_tuple(id_name_tuple(41, &td), writer).unwrap();
+        bp.commit_transaction(writer);
+
+        let ids: Vec<i32> = stream.map(|t| t.get_int(0).unwrap()).collect();
+        assert_eq!(ids, (1..40).collect::<Vec<_>>());
+        // writing aborts the snapshot transaction
+        assert!(table
+            .insert_tuple(id_name_tuple(42, &td), snapshot)
+            .is_err());
+        assert!(bp.get_lock_manager().get_locked_pages(snapshot).is_empty());
+
+        // a snapshot started afterwards sees the change
+        let snapshot = TransactionId::with_isolation(IsolationLevel::Snapshot);
+        let ids: Vec<i32> = table
+            .stream(snapshot)
+            .map(|t| t.get_int(0).unwrap())
+            .collect();
+        assert!(ids.contains(&1000) && ids.contains(&41) && !ids.contains(&30));
+        bp.commit_transaction(snapshot);
+    }
+
+    #[test]
+    fn test_import_csv() {
+        let table = create_test_table(id_name_desc());
+        let path = std::env::temp_dir().join(format!("import_{}.csv", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "id,name\n1,name_1\n2,\"name, 2\"\n\n3,name_3\n").unwrap();
+        let tid = TransactionId::new();
+        assert_eq!(table.import_csv(path, tid), Ok(3));
+        let names: Vec<String> = table
+            .scan(10, tid)
+            .unwrap()
+            .map(|t| t.get_string_by_name("name").unwrap())
+            .collect();
+        assert_eq!(names, vec!["name_1", "name, 2", "name_3"]);
+
+        // bad rows are reported with their line number and nothing is inserted
+        std::fs::write(path, "4,name_4\nfive,name_5\n").unwrap();
+        let err = table.import_csv(path, tid).unwrap_err();
+        assert!(err.starts_with("line 2:"), "{}", err);
+        std::fs::write(path, "4,name_4,extra\n").unwrap();
+        let err = table.import_csv(path, tid).unwrap_err();
+        assert_eq!(err, "line 1: expected 2 fields, found 3");
+        assert_eq!(table.scan(10, tid).unwrap().count(), 3);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_format_table() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        for i in [1, 100] {
+            table.insert_tuple(id_name_tuple(i, &td), tid).unwrap();
+        }
+        let expected = "\
+id  | name
+----+---------
+1   | name_1
+100 | name_100
+";
+        assert_eq!(table.scan_all(tid).unwrap().format_table(), expected);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_export_csv_and_json() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let mut quoted = id_name_tuple(2, &td);
+        let name = "say \"hi\", bye".to_string();
+        let len = name.len() as u32;
+        quoted.set_field(1, FieldVal::StringField(StringField::new(name, len)));
+        table
+            .insert_many_tuples(vec![id_name_tuple(1, &td), quoted], tid)
+            .unwrap();
+
+        let scan = table.scan(10, tid).unwrap();
+        let projection = scan
+            .project(vec!["id".to_string(), "name".to_string()])
+            .unwrap();
+        assert_eq!(
+            projection.to_csv(),
+            "id,name\n1,\"name_1\"\n2,\"say \"\"hi\"\", bye\"\n"
+        );
+        assert_eq!(
+            projection.to_json(),
+            r#"[{"id":1,"name":"name_1"},{"id":2,"name":"say \"hi\", bye"}]"#
+        );
+
+        // joined views export the combined schema
+        let other = create_test_table(td.clone());
+        other.insert_tuple(id_name_tuple(1, &td), tid).unwrap();
+        let ids = scan.project(vec!["id".to_string()]).unwrap();
+        let joined = ids.join(&other.scan(10, tid).unwrap(), "id", "id").unwrap();
+        let header = format!(
+            "{}.id,{}.id,{}.name",
+            table.get_name(),
+            other.get_name(),
+            other.get_name()
+        );
+        assert_eq!(joined.to_csv(), format!("{}\n1,1,\"name_1\"\n", header));
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let td = TupleDesc::new_with_nullable(
+            vec![Type::IntType, Type::IntType, Type::StringType],
+            vec!["id".to_string(), "age".to_string(), "name".to_string()],
+            vec![false, true, false],
+        );
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        for (id, age) in [(3, Some(40)), (-2, None), (7, Some(18))] {
+            let age = age.map_or(FieldVal::Null, |a| FieldVal::IntField(IntField::new(a)));
+            let fields = vec![
+                FieldVal::IntField(IntField::new(id)),
+                age,
+                FieldVal::StringField(StringField::new("x".to_string(), 1)),
+            ];
+            table.insert_tuple(Tuple::new(fields, &td), tid).unwrap();
+        }
+
+        let statistics = table.analyze(tid).unwrap();
+        assert_eq!(statistics.get_num_tuples(), 3);
+        assert_eq!(statistics.get_int_range("id"), Some((-2, 7)));
+        assert_eq!(statistics.get_int_range("age"), Some((18, 40)));
+        assert_eq!(statistics.get_int_range("name"), None);
+        assert_eq!(statistics.get_page_fill_factors().len(), 1);
+        assert!(statistics.get_page_fill_factors()[0] > 0.0);
+        let db = database::get_global_db();
+        assert_eq!(
+            db.get_catalog().get_statistics(table.get_id()),
+            Some(statistics)
+        );
+        db.get_buffer_pool().commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_new_missing_table() {
+        let name = format!("missing_{}", Uuid::new_v4());
+        let db = database::get_global_db();
+        assert!(!db.get_catalog().contains(&name));
+        let err = Table::new(name.clone(), "schema.txt".to_string()).err();
+        assert_eq!(err, Some(format!("table not found: {}", name)));
+
+        let table = create_test_table(id_name_desc());
+        assert!(db.get_catalog().contains(table.get_name()));
+    }
+
+    #[test]
+    fn test_project_as() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        table.insert_tuple(id_name_tuple(1, &td), tid).unwrap();
+
+        let scan = table.scan(10, tid).unwrap();
+        let projection = scan
+            .project_as(vec![
+                ("id".to_string(), "employee_id".to_string()),
+                ("name".to_string(), "name".to_string()),
+            ])
+            .unwrap();
+        assert!(projection
+            .explain()
+            .ends_with("Project(id AS employee_id, name AS name)"));
+        let tuples: Vec<Tuple> = projection.collect();
+        let ptd = tuples[0].get_tuple_desc();
+        assert_eq!(ptd.get_field_name(0).unwrap(), "employee_id");
+        assert_eq!(ptd.get_field_type(0), Some(&Type::IntType));
+        assert_eq!(ptd.name_to_id("id"), None);
+        assert_eq!(tuples[0].get_int_by_name("employee_id"), Some(1));
+        assert_eq!(tuples[0].get_string(1), Some("name_1".to_string()));
+
+        assert!(scan
+            .project_as(vec![("age".to_string(), "years".to_string())])
+            .is_err());
+        assert!(scan
+            .project_as(vec![
+                ("id".to_string(), "x".to_string()),
+                ("name".to_string(), "x".to_string()),
+            ])
+            .is_err());
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_project_in_requested_order() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        table.insert_tuple(id_name_tuple(1, &td), tid).unwrap();
+
+        let scan = table.scan(10, tid).unwrap();
+        let projection = scan
+            .project(vec!["name".to_string(), "id".to_string()])
+            .unwrap();
+        let tuples: Vec<Tuple> = projection.collect();
+        let ptd = tuples[0].get_tuple_desc();
+        assert_eq!(ptd.get_field_name(0).unwrap(), "name");
+        assert_eq!(ptd.get_field_type(0), Some(&Type::StringType));
+        assert_eq!(ptd.get_field_name(1).unwrap(), "id");
+        assert_eq!(tuples[0].get_string(0), Some("name_1".to_string()));
+        assert_eq!(tuples[0].get_int(1), Some(1));
+
+        let streamed: Vec<Tuple> = table
+            .stream(tid)
+            .project(vec!["name".to_string(), "id".to_string()])
+            .unwrap()
+            .collect();
+        assert_eq!(streamed[0].get_fields(), tuples[0].get_fields());
+
+        assert!(scan.project(vec!["age".to_string()]).is_err());
+        assert!(table.stream(tid).project(vec!["age".to_string()]).is_err());
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_empty_view_keeps_schema() {
+        let td = id_name_desc();
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        let empty = table.scan_all(tid).unwrap();
+        assert_eq!(empty.to_csv(), "id,name\n");
+
+        let names = empty.project(vec!["name".to_string()]).unwrap();
+        assert_eq!(names.to_csv(), "name\n");
+        assert!(empty.project(vec!["age".to_string()]).is_err());
+        let groups = empty.group_by("id", "name", AggOp::Count);
+        assert_eq!(groups.to_csv(), "id,count(name)\n");
+        let joined = names.join(&empty, "name", "name").unwrap();
+        assert_eq!(joined.to_csv(), "left.name,right.id,right.name\n");
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_join_prefixes_field_names() {
+        let td = id_name_desc();
+        let people = create_test_table(td.clone());
+        let pets_td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType],
+            vec!["id".to_string(), "owner".to_string()],
+        );
+        let pets = create_test_table(pets_td.clone());
+        let tid = TransactionId::new();
+        people.insert_tuple(id_name_tuple(1, &td), tid).unwrap();
+        let pet = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(7)),
+                FieldVal::IntField(IntField::new(1)),
+            ],
+            &pets_td,
+        );
+        pets.insert_tuple(pet, tid).unwrap();
+
+        let people_scan = people.scan(10, tid).unwrap();
+        let joined: Vec<Tuple> = people_scan
+            .join(&pets.scan(10, tid).unwrap(), "id", "owner")
+            .unwrap()
+            .collect();
+        let joined = &joined[0];
+        let people_id = format!("{}.id", people.get_name());
+        let pets_id = format!("{}.id", pets.get_name());
+        assert_eq!(joined.get_int_by_name(&people_id), Some(1));
+        assert_eq!(joined.get_int_by_name(&pets_id), Some(7));
+        // unambiguous names still work without the prefix, ambiguous ones don't
+        assert_eq!(
+            joined.get_string_by_name("name"),
+            Some("name_1".to_string())
+        );
+        assert_eq!(joined.get_tuple_desc().name_to_id("id"), None);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+
+    #[test]
+    fn test_self_join() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::IntType],
+            vec!["id".to_string(), "manager".to_string()],
+        );
+        let table = create_test_table(td.clone());
+        let tid = TransactionId::new();
+        for (id, manager) in [(1, 0), (2, 1), (3, 1)] {
+            let fields = vec![
+                FieldVal::IntField(IntField::new(id)),
+                FieldVal::IntField(IntField::new(manager)),
+            ];
+            table.insert_tuple(Tuple::new(fields, &td), tid).unwrap();
+        }
+
+        // the sides of a self-join are told apart as left and right
+        let (employees, managers) = (table.scan_all(tid).unwrap(), table.scan_all(tid).unwrap());
+        let joined = employees.join(&managers, "manager", "id").unwrap();
+        let pairs: Vec<(i32, i32)> = joined
+            .data
+            .iter()
+            .map(|t| {
+                let employee = t.get_int_by_name("left.id").unwrap();
+                (employee, t.get_int_by_name("right.id").unwrap())
+            })
+            .collect();
+        assert_eq!(pairs, vec![(2, 1), (3, 1)]);
+        let other = create_test_table(td.clone());
+        let err = joined
+            .hash_join(&other.scan_all(tid).unwrap(), "id", "id")
+            .err()
+            .unwrap();
+        assert_eq!(err, "ambiguous field name id");
+        // joining the result with the table again would repeat its prefix
+        let err = joined.join(&managers, "left.id", "id").err().unwrap();
+        assert!(err.starts_with("ambiguous field name"), "{}", err);
+        database::get_global_db()
+            .get_buffer_pool()
+            .commit_transaction(tid);
+    }
+}
@@ -1,25 +1,310 @@
+use crate::buffer_pool::BufferPool;
+use crate::heap_page::HeapPageId;
+#[cfg(test)]
+use std::cell::Cell;
+use std::cmp;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicU64, Ordering};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+// the tid handed to the next transaction
+static NEXT_TID: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(test)]
+thread_local! {
+    // counter installed on this thread by TransactionId::reset_for_test
+    static TEST_NEXT_TID: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+fn next_tid() -> u64 {
+    #[cfg(test)]
+    if let Some(tid) = TEST_NEXT_TID.with(|next| {
+        let tid = next.get()?;
+        next.set(Some(tid + 1));
+        Some(tid)
+    }) {
+        return tid;
+    }
+    NEXT_TID.fetch_add(1, Ordering::SeqCst)
+}
+
+// How much a transaction's reads are isolated from concurrent writers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum IsolationLevel {
+    // reads take no locks and may see uncommitted changes
+    ReadUncommitted,
+    // reads wait for writers but release their locks right after reading
+    ReadCommitted,
+    // read locks are held until the transaction commits or aborts
+    #[default]
+    Serializable,
+    // reads take no locks and see the committed data as of the transaction's first read,
+    // see BufferPool::get_snapshot_page. Writes abort the transaction.
+    Snapshot,
+}
+
+// Transactions are identified by their tid alone, the isolation level just rides along
+#[derive(Debug, Clone, Copy)]
 pub struct TransactionId {
     tid: u64,
+    isolation: IsolationLevel,
+}
+
+impl Default for TransactionId {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransactionId {
     pub fn new() -> Self {
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
-        let tid = COUNTER.fetch_add(1, Ordering::SeqCst);
-        TransactionId { tid }
+        TransactionId::with_isolation(IsolationLevel::default())
+    }
+
+    pub fn with_isolation(isolation: IsolationLevel) -> Self {
+        TransactionId {
+            tid: next_tid(),
+            isolation,
+        }
+    }
+
+    // Makes sure no tid up to and including tid is handed out again, e.g. after recovering
+    // a log written by a previous run
+    pub fn skip_past(tid: u64) {
+        NEXT_TID.fetch_max(tid + 1, Ordering::SeqCst);
+    }
+
+    // Makes the tids created on this thread start again from 0. Only this thread is
+    // affected, so tests running alongside it keep getting unique ids, but the ids it
+    // gets may repeat theirs.
+    #[cfg(test)]
+    pub fn reset_for_test() {
+        TEST_NEXT_TID.with(|next| next.set(Some(0)));
+    }
+
+    // Rebuilds the id of an existing transaction, e.g. when reading it back from the log
+    pub fn from_tid(tid: u64) -> Self {
+        TransactionId {
+            tid,
+            isolation: IsolationLevel::default(),
+        }
     }
 
     pub fn get_tid(&self) -> u64 {
         self.tid
     }
+
+    pub fn get_isolation(&self) -> IsolationLevel {
+        self.isolation
+    }
+}
+
+impl PartialEq for TransactionId {
+    fn eq(&self, other: &Self) -> bool {
+        self.tid == other.tid
+    }
+}
+
+impl Eq for TransactionId {}
+
+impl Hash for TransactionId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tid.hash(state);
+    }
+}
+
+impl PartialOrd for TransactionId {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Older transactions have smaller tids, which WAIT-DIE relies on, so tids must only
+// ever increase
+impl Ord for TransactionId {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.tid.cmp(&other.tid)
+    }
+}
+
+// Handle to a transaction started with Database::begin_transaction. Dropping it without
+// committing aborts the transaction, so its locks are released even if the thread using
+// it panics.
+pub struct Transaction<'a> {
+    tid: TransactionId,
+    bp: &'a BufferPool,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn new(tid: TransactionId, bp: &'a BufferPool) -> Self {
+        Transaction { tid, bp }
+    }
+
+    pub fn get_tid(&self) -> TransactionId {
+        self.tid
+    }
+
+    // Commits the transaction, failing if it was already aborted, e.g. by WAIT-DIE
+    pub fn commit(self) -> Result<(), TransactionAbort> {
+        if !self.bp.is_active(self.tid) {
+            return Err(TransactionAbort::new(self.tid));
+        }
+        self.bp.commit_transaction(self.tid);
+        Ok(())
+    }
+
+    // Aborts the transaction, reverting its changes
+    pub fn abort(self) {
+        self.bp.abort_transaction(self.tid);
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if self.bp.is_active(self.tid) {
+            self.bp.abort_transaction(self.tid);
+        }
+        self.bp.release_snapshot(self.tid);
+    }
+}
+
+// Error returned when a transaction is aborted, e.g. by the WAIT-DIE protocol.
+// By the time this is returned the transaction has already been rolled back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionAbort {
+    tid: TransactionId,
+    // whether it was aborted for waiting too long on a lock
+    timed_out: bool,
+    // page it was aborted for failing to read, e.g. because it's corrupt
+    unreadable_page: Option<HeapPageId>,
+}
+
+impl TransactionAbort {
+    pub fn new(tid: TransactionId) -> Self {
+        TransactionAbort {
+            tid,
+            timed_out: false,
+            unreadable_page: None,
+        }
+    }
+
+    pub fn timed_out(tid: TransactionId) -> Self {
+        TransactionAbort {
+            tid,
+            timed_out: true,
+            unreadable_page: None,
+        }
+    }
+
+    pub fn unreadable_page(tid: TransactionId, pid: HeapPageId) -> Self {
+        TransactionAbort {
+            tid,
+            timed_out: false,
+            unreadable_page: Some(pid),
+        }
+    }
+
+    pub fn get_tid(&self) -> TransactionId {
+        self.tid
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        self.timed_out
+    }
+
+    pub fn get_unreadable_page(&self) -> Option<HeapPageId> {
+        self.unreadable_page
+    }
+
+    // Whether running the transaction again could succeed. A page that couldn't be read
+    // won't read any better the next time.
+    pub fn is_retryable(&self) -> bool {
+        self.unreadable_page.is_none()
+    }
+}
+
+impl Display for TransactionAbort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(pid) = self.unreadable_page {
+            write!(
+                f,
+                "Transaction {} aborted because page {} of table {} couldn't be read",
+                self.tid.get_tid(),
+                pid.get_page_number(),
+                pid.get_table_id()
+            )
+        } else if self.timed_out {
+            write!(
+                f,
+                "Transaction {} aborted after timing out waiting for a lock",
+                self.tid.get_tid()
+            )
+        } else {
+            write!(f, "Transaction {} aborted", self.tid.get_tid())
+        }
+    }
+}
+
+impl From<TransactionAbort> for String {
+    fn from(abort: TransactionAbort) -> Self {
+        abort.to_string()
+    }
+}
+
+// Error from an operation that fails either because its transaction was aborted, which
+// is worth retrying, or for a reason of its own, e.g. a tuple that doesn't fit the schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbError {
+    Abort(TransactionAbort),
+    Other(String),
+}
+
+impl DbError {
+    // The abort, if this error is one
+    pub fn as_abort(&self) -> Option<&TransactionAbort> {
+        match self {
+            DbError::Abort(abort) => Some(abort),
+            DbError::Other(_) => None,
+        }
+    }
+}
+
+impl Display for DbError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::Abort(abort) => abort.fmt(f),
+            DbError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<TransactionAbort> for DbError {
+    fn from(abort: TransactionAbort) -> Self {
+        DbError::Abort(abort)
+    }
+}
+
+impl From<String> for DbError {
+    fn from(message: String) -> Self {
+        DbError::Other(message)
+    }
+}
+
+impl From<DbError> for String {
+    fn from(error: DbError) -> Self {
+        error.to_string()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database;
+    use crate::fields::{FieldVal, IntField};
+    use crate::heap_page::{HeapPageId, Permission};
+    use crate::tuple::{Tuple, TupleDesc};
+    use crate::types::Type;
+    use uuid::Uuid;
     #[test]
     fn test_transaction_id_increments() {
         let tid1 = TransactionId::new();
@@ -29,9 +314,82 @@ mod tests {
 
     #[test]
     fn test_transaction_id_get_tid() {
+        TransactionId::reset_for_test();
         let tid1 = TransactionId::new();
         let tid2 = TransactionId::new();
         assert_eq!(tid1.get_tid(), 0);
         assert_eq!(tid2.get_tid(), 1);
     }
+
+    #[test]
+    fn test_skip_past() {
+        let tid = TransactionId::new();
+        TransactionId::skip_past(tid.get_tid() + 100);
+        assert!(TransactionId::new().get_tid() > tid.get_tid() + 100);
+    }
+
+    #[test]
+    fn test_dropped_transaction_aborts() {
+        let db = database::get_global_db();
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let name = format!("txn_{}", Uuid::new_v4());
+        let table_id = db.get_catalog().create_temp_table(name, td.clone());
+        let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+        let int_tuple = |i| Tuple::new(vec![FieldVal::IntField(IntField::new(i))], &td);
+
+        let txn = db.begin_transaction();
+        table.add_tuple(txn.get_tid(), int_tuple(1)).unwrap();
+        txn.commit().unwrap();
+        let tid = {
+            let txn = db.begin_transaction();
+            table.add_tuple(txn.get_tid(), int_tuple(2)).unwrap();
+            assert!(db.get_buffer_pool().is_active(txn.get_tid()));
+            txn.get_tid()
+        };
+        assert!(!db.get_buffer_pool().is_active(tid));
+
+        let txn = db.begin_transaction();
+        let pid = HeapPageId::new(table_id, 0);
+        let page = db
+            .get_buffer_pool()
+            .get_page(txn.get_tid(), pid, Permission::Read)
+            .unwrap();
+        let ids: Vec<i32> = page
+            .read()
+            .unwrap()
+            .iter()
+            .map(|t| t.get_int(0).unwrap())
+            .collect();
+        assert_eq!(ids, vec![1]);
+        txn.abort();
+    }
+
+    #[test]
+    fn test_panicking_transaction_releases_locks() {
+        let db = database::get_global_db();
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let name = format!("txn_{}", Uuid::new_v4());
+        let table_id = db.get_catalog().create_temp_table(name, td.clone());
+        let pid = HeapPageId::new(table_id, 0);
+
+        let result = std::thread::spawn(move || {
+            let db = database::get_global_db();
+            let txn = db.begin_transaction();
+            let table = db.get_catalog().get_table_from_id(table_id).unwrap();
+            let tuple = Tuple::new(vec![FieldVal::IntField(IntField::new(1))], &td);
+            table.add_tuple(txn.get_tid(), tuple).unwrap();
+            panic!("transaction {} panicked", txn.get_tid().get_tid());
+        })
+        .join();
+        assert!(result.is_err());
+
+        // the younger transaction would die under WAIT-DIE if the lock were still held
+        let txn = db.begin_transaction();
+        let page = db
+            .get_buffer_pool()
+            .get_page(txn.get_tid(), pid, Permission::Write)
+            .unwrap();
+        assert_eq!(page.read().unwrap().iter().count(), 0);
+        txn.commit().unwrap();
+    }
 }
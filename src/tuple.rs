@@ -1,8 +1,8 @@
 use std::fmt::{Display, Formatter};
 
-use crate::fields::{Field, FieldVal};
+use crate::fields::{BoolField, Field, FieldVal, FloatField, IntField, LongField, StringField};
 use crate::heap_page::HeapPageId;
-use crate::types::Type;
+use crate::types::{Type, STRING_SIZE};
 
 // Reference to a tuple on a page of a table
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -30,11 +30,56 @@ impl RecordId {
 pub struct TupleDesc {
     types: Vec<Type>,
     fields: Vec<String>,
+    // whether the ith field may hold a NULL value
+    nullable: Vec<bool>,
+    // max bytes stored for the ith field if it's a string
+    string_lens: Vec<usize>,
+    // value used for the ith field when a row doesn't give one, if any
+    defaults: Vec<Option<FieldVal>>,
 }
 
 impl TupleDesc {
     pub fn new(types: Vec<Type>, fields: Vec<String>) -> Self {
-        TupleDesc { types, fields }
+        let nullable = vec![false; types.len()];
+        TupleDesc::new_with_nullable(types, fields, nullable)
+    }
+
+    pub fn new_with_nullable(types: Vec<Type>, fields: Vec<String>, nullable: Vec<bool>) -> Self {
+        let string_lens = vec![STRING_SIZE; types.len()];
+        let defaults = vec![None; types.len()];
+        TupleDesc {
+            types,
+            fields,
+            nullable,
+            string_lens,
+            defaults,
+        }
+    }
+
+    // Caps the ith field at len bytes of string instead of STRING_SIZE
+    pub fn set_string_len(&mut self, i: usize, len: usize) {
+        self.string_lens[i] = len;
+    }
+
+    // Returns the max number of bytes a string in the ith field can hold
+    pub fn get_string_len(&self, i: usize) -> usize {
+        self.string_lens.get(i).copied().unwrap_or(STRING_SIZE)
+    }
+
+    // Makes value the ith field's default, failing if the field couldn't store it
+    pub fn set_default(&mut self, i: usize, value: FieldVal) -> Result<(), String> {
+        self.validate_field(i, &value)?;
+        self.defaults[i] = Some(value);
+        Ok(())
+    }
+
+    pub fn get_default(&self, i: usize) -> Option<&FieldVal> {
+        self.defaults.get(i)?.as_ref()
+    }
+
+    // Returns the size (in bytes) of the ith field on disk
+    pub fn get_field_len(&self, i: usize) -> usize {
+        self.types[i].get_len_with(self.get_string_len(i))
     }
 
     pub fn combine(td1: &TupleDesc, td2: &TupleDesc) -> TupleDesc {
@@ -43,7 +88,19 @@ impl TupleDesc {
         types.extend(td2.types.clone());
         let mut field_names = td1.fields.clone();
         field_names.extend(td2.fields.clone());
-        TupleDesc::new(types, field_names)
+        let mut nullable = td1.nullable.clone();
+        nullable.extend(td2.nullable.clone());
+        let mut string_lens = td1.string_lens.clone();
+        string_lens.extend(td2.string_lens.clone());
+        let mut defaults = td1.defaults.clone();
+        defaults.extend(td2.defaults.clone());
+        TupleDesc {
+            types,
+            fields: field_names,
+            nullable,
+            string_lens,
+            defaults,
+        }
     }
 
     pub fn get_num_fields(&self) -> usize {
@@ -55,9 +112,73 @@ impl TupleDesc {
         self.fields.get(i)
     }
 
-    // Returns the (possibly null) index of the field with a given name
+    // Returns the (possibly null) index of the field with a given name. A name without a
+    // table prefix also finds a prefixed field, e.g. "id" finds "employees.id", as long as
+    // only one field has that name.
     pub fn name_to_id(&self, name: &str) -> Option<usize> {
-        self.fields.iter().position(|n| n == name)
+        if let Some(i) = self.fields.iter().position(|n| n == name) {
+            return Some(i);
+        }
+        let suffix = format!(".{}", name);
+        let mut matches = self
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.ends_with(&suffix));
+        match (matches.next(), matches.next()) {
+            (Some((i, _)), None) => Some(i),
+            _ => None,
+        }
+    }
+
+    // Like name_to_id, but tells a missing name apart from one that matches several
+    // prefixed fields, e.g. "id" after joining two tables that both have one
+    pub fn resolve_name(&self, name: &str) -> Result<usize, String> {
+        if let Some(i) = self.name_to_id(name) {
+            return Ok(i);
+        }
+        let suffix = format!(".{}", name);
+        match self.fields.iter().any(|n| n.ends_with(&suffix)) {
+            true => Err(format!("ambiguous field name {}", name)),
+            false => Err(format!("no field named {}", name)),
+        }
+    }
+
+    // Returns a copy with every field not already prefixed renamed to "prefix.field"
+    pub fn with_prefix(&self, prefix: &str) -> TupleDesc {
+        let mut td = self.clone();
+        for field in td.fields.iter_mut() {
+            if !field.contains('.') {
+                *field = format!("{}.{}", prefix, field);
+            }
+        }
+        td
+    }
+
+    // The schema of tuples projected to the (source field, output name) specs, along with
+    // the index of each source field
+    pub fn project_as(
+        &self,
+        specs: &[(String, String)],
+    ) -> Result<(TupleDesc, Vec<usize>), String> {
+        let mut ids = Vec::new();
+        for (field, _) in specs {
+            ids.push(
+                self.name_to_id(field)
+                    .ok_or(format!("unknown field {}", field))?,
+            );
+        }
+
+        let mut td = TupleDesc::new_with_nullable(
+            ids.iter().map(|i| self.types[*i].clone()).collect(),
+            specs.iter().map(|(_, name)| name.clone()).collect(),
+            ids.iter().map(|i| self.is_nullable(*i)).collect(),
+        );
+        for (j, i) in ids.iter().enumerate() {
+            td.set_string_len(j, self.get_string_len(*i));
+            td.defaults[j] = self.defaults[*i].clone();
+        }
+        Ok((td, ids))
     }
 
     // Returns the type of the ith field of this TupleDesc.
@@ -65,9 +186,111 @@ impl TupleDesc {
         self.types.get(i)
     }
 
+    // Returns whether the ith field of this TupleDesc may be NULL.
+    pub fn is_nullable(&self, i: usize) -> bool {
+        self.nullable.get(i).copied().unwrap_or(false)
+    }
+
+    // Checks that the other TupleDesc has the same number and types of fields
+    pub fn matches_types(&self, other: &TupleDesc) -> bool {
+        self.types == other.types
+    }
+
+    // Parses one raw string per column into a value of the column's type, e.g. from a CSV
+    // row. Empty strings and missing trailing columns take the column's default, and
+    // empty strings are NULL for columns without one; use validate_fields to check the
+    // result can be stored.
+    pub fn coerce(&self, raw: &[String]) -> Result<Vec<FieldVal>, String> {
+        let all_defaulted = (raw.len()..self.types.len()).all(|i| self.get_default(i).is_some());
+        if raw.len() > self.types.len() || !all_defaulted {
+            return Err(format!(
+                "expected {} fields but got {}",
+                self.types.len(),
+                raw.len()
+            ));
+        }
+        let mut fields = vec![];
+        for i in 0..self.types.len() {
+            let value = raw.get(i).map_or("", |v| v.as_str());
+            if let (true, Some(default)) = (value.is_empty(), self.get_default(i)) {
+                fields.push(default.clone());
+                continue;
+            }
+            fields.push(self.parse_field(i, value)?);
+        }
+        Ok(fields)
+    }
+
+    fn parse_field(&self, i: usize, raw: &str) -> Result<FieldVal, String> {
+        coerce_value(raw, &self.types[i]).ok_or(format!(
+            "could not parse {:?} as {:?} for field {}",
+            raw, self.types[i], self.fields[i]
+        ))
+    }
+
+    // Parses a raw string into a value for the ith field, e.g. a default from a schema,
+    // failing if the field couldn't store it
+    pub fn coerce_field(&self, i: usize, raw: &str) -> Result<FieldVal, String> {
+        let field = self.parse_field(i, raw)?;
+        self.validate_field(i, &field)?;
+        Ok(field)
+    }
+
+    // Checks that the given field values line up with the types of this TupleDesc
+    pub fn validate_fields(&self, fields: &[FieldVal]) -> Result<(), String> {
+        if fields.len() != self.types.len() {
+            return Err(format!(
+                "expected {} fields but got {}",
+                self.types.len(),
+                fields.len()
+            ));
+        }
+        for (i, field) in fields.iter().enumerate() {
+            self.validate_field(i, field)?;
+        }
+        Ok(())
+    }
+
+    // Checks that the ith field could store the value
+    pub fn validate_field(&self, i: usize, field: &FieldVal) -> Result<(), String> {
+        match field.get_type() {
+            Some(t) if t != self.types[i] => Err(format!(
+                "field {} expected {:?} but got {:?}",
+                self.fields[i], self.types[i], t
+            )),
+            None if !self.is_nullable(i) => {
+                Err(format!("field {} is not nullable", self.fields[i]))
+            }
+            _ if field
+                .clone()
+                .into_string()
+                .is_some_and(|s| s.get_value().len() > self.get_string_len(i)) =>
+            {
+                Err(format!(
+                    "field {} holds at most {} bytes",
+                    self.fields[i],
+                    self.get_string_len(i)
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Return the size (in bytes) of the null bitmap prefixed to each tuple.
+    // Tables without nullable columns don't reserve a bitmap at all.
+    pub fn get_null_bitmap_size(&self) -> usize {
+        if self.nullable.iter().any(|n| *n) {
+            self.types.len().div_ceil(8)
+        } else {
+            0
+        }
+    }
+
     // Return the size (in bytes) of tuples corresponding to this TupleDesc.
     pub fn get_size(&self) -> usize {
-        self.types.iter().fold(0, |acc, t| acc + t.get_len())
+        (0..self.types.len()).fold(self.get_null_bitmap_size(), |acc, i| {
+            acc + self.get_field_len(i)
+        })
     }
 }
 
@@ -93,6 +316,22 @@ impl Display for Tuple {
                     self.td.fields[i],
                     string_field.get_value()
                 )),
+                FieldVal::FloatField(float_field) => s.push_str(&format!(
+                    "{}: {}",
+                    self.td.fields[i],
+                    float_field.get_value()
+                )),
+                FieldVal::BoolField(bool_field) => s.push_str(&format!(
+                    "{}: {}",
+                    self.td.fields[i],
+                    bool_field.get_value()
+                )),
+                FieldVal::LongField(long_field) => s.push_str(&format!(
+                    "{}: {}",
+                    self.td.fields[i],
+                    long_field.get_value()
+                )),
+                FieldVal::Null => s.push_str(&format!("{}: NULL", self.td.fields[i])),
             }
             if i != self.fields.len() - 1 {
                 s.push_str(", ");
@@ -115,6 +354,12 @@ impl Tuple {
         &self.td
     }
 
+    // Gets the number of bytes the tuple takes up in a page slot, the same for every tuple
+    // with its TupleDesc
+    pub fn size_bytes(&self) -> usize {
+        self.td.get_size()
+    }
+
     pub fn get_record_id(&self) -> RecordId {
         self.rid
     }
@@ -123,34 +368,132 @@ impl Tuple {
         self.rid = rid;
     }
 
+    // Compares the schema and values of the tuples, ignoring where they're stored
+    pub fn value_eq(&self, other: &Tuple) -> bool {
+        self.td == other.td && self.fields == other.fields
+    }
+
     pub fn get_field(&self, i: usize) -> Option<&FieldVal> {
         self.fields.get(i)
     }
 
+    // Returns the ith field if it's an int
+    pub fn get_int(&self, i: usize) -> Option<i32> {
+        match self.fields.get(i)? {
+            FieldVal::IntField(int_field) => Some(int_field.get_value()),
+            _ => None,
+        }
+    }
+
+    // Returns the ith field if it's a string
+    pub fn get_string(&self, i: usize) -> Option<String> {
+        match self.fields.get(i)? {
+            FieldVal::StringField(string_field) => Some(string_field.get_value()),
+            _ => None,
+        }
+    }
+
+    // Returns the named field if it's an int
+    pub fn get_int_by_name(&self, name: &str) -> Option<i32> {
+        self.get_int(self.td.name_to_id(name)?)
+    }
+
+    // Returns the named field if it's a string
+    pub fn get_string_by_name(&self, name: &str) -> Option<String> {
+        self.get_string(self.td.name_to_id(name)?)
+    }
+
+    // Keeps only the given fields, in the order they're listed, failing on an unknown name.
+    // Nullability and string lengths carry over to the new TupleDesc.
+    pub fn project(&self, fields: &[String]) -> Result<Tuple, String> {
+        let specs: Vec<(String, String)> = fields.iter().map(|f| (f.clone(), f.clone())).collect();
+        self.project_as(&specs)
+    }
+
+    // Projects the (source field, output name) specs, renaming each field in the result
+    pub fn project_as(&self, specs: &[(String, String)]) -> Result<Tuple, String> {
+        let (td, ids) = self.td.project_as(specs)?;
+        let fields = ids.iter().map(|i| self.fields[*i].clone()).collect();
+        Ok(Tuple::new(fields, &td))
+    }
+
+    // Renders each field's value as it appears in a printed table, NULLs as NULL
+    pub fn format_values(&self) -> Vec<String> {
+        self.fields
+            .iter()
+            .map(|field| match field {
+                FieldVal::IntField(int_field) => int_field.get_value().to_string(),
+                FieldVal::StringField(string_field) => string_field.get_value(),
+                FieldVal::FloatField(float_field) => float_field.get_value().to_string(),
+                FieldVal::BoolField(bool_field) => bool_field.get_value().to_string(),
+                FieldVal::LongField(long_field) => long_field.get_value().to_string(),
+                FieldVal::Null => "NULL".to_string(),
+            })
+            .collect()
+    }
+
+    // Renders the values as a row of columns separated by " | ", padding the ith value to
+    // widths[i] characters so rows printed with the same widths line up
+    pub fn format_row(&self, widths: &[usize]) -> String {
+        format_columns(&self.format_values(), widths)
+    }
+
     pub fn set_field(&mut self, i: usize, field: FieldVal) {
         self.fields[i] = field;
     }
 
     pub fn serialize(&self) -> Vec<u8> {
-        let mut bytes = vec![];
-        for field in self.fields.iter() {
+        // null bitmap, where bit i is set if the ith field is NULL
+        let mut bytes = vec![0; self.td.get_null_bitmap_size()];
+        for (i, field) in self.fields.iter().enumerate() {
             match field {
                 FieldVal::IntField(int_field) => bytes.extend(int_field.serialize()),
-                FieldVal::StringField(string_field) => bytes.extend(string_field.serialize()),
+                FieldVal::StringField(string_field) => {
+                    bytes.extend(string_field.serialize_with_len(self.td.get_string_len(i)))
+                }
+                FieldVal::FloatField(float_field) => bytes.extend(float_field.serialize()),
+                FieldVal::BoolField(bool_field) => bytes.extend(bool_field.serialize()),
+                FieldVal::LongField(long_field) => bytes.extend(long_field.serialize()),
+                FieldVal::Null => {
+                    // pages validate tuples before storing them, see validate_fields
+                    debug_assert!(
+                        self.td.is_nullable(i),
+                        "NULL in non-nullable field {}",
+                        self.td.fields[i]
+                    );
+                    if self.td.is_nullable(i) {
+                        bytes[i / 8] |= 1 << (i % 8);
+                    }
+                    bytes.extend(vec![0; self.td.get_field_len(i)]);
+                }
             }
         }
         bytes
     }
 
-    pub fn deserialize(bytes: &[u8], td: &TupleDesc) -> Self {
-        let mut offset = 0;
+    pub fn deserialize(bytes: &[u8], td: &TupleDesc) -> Result<Self, String> {
+        if bytes.len() < td.get_size() {
+            return Err(format!(
+                "tuple needs {} bytes but only {} were given",
+                td.get_size(),
+                bytes.len()
+            ));
+        }
+        let bitmap_size = td.get_null_bitmap_size();
+        let bitmap = &bytes[..bitmap_size];
+        let mut offset = bitmap_size;
         let mut fields = vec![];
-        for t in td.types.iter() {
-            let field = t.parse(&bytes[offset..]).unwrap();
-            offset += t.get_len();
+        for (i, t) in td.types.iter().enumerate() {
+            let is_null = bitmap_size > 0 && bitmap[i / 8] & (1 << (i % 8)) != 0;
+            let field = if is_null {
+                FieldVal::Null
+            } else {
+                t.parse_with_len(&bytes[offset..], td.get_string_len(i))?
+            };
+            offset += td.get_field_len(i);
             fields.push(field);
         }
-        Tuple::new(fields, td)
+        Ok(Tuple::new(fields, td))
     }
 
     pub fn get_fields(&self) -> Vec<FieldVal> {
@@ -158,11 +501,101 @@ impl Tuple {
     }
 }
 
+// Pads each value to its column's width, leaving no trailing spaces on the last column
+pub fn format_columns(values: &[String], widths: &[usize]) -> String {
+    let columns: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            format!(
+                "{:<width$}",
+                value,
+                width = widths.get(i).copied().unwrap_or(0)
+            )
+        })
+        .collect();
+    columns.join(" | ").trim_end().to_string()
+}
+
+// Parses a raw string into a value of the given type, with an empty string being NULL
+fn coerce_value(value: &str, field_type: &Type) -> Option<FieldVal> {
+    if value.is_empty() {
+        return Some(FieldVal::Null);
+    }
+    let field = match field_type {
+        Type::IntType => FieldVal::IntField(IntField::new(value.trim().parse().ok()?)),
+        Type::FloatType => FieldVal::FloatField(FloatField::new(value.trim().parse().ok()?)),
+        Type::BoolType => FieldVal::BoolField(BoolField::new(value.trim().parse().ok()?)),
+        Type::LongType => FieldVal::LongField(LongField::new(value.trim().parse().ok()?)),
+        Type::StringType => {
+            FieldVal::StringField(StringField::new(value.to_string(), value.len() as u32))
+        }
+    };
+    Some(field)
+}
+
+// Builds a tuple by field name, checking it against the TupleDesc
+pub struct TupleBuilder<'a> {
+    td: &'a TupleDesc,
+    fields: Vec<Option<FieldVal>>,
+    // names that were set but aren't in the TupleDesc
+    unknown: Vec<String>,
+}
+
+impl<'a> TupleBuilder<'a> {
+    pub fn new(td: &'a TupleDesc) -> Self {
+        TupleBuilder {
+            td,
+            fields: vec![None; td.get_num_fields()],
+            unknown: vec![],
+        }
+    }
+
+    pub fn set(mut self, field_name: &str, value: FieldVal) -> Self {
+        match self.td.name_to_id(field_name) {
+            Some(i) => self.fields[i] = Some(value),
+            None => self.unknown.push(field_name.to_string()),
+        }
+        self
+    }
+
+    // Builds the tuple, failing on unknown fields, fields that weren't set or values of
+    // the wrong type. Fields that weren't set take their default, or are NULL if they're
+    // nullable and have none.
+    pub fn build(self) -> Result<Tuple, String> {
+        if !self.unknown.is_empty() {
+            return Err(format!("unknown fields: {}", self.unknown.join(", ")));
+        }
+        let missing: Vec<&str> = (0..self.fields.len())
+            .filter(|i| {
+                self.fields[*i].is_none()
+                    && self.td.get_default(*i).is_none()
+                    && !self.td.is_nullable(*i)
+            })
+            .map(|i| self.td.fields[i].as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("missing fields: {}", missing.join(", ")));
+        }
+        let fields: Vec<FieldVal> = self
+            .fields
+            .into_iter()
+            .enumerate()
+            .map(|(i, field)| {
+                field.unwrap_or_else(|| self.td.get_default(i).cloned().unwrap_or(FieldVal::Null))
+            })
+            .collect();
+        self.td.validate_fields(&fields)?;
+        Ok(Tuple::new(fields, self.td))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::fields::{IntField, StringField};
-    use crate::types::Type;
+    use crate::buffer_pool::PAGE_SIZE;
+    use crate::fields::{BoolField, FloatField, IntField, LongField, StringField};
+    use crate::heap_page::{HeapPage, CHECKSUM_SIZE};
 
     #[test]
     fn test_tuple_desc_combine() {
@@ -205,7 +638,337 @@ mod tests {
             &td,
         );
         let bytes = tuple.serialize();
-        let tuple2 = Tuple::deserialize(&bytes, &td);
+        let tuple2 = Tuple::deserialize(&bytes, &td).unwrap();
+        assert_eq!(tuple, tuple2);
+    }
+
+    #[test]
+    fn test_tuple_float_serialize_deserialize() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::FloatType],
+            vec!["id".to_string(), "amount".to_string()],
+        );
+        let tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::FloatField(FloatField::new(19.99)),
+            ],
+            &td,
+        );
+        assert_eq!(td.get_size(), 12);
+        let tuple2 = Tuple::deserialize(&tuple.serialize(), &td).unwrap();
         assert_eq!(tuple, tuple2);
+        assert_eq!(format!("{}", tuple2), "{id: 1, amount: 19.99}");
+    }
+
+    #[test]
+    fn test_tuple_bool_serialize_deserialize() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::BoolType],
+            vec!["id".to_string(), "active".to_string()],
+        );
+        let tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::BoolField(BoolField::new(true)),
+            ],
+            &td,
+        );
+        assert_eq!(td.get_size(), 5);
+        let tuple2 = Tuple::deserialize(&tuple.serialize(), &td).unwrap();
+        assert_eq!(tuple, tuple2);
+        assert_eq!(format!("{}", tuple2), "{id: 1, active: true}");
+    }
+
+    #[test]
+    fn test_tuple_long_serialize_deserialize() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::LongType],
+            vec!["id".to_string(), "views".to_string()],
+        );
+        let tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::LongField(LongField::new(i64::MAX)),
+            ],
+            &td,
+        );
+        assert_eq!(td.get_size(), 12);
+        let tuple2 = Tuple::deserialize(&tuple.serialize(), &td).unwrap();
+        assert_eq!(tuple, tuple2);
+        assert_eq!(
+            format!("{}", tuple2),
+            format!("{{id: 1, views: {}}}", i64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_project() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::StringType],
+            vec!["id".to_string(), "name".to_string()],
+        );
+        let tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::StringField(StringField::new("Alice".to_string(), 5)),
+            ],
+            &td,
+        );
+        let projected = tuple.project(&["name".to_string()]).unwrap();
+        assert_eq!(projected.get_tuple_desc().get_num_fields(), 1);
+        assert_eq!(
+            projected.get_tuple_desc().get_field_type(0),
+            Some(&Type::StringType)
+        );
+        assert_eq!(projected.get_string(0), Some("Alice".to_string()));
+        assert!(tuple.project(&["age".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_value_eq_ignores_record_id() {
+        let td = TupleDesc::new(vec![Type::IntType], vec!["id".to_string()]);
+        let mut a = Tuple::new(vec![FieldVal::IntField(IntField::new(1))], &td);
+        let mut b = a.clone();
+        a.set_record_id(RecordId::new(HeapPageId::new(0, 0), 0));
+        b.set_record_id(RecordId::new(HeapPageId::new(0, 1), 3));
+        assert_ne!(a, b);
+        assert!(a.value_eq(&b));
+        let c = Tuple::new(vec![FieldVal::IntField(IntField::new(2))], &td);
+        assert!(!a.value_eq(&c));
+    }
+
+    #[test]
+    fn test_coerce() {
+        let td = TupleDesc::new_with_nullable(
+            vec![Type::IntType, Type::StringType, Type::IntType],
+            vec!["id".to_string(), "name".to_string(), "age".to_string()],
+            vec![false, false, true],
+        );
+        let raw = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            td.coerce(&raw(&[" 7", "Alice", ""])),
+            Ok(vec![
+                FieldVal::IntField(IntField::new(7)),
+                FieldVal::StringField(StringField::new("Alice".to_string(), 5)),
+                FieldVal::Null,
+            ])
+        );
+        assert_eq!(
+            td.coerce(&raw(&["seven", "Alice", "3"])),
+            Err("could not parse \"seven\" as IntType for field id".to_string())
+        );
+        assert!(td.coerce(&raw(&["7", "Alice"])).is_err());
+
+        // a missing or empty value takes the column's default
+        let mut td = td;
+        td.set_default(2, FieldVal::IntField(IntField::new(30)))
+            .unwrap();
+        assert!(td
+            .set_default(1, FieldVal::IntField(IntField::new(1)))
+            .is_err());
+        let age = FieldVal::IntField(IntField::new(30));
+        assert_eq!(td.coerce(&raw(&["7", "Alice"])).unwrap()[2], age);
+        assert_eq!(td.coerce(&raw(&["7", "Alice", ""])).unwrap()[2], age);
+        assert!(td.coerce(&raw(&["7"])).is_err());
+        assert_eq!(
+            td.coerce_field(2, "5"),
+            Ok(FieldVal::IntField(IntField::new(5)))
+        );
+        assert!(td.coerce_field(0, "").is_err());
+    }
+
+    #[test]
+    fn test_tuple_null_serialize_deserialize() {
+        let td = TupleDesc::new_with_nullable(
+            vec![Type::IntType, Type::IntType, Type::StringType],
+            vec!["id".to_string(), "age".to_string(), "name".to_string()],
+            vec![false, true, true],
+        );
+        // one byte of null bitmap in front of the fields
+        assert_eq!(td.get_size(), 269);
+        let null_tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::Null,
+                FieldVal::Null,
+            ],
+            &td,
+        );
+        let zero_tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::IntField(IntField::new(0)),
+                FieldVal::StringField(StringField::new("".to_string(), 0)),
+            ],
+            &td,
+        );
+        assert_ne!(null_tuple.serialize(), zero_tuple.serialize());
+        assert_eq!(
+            Tuple::deserialize(&null_tuple.serialize(), &td).unwrap(),
+            null_tuple
+        );
+        assert_eq!(
+            Tuple::deserialize(&zero_tuple.serialize(), &td).unwrap(),
+            zero_tuple
+        );
+        assert_eq!(format!("{}", null_tuple), "{id: 1, age: NULL, name: NULL}");
+    }
+
+    #[test]
+    fn test_size_bytes() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::StringType],
+            vec!["id".to_string(), "name".to_string()],
+        );
+        let tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::StringField(StringField::new("a".to_string(), 1)),
+            ],
+            &td,
+        );
+        // 4 bytes for the int, 4 for the string's length and STRING_SIZE for its bytes
+        assert_eq!(tuple.size_bytes(), 4 + 4 + STRING_SIZE);
+        assert_eq!(tuple.size_bytes(), tuple.serialize().len());
+    }
+
+    #[test]
+    fn test_format_row() {
+        let td = TupleDesc::new_with_nullable(
+            vec![Type::IntType, Type::StringType, Type::BoolType],
+            vec!["id".to_string(), "name".to_string(), "ok".to_string()],
+            vec![false, true, false],
+        );
+        let tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(7)),
+                FieldVal::Null,
+                FieldVal::BoolField(BoolField::new(true)),
+            ],
+            &td,
+        );
+        assert_eq!(tuple.format_row(&[3, 6, 5]), "7   | NULL   | true");
+        // values longer than their width aren't cut off
+        assert_eq!(tuple.format_row(&[0, 0, 0]), "7 | NULL | true");
+    }
+
+    #[test]
+    fn test_tuple_deserialize_corrupt_bytes() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::StringType],
+            vec!["int".to_string(), "string".to_string()],
+        );
+        let tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::StringField(StringField::new("hello".to_string(), 5)),
+            ],
+            &td,
+        );
+        let mut bytes = tuple.serialize();
+        assert!(Tuple::deserialize(&bytes[..6], &td).is_err());
+        let err = Tuple::deserialize(&bytes[..1], &td).unwrap_err();
+        assert!(err.contains(&td.get_size().to_string()));
+        assert!(Tuple::deserialize(&[], &td).is_err());
+        bytes[8] = 0xFF;
+        assert!(Tuple::deserialize(&bytes, &td).is_err());
+    }
+
+    #[test]
+    fn test_short_string_column() {
+        let mut td = TupleDesc::new(
+            vec![Type::IntType, Type::StringType],
+            vec!["id".to_string(), "name".to_string()],
+        );
+        let default_size = td.get_size();
+        td.set_string_len(1, 16);
+        assert_eq!(td.get_size(), 4 + 4 + 16);
+        let tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                FieldVal::StringField(StringField::new("hello".to_string(), 5)),
+            ],
+            &td,
+        );
+        assert_eq!(tuple.serialize().len(), td.get_size());
+        assert_eq!(Tuple::deserialize(&tuple.serialize(), &td).unwrap(), tuple);
+        let too_long = FieldVal::StringField(StringField::new("a".repeat(17), 17));
+        assert!(td
+            .validate_fields(&[FieldVal::IntField(IntField::new(1)), too_long])
+            .is_err());
+
+        // the smaller column fits more tuples on a page
+        let pid = HeapPageId::new(0, 0);
+        let short_page = HeapPage::new(pid, vec![0; PAGE_SIZE], td);
+        let usable = PAGE_SIZE - CHECKSUM_SIZE;
+        assert_eq!(short_page.get_num_slots(), (usable * 8) / (24 * 8 + 1));
+        assert!(short_page.get_num_slots() > (usable * 8) / (default_size * 8 + 1));
+    }
+
+    #[test]
+    fn test_typed_accessors() {
+        let td = TupleDesc::new(
+            vec![Type::IntType, Type::StringType],
+            vec!["id".to_string(), "name".to_string()],
+        );
+        let tuple = Tuple::new(
+            vec![
+                FieldVal::IntField(IntField::new(7)),
+                FieldVal::StringField(StringField::new("Alice".to_string(), 5)),
+            ],
+            &td,
+        );
+        assert_eq!(tuple.get_int(0), Some(7));
+        assert_eq!(tuple.get_string(1), Some("Alice".to_string()));
+        assert_eq!(tuple.get_int_by_name("id"), Some(7));
+        assert_eq!(tuple.get_string_by_name("name"), Some("Alice".to_string()));
+        // wrong types
+        assert_eq!(tuple.get_int(1), None);
+        assert_eq!(tuple.get_string(0), None);
+        assert_eq!(tuple.get_int_by_name("name"), None);
+        // out of range or unknown
+        assert_eq!(tuple.get_int(2), None);
+        assert_eq!(tuple.get_string(5), None);
+        assert_eq!(tuple.get_int_by_name("missing"), None);
+    }
+
+    #[test]
+    fn test_tuple_builder() {
+        let td = TupleDesc::new_with_nullable(
+            vec![Type::IntType, Type::StringType, Type::IntType],
+            vec!["id".to_string(), "name".to_string(), "age".to_string()],
+            vec![false, false, true],
+        );
+        let name = FieldVal::StringField(StringField::new("Bob".to_string(), 3));
+        let tuple = TupleBuilder::new(&td)
+            .set("name", name.clone())
+            .set("id", FieldVal::IntField(IntField::new(1)))
+            .build()
+            .unwrap();
+        assert_eq!(
+            tuple.get_fields(),
+            vec![
+                FieldVal::IntField(IntField::new(1)),
+                name.clone(),
+                FieldVal::Null
+            ]
+        );
+
+        let err = TupleBuilder::new(&td)
+            .set("id", FieldVal::IntField(IntField::new(1)))
+            .build();
+        assert_eq!(err.err(), Some("missing fields: name".to_string()));
+        let err = TupleBuilder::new(&td)
+            .set("id", FieldVal::IntField(IntField::new(1)))
+            .set("name", name.clone())
+            .set("email", name.clone())
+            .build();
+        assert_eq!(err.err(), Some("unknown fields: email".to_string()));
+        let err = TupleBuilder::new(&td)
+            .set("id", name.clone())
+            .set("name", name)
+            .build();
+        assert!(err.is_err());
     }
 }
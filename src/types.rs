@@ -1,12 +1,16 @@
-use crate::fields::{FieldVal, IntField, StringField};
+use crate::fields::{BoolField, FieldVal, FloatField, IntField, LongField, StringField};
 
 pub const STRING_SIZE: usize = 256;
 
-// Only support Int and String types
+// Only support Int, String, Float, Bool and Long types
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Type {
     IntType,
     StringType,
+    FloatType,
+    BoolType,
+    LongType,
 }
 
 impl Type {
@@ -17,29 +21,121 @@ impl Type {
             Type::IntType => 4,
             // 4 bytes for length + STRING_SIZE bytes for string
             Type::StringType => STRING_SIZE + 4,
+            // 8 bytes floats
+            Type::FloatType => 8,
+            // 1 byte bools
+            Type::BoolType => 1,
+            // 8 bytes longs
+            Type::LongType => 8,
         }
     }
 
-    // Parse bytes into a FieldVal
+    // Get the size in bytes of a column of this type, where string columns hold at most
+    // string_len bytes
+    pub fn get_len_with(&self, string_len: usize) -> usize {
+        match self {
+            Type::StringType => string_len + 4,
+            _ => self.get_len(),
+        }
+    }
+
+    // Parse bytes into a FieldVal, failing if the bytes are truncated or corrupt
     pub fn parse(&self, bytes: &[u8]) -> Result<FieldVal, String> {
+        self.parse_with_len(bytes, STRING_SIZE)
+    }
+
+    // Parse bytes from a column whose strings hold at most string_len bytes
+    pub fn parse_with_len(&self, bytes: &[u8], string_len: usize) -> Result<FieldVal, String> {
         match self {
             Type::IntType => {
                 let mut int_bytes = [0; 4];
-                int_bytes.copy_from_slice(&bytes[..4]);
+                int_bytes.copy_from_slice(Self::take(bytes, 4, "Int")?);
                 Ok(FieldVal::IntField(IntField::new(i32::from_be_bytes(
                     int_bytes,
                 ))))
             }
             Type::StringType => {
                 let mut len_bytes = [0; 4];
-                len_bytes.copy_from_slice(&bytes[..4]);
+                len_bytes.copy_from_slice(Self::take(bytes, 4, "String length")?);
                 let len = u32::from_be_bytes(len_bytes);
-                let string_bytes = bytes[4..len as usize + 4].to_vec();
-                Ok(FieldVal::StringField(StringField::new(
-                    String::from_utf8(string_bytes.to_vec()).unwrap(),
-                    len,
-                )))
+                if len as usize > string_len {
+                    return Err(format!(
+                        "String length {} exceeds maximum of {} bytes",
+                        len, string_len
+                    ));
+                }
+                let string_bytes = Self::take(&bytes[4..], len as usize, "String")?;
+                let value = String::from_utf8(string_bytes.to_vec())
+                    .map_err(|e| format!("String is not valid UTF-8: {}", e))?;
+                Ok(FieldVal::StringField(StringField::new(value, len)))
+            }
+            Type::FloatType => {
+                let mut float_bytes = [0; 8];
+                float_bytes.copy_from_slice(Self::take(bytes, 8, "Float")?);
+                Ok(FieldVal::FloatField(FloatField::new(f64::from_be_bytes(
+                    float_bytes,
+                ))))
+            }
+            Type::BoolType => match Self::take(bytes, 1, "Bool")?[0] {
+                0 => Ok(FieldVal::BoolField(BoolField::new(false))),
+                1 => Ok(FieldVal::BoolField(BoolField::new(true))),
+                b => Err(format!("Bool byte must be 0 or 1, found {}", b)),
+            },
+            Type::LongType => {
+                let mut long_bytes = [0; 8];
+                long_bytes.copy_from_slice(Self::take(bytes, 8, "Long")?);
+                Ok(FieldVal::LongField(LongField::new(i64::from_be_bytes(
+                    long_bytes,
+                ))))
             }
         }
     }
+
+    // Returns the first len bytes, or an error naming what was being read
+    fn take<'a>(bytes: &'a [u8], len: usize, what: &str) -> Result<&'a [u8], String> {
+        bytes.get(..len).ok_or(format!(
+            "{} needs {} bytes but only {} are available",
+            what,
+            len,
+            bytes.len()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_truncated_bytes() {
+        assert!(Type::IntType.parse(&[0, 0, 1]).is_err());
+        assert!(Type::FloatType.parse(&[0; 7]).is_err());
+        assert!(Type::StringType.parse(&[0, 0]).is_err());
+        // length claims 5 bytes but only 3 follow
+        assert!(Type::StringType
+            .parse(&[0, 0, 0, 5, b'a', b'b', b'c'])
+            .is_err());
+        assert_eq!(
+            Type::StringType.parse(&[0, 0, 0, 3, b'a', b'b', b'c']),
+            Ok(FieldVal::StringField(StringField::new(
+                "abc".to_string(),
+                3
+            )))
+        );
+    }
+
+    #[test]
+    fn test_parse_bad_string_length() {
+        let mut bytes = vec![0; Type::StringType.get_len()];
+        bytes[..4].copy_from_slice(&(STRING_SIZE as u32 + 1).to_be_bytes());
+        assert!(Type::StringType.parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid_utf8() {
+        let err = Type::StringType
+            .parse(&[0, 0, 0, 2, 0xC3, 0x28])
+            .unwrap_err();
+        assert!(err.contains("UTF-8"));
+    }
 }